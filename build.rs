@@ -0,0 +1,47 @@
+//! Build script
+//!
+//! Compiles `assets/app.rc` into the executable so Explorer and the tray show
+//! the application icon. A missing resource compiler only costs the icon.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=assets/app.rc");
+    println!("cargo:rerun-if-changed=assets/icon.ico");
+
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return;
+    }
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let compiled = if env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc") {
+        let res = out_dir.join("app.res");
+        let status = Command::new("rc.exe")
+            .args(["/nologo", "/fo"])
+            .arg(&res)
+            .arg("assets/app.rc")
+            .status();
+        status.is_ok_and(|s| s.success()).then_some(res)
+    } else {
+        // Cross builds use the target-prefixed mingw tool
+        let windres = if env::var("HOST").is_ok_and(|host| host.contains("windows")) {
+            "windres".to_string()
+        } else {
+            let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+            format!("{}-w64-mingw32-windres", arch)
+        };
+        let obj = out_dir.join("app.o");
+        let status = Command::new(windres)
+            .args(["--input", "assets/app.rc", "--output-format=coff", "--output"])
+            .arg(&obj)
+            .status();
+        status.is_ok_and(|s| s.success()).then_some(obj)
+    };
+
+    match compiled {
+        Some(path) => println!("cargo:rustc-link-arg-bins={}", path.display()),
+        None => println!("cargo:warning=Could not compile resources; building without the icon"),
+    }
+}
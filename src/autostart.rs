@@ -0,0 +1,218 @@
+//! Autostart module
+//!
+//! Registers the executable under the `Run` key of the current user, or of
+//! the machine for all users, so it starts at login with the flags it was
+//! launched with. A Task Scheduler logon task can start it elevated instead,
+//! without a UAC prompt.
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_SUCCESS};
+use windows_sys::Win32::System::Registry::{
+    RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY, HKEY_CURRENT_USER,
+    HKEY_LOCAL_MACHINE, REG_SZ, RRF_RT_REG_SZ,
+};
+use crate::commands;
+use taskbar_hider::config::AutostartScope;
+use taskbar_hider::winapi::Error;
+
+const RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+const VALUE_NAME: &str = "CleanTaskbar";
+const TASK_NAME: &str = "CleanTaskbar";
+
+/// The entry goes under the machine's `Run` key instead of the current user's
+static ALL_USERS: AtomicBool = AtomicBool::new(false);
+/// Whether the logon task exists, once queried; asking `schtasks` takes a moment
+static TASK_REGISTERED: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Sets whether the entry is registered for the current user or for all users
+pub fn set_scope(scope: AutostartScope) {
+    ALL_USERS.store(scope == AutostartScope::AllUsers, Ordering::SeqCst);
+}
+
+/// Returns the registry roots the entry may be under, the configured one first
+///
+/// An all-users entry falls back to the current user without elevation, so
+/// both count then.
+fn roots() -> &'static [HKEY] {
+    if ALL_USERS.load(Ordering::SeqCst) {
+        &[HKEY_LOCAL_MACHINE, HKEY_CURRENT_USER]
+    } else {
+        &[HKEY_CURRENT_USER]
+    }
+}
+
+/// Returns true if the autostart entry exists
+pub fn is_enabled() -> bool {
+    roots().iter().any(|&root| has_entry(root))
+}
+
+/// Returns true if the entry exists under `root`
+fn has_entry(root: HKEY) -> bool {
+    let subkey = wide_string(RUN_KEY);
+    let value = wide_string(VALUE_NAME);
+    unsafe {
+        RegGetValueW(
+            root,
+            subkey.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_SZ,
+            null_mut(),
+            null_mut(),
+            null_mut(),
+        ) == ERROR_SUCCESS
+    }
+}
+
+/// Returns true if the error means the all-users entry needs administrator rights
+pub fn needs_elevation(error: &Error) -> bool {
+    ALL_USERS.load(Ordering::SeqCst) && error.code() == Some(ERROR_ACCESS_DENIED)
+}
+
+/// Builds the `Run` command line, quoting the parts that contain spaces
+pub fn command_line(exe: &str, flags: &[String]) -> String {
+    // Quote the path so spaces in it don't break the command line
+    let mut command = format!("\"{}\"", exe);
+    for flag in flags {
+        if flag.contains(' ') {
+            command.push_str(&format!(" \"{}\"", flag));
+        } else {
+            command.push_str(&format!(" {}", flag));
+        }
+    }
+    command
+}
+
+/// Adds or removes the autostart entry, which starts the hider with `flags`
+pub fn set_enabled(enable: bool, flags: &[String]) -> Result<(), Error> {
+    if !enable {
+        // Removing needs write access, which the machine key may not grant
+        for &root in roots().iter().filter(|&&root| has_entry(root)) {
+            remove_entry(root)?;
+        }
+        return Ok(());
+    }
+    write_entry(roots()[0], flags)
+}
+
+/// Adds the entry for the current user only, when an all-users one can't be written
+pub fn enable_for_current_user(flags: &[String]) -> Result<(), Error> {
+    write_entry(HKEY_CURRENT_USER, flags)
+}
+
+/// Removes the entry under `root`
+fn remove_entry(root: HKEY) -> Result<(), Error> {
+    let subkey = wide_string(RUN_KEY);
+    let value = wide_string(VALUE_NAME);
+    let status = unsafe { RegDeleteKeyValueW(root, subkey.as_ptr(), value.as_ptr()) };
+    if status == ERROR_SUCCESS {
+        Ok(())
+    } else {
+        Err(Error::status("Failed to remove autostart entry", status))
+    }
+}
+
+/// Writes the entry under `root`
+fn write_entry(root: HKEY, flags: &[String]) -> Result<(), Error> {
+    let subkey = wide_string(RUN_KEY);
+    let value = wide_string(VALUE_NAME);
+    let exe =
+        std::env::current_exe().map_err(|_| Error::Failed("Failed to get executable path"))?;
+    let command = wide_string(&command_line(&exe.display().to_string(), flags));
+    let status = unsafe {
+        RegSetKeyValueW(
+            root,
+            subkey.as_ptr(),
+            value.as_ptr(),
+            REG_SZ,
+            command.as_ptr() as *const _,
+            (command.len() * 2) as u32,
+        )
+    };
+    if status == ERROR_SUCCESS {
+        Ok(())
+    } else {
+        Err(Error::status("Failed to write autostart entry", status))
+    }
+}
+
+/// Builds the `schtasks` command line that creates the logon task running `command`
+fn create_task_command(command: &str) -> String {
+    // /TR takes the whole command line as one argument, with its quotes escaped
+    format!(
+        "schtasks.exe /Create /F /TN {} /SC ONLOGON /RL HIGHEST /TR \"{}\"",
+        TASK_NAME,
+        command.replace('"', "\\\"")
+    )
+}
+
+/// Returns true if the elevated logon task exists
+pub fn is_task_registered() -> bool {
+    let Ok(mut registered) = TASK_REGISTERED.lock() else {
+        return false;
+    };
+    *registered.get_or_insert_with(|| {
+        let query = format!("schtasks.exe /Query /TN {}", TASK_NAME);
+        commands::run_and_wait(&query).is_ok_and(|exit_code| exit_code == 0)
+    })
+}
+
+/// Creates or deletes the logon task, which starts the hider elevated with `flags`
+///
+/// Creating it needs administrator rights, since the task runs with the highest
+/// privileges.
+pub fn set_task_registered(enable: bool, flags: &[String]) -> Result<(), Error> {
+    let command = if enable {
+        let exe =
+            std::env::current_exe().map_err(|_| Error::Failed("Failed to get executable path"))?;
+        create_task_command(&command_line(&exe.display().to_string(), flags))
+    } else {
+        format!("schtasks.exe /Delete /F /TN {}", TASK_NAME)
+    };
+    let exit_code = commands::run_and_wait(&command)?;
+    if let Ok(mut registered) = TASK_REGISTERED.lock() {
+        *registered = None;
+    }
+    match exit_code {
+        0 => Ok(()),
+        _ if enable => Err(Error::Failed(
+            "Failed to create the logon task; it needs administrator rights",
+        )),
+        _ => Err(Error::Failed("Failed to delete the logon task")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_quotes_the_exe_and_spaced_flags() {
+        let flags = ["--config".to_string(), r"C:\My Files\hider.toml".to_string()];
+        assert_eq!(
+            command_line(r"C:\Tools\taskbar-hider.exe", &flags),
+            r#""C:\Tools\taskbar-hider.exe" --config "C:\My Files\hider.toml""#
+        );
+        assert_eq!(
+            command_line("hider.exe", &["--keep-primary".to_string()]),
+            r#""hider.exe" --keep-primary"#
+        );
+    }
+
+    #[test]
+    fn create_task_command_escapes_the_quotes_of_the_command() {
+        assert_eq!(
+            create_task_command(r#""C:\Tools\hider.exe" --keep-primary"#),
+            concat!(
+                "schtasks.exe /Create /F /TN CleanTaskbar /SC ONLOGON /RL HIGHEST ",
+                r#"/TR "\"C:\Tools\hider.exe\" --keep-primary""#
+            )
+        );
+    }
+}
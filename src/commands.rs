@@ -0,0 +1,102 @@
+//! Visibility command module
+//!
+//! Runs the user's `on_show_command` and `on_hide_command` when the taskbar
+//! is revealed or hidden. Those are started detached and never waited on;
+//! helper tools the hider runs itself can be waited on for their exit code.
+
+use std::ptr::null;
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::CloseHandle;
+use windows_sys::Win32::System::Threading::{
+    CreateProcessW, GetExitCodeProcess, WaitForSingleObject, CREATE_NO_WINDOW, INFINITE,
+    PROCESS_INFORMATION, STARTUPINFOW,
+};
+use taskbar_hider::winapi::Error;
+
+static ON_SHOW_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+static ON_HIDE_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Sets the commands to run; an empty string disables one
+pub fn set_commands(on_show: &str, on_hide: &str) {
+    for (slot, command) in [(&ON_SHOW_COMMAND, on_show), (&ON_HIDE_COMMAND, on_hide)] {
+        if let Ok(mut guard) = slot.lock() {
+            *guard = (!command.is_empty()).then(|| command.to_string());
+        }
+    }
+}
+
+/// Starts the show command, if one is set
+pub fn run_on_show() -> Result<(), Error> {
+    run(&ON_SHOW_COMMAND)
+}
+
+/// Starts the hide command, if one is set
+pub fn run_on_hide() -> Result<(), Error> {
+    run(&ON_HIDE_COMMAND)
+}
+
+fn run(slot: &Mutex<Option<String>>) -> Result<(), Error> {
+    let command = slot.lock().ok().and_then(|guard| guard.clone());
+    match command {
+        Some(command) => spawn(&command),
+        None => Ok(()),
+    }
+}
+
+/// Starts a command line without a console window and without waiting for it
+pub fn spawn(command_line: &str) -> Result<(), Error> {
+    let process = create_process(command_line)?;
+    unsafe {
+        CloseHandle(process.hThread);
+        CloseHandle(process.hProcess);
+    }
+    Ok(())
+}
+
+/// Runs a command line without a console window and returns its exit code
+pub fn run_and_wait(command_line: &str) -> Result<u32, Error> {
+    let process = create_process(command_line)?;
+    unsafe {
+        WaitForSingleObject(process.hProcess, INFINITE);
+        let mut exit_code = 0;
+        let got_code = GetExitCodeProcess(process.hProcess, &mut exit_code);
+        CloseHandle(process.hThread);
+        CloseHandle(process.hProcess);
+        if got_code == 0 {
+            return Err(Error::Failed("Failed to get the command's exit code"));
+        }
+        Ok(exit_code)
+    }
+}
+
+fn create_process(command_line: &str) -> Result<PROCESS_INFORMATION, Error> {
+    // CreateProcessW may write to the command line buffer, so it must be owned
+    let mut command_line = wide_string(command_line);
+    unsafe {
+        let mut startup: STARTUPINFOW = std::mem::zeroed();
+        startup.cb = size_of::<STARTUPINFOW>() as u32;
+        let mut process: PROCESS_INFORMATION = std::mem::zeroed();
+
+        let created = CreateProcessW(
+            null(),
+            command_line.as_mut_ptr(),
+            null(),
+            null(),
+            0,
+            CREATE_NO_WINDOW,
+            null(),
+            null(),
+            &startup,
+            &mut process,
+        );
+        if created == 0 {
+            return Err(Error::last("Failed to start the command"));
+        }
+        Ok(process)
+    }
+}
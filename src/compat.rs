@@ -0,0 +1,66 @@
+//! Compatibility module
+//!
+//! Detects environments such as Wine where some shell APIs are stubbed or
+//! behave differently from native Windows, and the Windows version, whose
+//! shell windows differ between releases.
+
+use std::sync::OnceLock;
+use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+use windows_sys::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ};
+
+/// First build of Windows 11
+const WINDOWS_11_BUILD: u32 = 22000;
+
+static IS_WINE: OnceLock<bool> = OnceLock::new();
+static WINDOWS_BUILD: OnceLock<u32> = OnceLock::new();
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Returns true when running under Wine (ntdll exports `wine_get_version`)
+pub fn is_wine() -> bool {
+    *IS_WINE.get_or_init(|| unsafe {
+        let ntdll = GetModuleHandleW(wide_string("ntdll.dll").as_ptr());
+        if ntdll.is_null() {
+            return false;
+        }
+        GetProcAddress(ntdll, c"wine_get_version".as_ptr() as *const u8).is_some()
+    })
+}
+
+/// Returns the Windows build number, or zero if it can't be read
+///
+/// Read from the registry, since `GetVersionEx` reports an older version to
+/// applications without a compatibility manifest.
+pub fn windows_build() -> u32 {
+    *WINDOWS_BUILD.get_or_init(|| {
+        let subkey = wide_string("SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion");
+        let value = wide_string("CurrentBuildNumber");
+        let mut buf = [0u16; 16];
+        let mut size = size_of_val(&buf) as u32;
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                subkey.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                buf.as_mut_ptr() as *mut _,
+                &mut size,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return 0;
+        }
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        String::from_utf16_lossy(&buf[..len]).trim().parse().unwrap_or(0)
+    })
+}
+
+/// Returns true on Windows 11 or later
+pub fn is_windows_11() -> bool {
+    windows_build() >= WINDOWS_11_BUILD
+}
@@ -0,0 +1,1433 @@
+//! Configuration module
+//!
+//! Loads user settings from `clean-taskbar.toml` in `%APPDATA%`. Only a flat
+//! `key = value` subset of TOML is understood, plus `[monitor.*]` sections;
+//! missing keys keep their defaults.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use crate::hotkey::HotkeySpec;
+use crate::taskbar::Edge;
+
+pub const DEFAULT_REVEAL_DELAY_MS: u64 = 400;
+pub const DEFAULT_HIDE_RETRY_COUNT: u32 = 2;
+pub const DEFAULT_ANIMATION_MS: u64 = 120;
+pub const DEFAULT_MIN_VISIBLE_MS: u64 = 150;
+pub const DEFAULT_PEEK_MS: u64 = 3000;
+pub const DEFAULT_HIDE_ALPHA: u8 = 64;
+pub const DEFAULT_LOG_MAX_KB: u64 = 1024;
+pub const DEFAULT_LOG_BACKUPS: u32 = 1;
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 500;
+/// Shorter polls would wake the CPU for little gain in responsiveness
+const MIN_POLL_INTERVAL_MS: u64 = 100;
+const MIN_WATCHDOG_INTERVAL_MS: u64 = 1000;
+pub const DEFAULT_WATCHDOG_HANG_MS: u64 = 30_000;
+/// A couple of the main process's heartbeats, so one late write isn't taken for a hang
+const MIN_WATCHDOG_HANG_MS: u64 = 10_000;
+/// Most scan codes `win_scan_codes` may list; the hook checks each on every keystroke
+pub const MAX_WIN_SCAN_CODES: usize = 4;
+const DEFAULT_FALLBACK_HOTKEY: &str = "Win+`";
+const DEFAULT_QUIT_HOTKEY: &str = "Win+Shift+Q";
+
+const CONFIG_FILE_NAME: &str = "clean-taskbar.toml";
+
+/// Written by "Reset to defaults"; keys left out keep their built-in defaults anyway
+const DEFAULT_CONFIG: &str = "\
+# clean-taskbar settings; see the README for every key
+reveal_delay_ms = 400
+hold_threshold_ms = 0
+reveal_mode = \"both\"
+hover_edge = \"auto\"
+reveal_hotkey = \"Win\"
+fallback_hotkey = \"Win+`\"
+quit_hotkey = \"Win+Shift+Q\"
+hide_style = \"hide\"
+hide_alpha = 64
+animation_ms = 120
+min_visible_ms = 150
+peek_ms = 3000
+logging = false
+";
+
+/// Config file given with `--config`, used instead of the one in `%APPDATA%`
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Which triggers reveal the taskbar
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RevealMode {
+    /// Holding the reveal hotkey
+    WinKey,
+    /// Touching the screen edge the taskbar is docked to
+    EdgeHover,
+    /// Either of the above
+    Both,
+}
+
+impl RevealMode {
+    /// Returns true if the hotkey reveal is enabled
+    pub fn uses_winkey(self) -> bool {
+        matches!(self, RevealMode::WinKey | RevealMode::Both)
+    }
+
+    /// Returns true if the edge hover reveal is enabled
+    pub fn uses_edge_hover(self) -> bool {
+        matches!(self, RevealMode::EdgeHover | RevealMode::Both)
+    }
+
+    /// Returns the name used for this mode in the config file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RevealMode::WinKey => "winkey",
+            RevealMode::EdgeHover => "edge_hover",
+            RevealMode::Both => "both",
+        }
+    }
+
+    /// Returns a short description of the triggers, e.g. for the tray tooltip
+    pub fn label(self) -> &'static str {
+        match self {
+            RevealMode::WinKey => "hotkey",
+            RevealMode::EdgeHover => "edge hover",
+            RevealMode::Both => "hotkey or edge hover",
+        }
+    }
+}
+
+/// How the taskbar is put out of sight
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HideStyle {
+    /// Hide the window entirely
+    Hide,
+    /// Leave the window in place but make it mostly transparent
+    Transparent,
+}
+
+impl HideStyle {
+    /// Returns the name used for this style in the config file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HideStyle::Hide => "hide",
+            HideStyle::Transparent => "transparent",
+        }
+    }
+}
+
+/// How the taskbar moves when it is shown or hidden
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AnimationStyle {
+    /// Slide in from and out to the docked edge
+    Slide,
+    /// Fade the opacity in and out in place
+    Fade,
+}
+
+impl AnimationStyle {
+    /// Returns the name used for this style in the config file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnimationStyle::Slide => "slide",
+            AnimationStyle::Fade => "fade",
+        }
+    }
+}
+
+/// Whose login "Start with Windows" registers the hider for
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AutostartScope {
+    CurrentUser,
+    /// Every user of the machine; needs the hider to run elevated
+    AllUsers,
+}
+
+impl AutostartScope {
+    /// Returns the name used for this scope in the config file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutostartScope::CurrentUser => "current_user",
+            AutostartScope::AllUsers => "all_users",
+        }
+    }
+}
+
+/// A setup the mode cycle hotkey steps through
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CycleMode {
+    /// Hiding with the given reveal triggers
+    Reveal(RevealMode),
+    /// Hiding paused, so the taskbar stays visible
+    Paused,
+}
+
+impl CycleMode {
+    /// The default cycle, in order
+    pub const DEFAULT_CYCLE: [CycleMode; 4] = [
+        CycleMode::Reveal(RevealMode::Both),
+        CycleMode::Reveal(RevealMode::WinKey),
+        CycleMode::Reveal(RevealMode::EdgeHover),
+        CycleMode::Paused,
+    ];
+
+    /// Returns the mode with the given name in the config file
+    pub fn from_name(name: &str) -> Option<CycleMode> {
+        let mode = match name {
+            "winkey" => CycleMode::Reveal(RevealMode::WinKey),
+            "edge_hover" => CycleMode::Reveal(RevealMode::EdgeHover),
+            "both" => CycleMode::Reveal(RevealMode::Both),
+            "paused" => CycleMode::Paused,
+            _ => return None,
+        };
+        Some(mode)
+    }
+
+    /// Returns the name used for this mode in the config file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CycleMode::Reveal(mode) => mode.as_str(),
+            CycleMode::Paused => "paused",
+        }
+    }
+
+    /// Returns a short description, e.g. for the balloon shown on a switch
+    pub fn label(self) -> &'static str {
+        match self {
+            CycleMode::Reveal(mode) => mode.label(),
+            CycleMode::Paused => "paused, always visible",
+        }
+    }
+
+    /// Returns the mode after the current one in `cycle`, or its first mode if the
+    /// current one isn't in it
+    pub fn next(cycle: &[CycleMode], reveal_mode: RevealMode, paused: bool) -> Option<CycleMode> {
+        let current = if paused { CycleMode::Paused } else { CycleMode::Reveal(reveal_mode) };
+        let next = match cycle.iter().position(|&mode| mode == current) {
+            Some(index) => (index + 1) % cycle.len(),
+            None => 0,
+        };
+        cycle.get(next).copied()
+    }
+}
+
+/// Settings a `[monitor.DISPLAY2]` section overrides for one monitor, whose taskbar
+/// is then revealed independently of the others
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MonitorSection {
+    /// Device name without the `\\.\` prefix, e.g. `DISPLAY2`
+    pub device: String,
+    pub reveal_mode: Option<RevealMode>,
+    pub reveal_delay_ms: Option<u64>,
+    pub min_visible_ms: Option<u64>,
+}
+
+impl MonitorSection {
+    /// Creates a section that overrides nothing yet
+    fn new(device: String) -> MonitorSection {
+        MonitorSection { device, reveal_mode: None, reveal_delay_ms: None, min_visible_ms: None }
+    }
+}
+
+/// An entry of the tray menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuItem {
+    Peek,
+    Toggle,
+    Autostart,
+    /// Registers or removes the elevated Task Scheduler logon task
+    LogonTask,
+    Settings,
+    /// Submenu choosing the monitors whose taskbar is hidden
+    Monitors,
+    OpenConfig,
+    Reload,
+    Reset,
+    /// Saves the current settings to a file of the user's choosing
+    Export,
+    /// Replaces the settings with those of a file of the user's choosing
+    Import,
+    Overlay,
+    About,
+    Quit,
+    Separator,
+}
+
+impl MenuItem {
+    /// The default menu, in order
+    pub const DEFAULT_MENU: [MenuItem; 13] = [
+        MenuItem::Peek,
+        MenuItem::Toggle,
+        MenuItem::Autostart,
+        MenuItem::LogonTask,
+        MenuItem::Settings,
+        MenuItem::Monitors,
+        MenuItem::Reload,
+        MenuItem::Reset,
+        MenuItem::Export,
+        MenuItem::Import,
+        MenuItem::Overlay,
+        MenuItem::About,
+        MenuItem::Quit,
+    ];
+
+    /// Returns the entry with the given name in the config file
+    pub fn from_name(name: &str) -> Option<MenuItem> {
+        let item = match name {
+            "peek" => MenuItem::Peek,
+            "toggle" => MenuItem::Toggle,
+            "autostart" => MenuItem::Autostart,
+            "logon_task" => MenuItem::LogonTask,
+            "settings" => MenuItem::Settings,
+            "monitors" => MenuItem::Monitors,
+            "open_config" => MenuItem::OpenConfig,
+            "reload" => MenuItem::Reload,
+            "reset" => MenuItem::Reset,
+            "export" => MenuItem::Export,
+            "import" => MenuItem::Import,
+            "overlay" => MenuItem::Overlay,
+            "about" => MenuItem::About,
+            "quit" => MenuItem::Quit,
+            "separator" => MenuItem::Separator,
+            _ => return None,
+        };
+        Some(item)
+    }
+
+    /// Returns the name used for this entry in the config file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MenuItem::Peek => "peek",
+            MenuItem::Toggle => "toggle",
+            MenuItem::Autostart => "autostart",
+            MenuItem::LogonTask => "logon_task",
+            MenuItem::Settings => "settings",
+            MenuItem::Monitors => "monitors",
+            MenuItem::OpenConfig => "open_config",
+            MenuItem::Reload => "reload",
+            MenuItem::Reset => "reset",
+            MenuItem::Export => "export",
+            MenuItem::Import => "import",
+            MenuItem::Overlay => "overlay",
+            MenuItem::About => "about",
+            MenuItem::Quit => "quit",
+            MenuItem::Separator => "separator",
+        }
+    }
+}
+
+/// User-configurable settings
+#[derive(Clone)]
+pub struct Config {
+    /// How long the taskbar stays visible after the Windows key is released
+    pub reveal_delay_ms: u64,
+    /// How long the reveal hotkey must be held before the taskbar appears
+    pub hold_threshold_ms: u64,
+    /// Key combination that reveals the taskbar while held
+    pub reveal_hotkey: HotkeySpec,
+    /// `RegisterHotKey` combination used when games bypass the hook; `None` disables it
+    pub fallback_hotkey: Option<HotkeySpec>,
+    /// Scan codes, e.g. `0xE05B`, of keys that also count as the Windows key, for
+    /// remapped keyboards whose Windows key reports a different vk
+    pub win_scan_codes: Vec<u32>,
+    /// Log the vk and scan code of every pressed key, to find the ones to configure
+    pub log_key_codes: bool,
+    /// Registered hotkey that quits the hider; `None` disables it
+    pub quit_hotkey: Option<HotkeySpec>,
+    /// Registered hotkey that switches to the next mode of `mode_cycle`; `None` disables it
+    pub mode_cycle_hotkey: Option<HotkeySpec>,
+    /// Modes the mode cycle hotkey steps through, in order
+    pub mode_cycle: Vec<CycleMode>,
+    /// Which triggers reveal the taskbar
+    pub reveal_mode: RevealMode,
+    /// Whether the taskbar is hidden or only faded out
+    pub hide_style: HideStyle,
+    /// Opacity (0-255) of the taskbar while faded out with `HideStyle::Transparent`
+    pub hide_alpha: u8,
+    /// Screen edge that triggers a hover reveal; `None` follows the taskbar's dock edge
+    pub hover_edge: Option<Edge>,
+    /// The cursor anywhere over a hidden bar's area reveals it, like native auto-hide,
+    /// instead of only at the screen edge
+    pub hover_whole_bar: bool,
+    /// Extra hide attempts made if Explorer re-shows the taskbar
+    pub hide_retry_count: u32,
+    /// How often the idle checks poll the time since the last input
+    pub poll_interval_ms: u64,
+    /// How often the watchdog re-hides a bar that came back and restores auto-hide;
+    /// zero turns it off
+    pub watchdog_interval_ms: u64,
+    /// Start a helper process that restores the taskbar if the hider is killed or hangs
+    pub external_watchdog: bool,
+    /// How long the hider may go without a heartbeat before the helper restores the bar
+    pub watchdog_hang_ms: u64,
+    /// Duration of the show and hide animation; zero shows and hides instantly
+    pub animation_ms: u64,
+    /// Whether the show and hide animation slides or fades the taskbar
+    pub animation_style: AnimationStyle,
+    /// Shortest time the taskbar stays up once shown, so quick taps don't flicker
+    pub min_visible_ms: u64,
+    /// How long "Show temporarily" keeps the taskbar up
+    pub peek_ms: u64,
+    /// Drops every reveal and hide delay, overriding the individual settings
+    pub instant: bool,
+    /// Briefly reveal the taskbar when a notification toast appears
+    pub reveal_on_toast: bool,
+    /// Briefly reveal the taskbar when the battery runs low while unplugged
+    pub reveal_on_low_battery: bool,
+    /// Remaining charge, in percent, at or below which the battery counts as low
+    pub low_battery_percent: u32,
+    /// Reveal the taskbar while the volume OSD is shown, and for the reveal delay after
+    pub reveal_on_volume_osd: bool,
+    /// Keep a visible taskbar up while the Windows 11 snap layouts flyout is shown
+    pub keep_on_snap_layouts: bool,
+    /// Reveal the taskbar while the screen clip overlay is up, so it can be captured
+    pub reveal_on_snip: bool,
+    /// Keep a visible taskbar up while the Win+Space input switcher is shown
+    pub keep_on_input_switcher: bool,
+    /// Reveal the taskbar while the On-Screen Keyboard or touch keyboard is open
+    pub reveal_on_keyboard: bool,
+    /// Executables, e.g. `explorer.exe`, whose dialog boxes reveal the taskbar while open
+    pub reveal_on_dialogs: Vec<String>,
+    /// Time without input after which the next input briefly reveals the taskbar;
+    /// zero turns this off
+    pub idle_reveal_after_ms: u64,
+    /// How long the taskbar stays up when input resumes after being idle
+    pub idle_reveal_ms: u64,
+    /// Time without input after which a revealed taskbar hides, even under the
+    /// cursor; zero turns this off
+    pub inactive_hide_ms: u64,
+    /// Edge hover reveals only the hovered monitor's taskbar instead of all of them
+    pub per_monitor_reveal: bool,
+    /// Monitors whose taskbar is never hidden, as device names like `DISPLAY2`
+    pub always_visible_monitors: Vec<String>,
+    /// The primary taskbar is never hidden, like `--keep-primary`
+    pub keep_primary_visible: bool,
+    /// The secondary taskbars stay hidden; only the primary one is ever revealed
+    pub hide_secondary_bars: bool,
+    /// Keep the taskbar shown while the bare desktop is focused
+    pub show_on_desktop: bool,
+    /// Nothing but pausing reveals the taskbar over an exclusive fullscreen app
+    pub block_exclusive_fullscreen: bool,
+    /// How long an edge hover over a borderless fullscreen app briefly reveals the
+    /// taskbar; zero keeps the hover ignored there
+    pub borderless_peek_ms: u64,
+    /// Only hide the taskbar while the foreground window covers it
+    pub smart_hide: bool,
+    /// Executables, e.g. `mstsc.exe`, that the taskbar is only hidden in; empty hides
+    /// it everywhere
+    pub hide_only_in: Vec<String>,
+    /// Command line run each time the taskbar is revealed; empty runs nothing
+    pub on_show_command: String,
+    /// Command line run each time the taskbar is hidden; empty runs nothing
+    pub on_hide_command: String,
+    /// Tray tooltip text; empty keeps the default
+    pub tray_tooltip: String,
+    /// Whether "Start with Windows" registers the hider for the current user or all users
+    pub autostart_scope: AutostartScope,
+    /// Tray menu entries in order; always ends up containing Quit
+    pub tray_menu: Vec<MenuItem>,
+    /// Hiding was paused when the hider last ran
+    pub paused: bool,
+    /// Monitors revealed independently, each with its own overrides
+    pub monitor_sections: Vec<MonitorSection>,
+    /// Write a troubleshooting log to `%LOCALAPPDATA%\clean-taskbar\log.txt`
+    pub logging: bool,
+    /// Size in KB at which the log is rotated to `log.1.txt`; zero never rotates
+    pub log_max_kb: u64,
+    /// Number of rotated logs kept
+    pub log_backups: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            reveal_delay_ms: DEFAULT_REVEAL_DELAY_MS,
+            hold_threshold_ms: 0,
+            reveal_hotkey: HotkeySpec::default(),
+            fallback_hotkey: HotkeySpec::parse(DEFAULT_FALLBACK_HOTKEY),
+            win_scan_codes: Vec::new(),
+            log_key_codes: false,
+            quit_hotkey: HotkeySpec::parse(DEFAULT_QUIT_HOTKEY),
+            mode_cycle_hotkey: None,
+            mode_cycle: CycleMode::DEFAULT_CYCLE.to_vec(),
+            reveal_mode: RevealMode::Both,
+            hover_edge: None,
+            hover_whole_bar: false,
+            hide_style: HideStyle::Hide,
+            hide_alpha: DEFAULT_HIDE_ALPHA,
+            hide_retry_count: DEFAULT_HIDE_RETRY_COUNT,
+            poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            watchdog_interval_ms: 0,
+            external_watchdog: false,
+            watchdog_hang_ms: DEFAULT_WATCHDOG_HANG_MS,
+            animation_ms: DEFAULT_ANIMATION_MS,
+            animation_style: AnimationStyle::Slide,
+            min_visible_ms: DEFAULT_MIN_VISIBLE_MS,
+            peek_ms: DEFAULT_PEEK_MS,
+            instant: false,
+            reveal_on_toast: false,
+            reveal_on_low_battery: false,
+            low_battery_percent: 10,
+            reveal_on_volume_osd: false,
+            keep_on_snap_layouts: true,
+            reveal_on_snip: false,
+            keep_on_input_switcher: true,
+            reveal_on_keyboard: true,
+            reveal_on_dialogs: Vec::new(),
+            show_on_desktop: false,
+            block_exclusive_fullscreen: false,
+            borderless_peek_ms: 0,
+            smart_hide: false,
+            hide_only_in: Vec::new(),
+            idle_reveal_after_ms: 0,
+            idle_reveal_ms: DEFAULT_PEEK_MS,
+            inactive_hide_ms: 0,
+            per_monitor_reveal: false,
+            always_visible_monitors: Vec::new(),
+            keep_primary_visible: false,
+            hide_secondary_bars: false,
+            on_show_command: String::new(),
+            on_hide_command: String::new(),
+            tray_tooltip: String::new(),
+            autostart_scope: AutostartScope::CurrentUser,
+            tray_menu: MenuItem::DEFAULT_MENU.to_vec(),
+            paused: false,
+            monitor_sections: Vec::new(),
+            logging: false,
+            log_max_kb: DEFAULT_LOG_MAX_KB,
+            log_backups: DEFAULT_LOG_BACKUPS,
+        }
+    }
+}
+
+impl Config {
+    /// Formats every setting as config file contents that `parse` reads back unchanged
+    pub fn to_text(&self) -> String {
+        let hotkey = |spec: Option<HotkeySpec>| {
+            format_string(&spec.map(|spec| spec.to_text()).unwrap_or_default())
+        };
+        let names = |names: &[String]| format_string_list(names.iter().map(String::as_str));
+        let values: [(&str, String); 54] = [
+            ("reveal_delay_ms", self.reveal_delay_ms.to_string()),
+            ("hold_threshold_ms", self.hold_threshold_ms.to_string()),
+            ("reveal_mode", format_string(self.reveal_mode.as_str())),
+            ("hover_edge", format_string(edge_name(self.hover_edge))),
+            ("hover_whole_bar", self.hover_whole_bar.to_string()),
+            ("reveal_hotkey", format_string(&self.reveal_hotkey.to_text())),
+            ("win_scan_codes", format_scan_codes(&self.win_scan_codes)),
+            ("log_key_codes", self.log_key_codes.to_string()),
+            ("fallback_hotkey", hotkey(self.fallback_hotkey)),
+            ("quit_hotkey", hotkey(self.quit_hotkey)),
+            ("mode_cycle_hotkey", hotkey(self.mode_cycle_hotkey)),
+            ("mode_cycle", format_string_list(self.mode_cycle.iter().map(|m| m.as_str()))),
+            ("hide_style", format_string(self.hide_style.as_str())),
+            ("hide_alpha", self.hide_alpha.to_string()),
+            ("hide_retry_count", self.hide_retry_count.to_string()),
+            ("poll_interval_ms", self.poll_interval_ms.to_string()),
+            ("watchdog_interval_ms", self.watchdog_interval_ms.to_string()),
+            ("external_watchdog", self.external_watchdog.to_string()),
+            ("watchdog_hang_ms", self.watchdog_hang_ms.to_string()),
+            ("animation_ms", self.animation_ms.to_string()),
+            ("animation_style", format_string(self.animation_style.as_str())),
+            ("min_visible_ms", self.min_visible_ms.to_string()),
+            ("peek_ms", self.peek_ms.to_string()),
+            ("instant", self.instant.to_string()),
+            ("reveal_on_toast", self.reveal_on_toast.to_string()),
+            ("reveal_on_low_battery", self.reveal_on_low_battery.to_string()),
+            ("low_battery_percent", self.low_battery_percent.to_string()),
+            ("reveal_on_volume_osd", self.reveal_on_volume_osd.to_string()),
+            ("keep_on_snap_layouts", self.keep_on_snap_layouts.to_string()),
+            ("reveal_on_snip", self.reveal_on_snip.to_string()),
+            ("keep_on_input_switcher", self.keep_on_input_switcher.to_string()),
+            ("reveal_on_keyboard", self.reveal_on_keyboard.to_string()),
+            ("reveal_on_dialogs", names(&self.reveal_on_dialogs)),
+            ("idle_reveal_after_ms", self.idle_reveal_after_ms.to_string()),
+            ("idle_reveal_ms", self.idle_reveal_ms.to_string()),
+            ("inactive_hide_ms", self.inactive_hide_ms.to_string()),
+            ("per_monitor_reveal", self.per_monitor_reveal.to_string()),
+            ("always_visible_monitors", format_monitors(&self.always_visible_monitors)),
+            ("keep_primary_visible", self.keep_primary_visible.to_string()),
+            ("hide_secondary_bars", self.hide_secondary_bars.to_string()),
+            ("show_on_desktop", self.show_on_desktop.to_string()),
+            ("block_exclusive_fullscreen", self.block_exclusive_fullscreen.to_string()),
+            ("borderless_peek_ms", self.borderless_peek_ms.to_string()),
+            ("smart_hide", self.smart_hide.to_string()),
+            ("hide_only_in", names(&self.hide_only_in)),
+            ("on_show_command", format_string(&self.on_show_command)),
+            ("on_hide_command", format_string(&self.on_hide_command)),
+            ("tray_tooltip", format_string(&self.tray_tooltip)),
+            ("autostart_scope", format_string(self.autostart_scope.as_str())),
+            ("tray_menu", format_string_list(self.tray_menu.iter().map(|i| i.as_str()))),
+            ("paused", self.paused.to_string()),
+            ("logging", self.logging.to_string()),
+            ("log_max_kb", self.log_max_kb.to_string()),
+            ("log_backups", self.log_backups.to_string()),
+        ];
+        let mut text = String::from("# clean-taskbar settings; see the README for every key\n");
+        for (key, value) in values {
+            text.push_str(&format!("{} = {}\n", key, value));
+        }
+        // Sections come last, since every key after a header belongs to it
+        for section in &self.monitor_sections {
+            text.push_str(&format!("\n[monitor.{}]\n", section.device));
+            if let Some(mode) = section.reveal_mode {
+                text.push_str(&format!("reveal_mode = {}\n", format_string(mode.as_str())));
+            }
+            if let Some(ms) = section.reveal_delay_ms {
+                text.push_str(&format!("reveal_delay_ms = {}\n", ms));
+            }
+            if let Some(ms) = section.min_visible_ms {
+                text.push_str(&format!("min_visible_ms = {}\n", ms));
+            }
+        }
+        text
+    }
+}
+
+/// Returns the path of the config file
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = PATH_OVERRIDE.get() {
+        return Some(path.clone());
+    }
+    let appdata = std::env::var_os("APPDATA")?;
+    Some(PathBuf::from(appdata).join(CONFIG_FILE_NAME))
+}
+
+/// Returns the config file given with `--config`, if any
+pub fn path_override() -> Option<&'static Path> {
+    PATH_OVERRIDE.get().map(PathBuf::as_path)
+}
+
+/// Uses `path` as the config file instead of the default location
+///
+/// The file itself may be missing, but its folder must exist. An invalid path
+/// is rejected and the default location stays in use.
+pub fn set_path(path: &Path) -> Result<(), String> {
+    let path = validate_path(path)?;
+    PATH_OVERRIDE
+        .set(path)
+        .map_err(|_| "The config path was already set".to_string())
+}
+
+/// Resolves a `--config` path against the working directory and checks it can hold a file
+fn validate_path(path: &Path) -> Result<PathBuf, String> {
+    if path.as_os_str().is_empty() {
+        return Err("--config needs a file path".to_string());
+    }
+    let path = std::path::absolute(path)
+        .map_err(|_| format!("Invalid config path {}", path.display()))?;
+    if path.is_dir() {
+        return Err(format!("Config path {} is a folder", path.display()));
+    }
+    if !path.parent().is_some_and(Path::is_dir) {
+        return Err(format!("Config path {} is in a missing folder", path.display()));
+    }
+    Ok(path)
+}
+
+/// Loads the config file, falling back to defaults if it is absent or malformed
+pub fn load() -> Config {
+    try_load().unwrap_or_default()
+}
+
+/// Loads the config file, failing if it is malformed; a missing file gives the defaults
+pub fn try_load() -> Result<Config, String> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return Ok(Config::default());
+    };
+    parse(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+}
+
+/// Writes the given `key = value` pairs to the config file
+///
+/// Existing lines for those keys are replaced in place so comments and other
+/// settings survive; keys not yet in the file are appended.
+pub fn update(values: &[(&str, String)]) -> Result<(), &'static str> {
+    let path = config_path().ok_or("Failed to locate the config file")?;
+    let text = std::fs::read_to_string(&path).unwrap_or_default();
+    std::fs::write(&path, update_text(&text, values))
+        .map_err(|_| "Failed to write the config file")
+}
+
+/// Rewrites config file contents with the given `key = value` pairs
+///
+/// Only top-level keys are replaced; new ones go before the first section header,
+/// where they would otherwise become part of that section.
+fn update_text(text: &str, values: &[(&str, String)]) -> String {
+    let mut pending: Vec<&(&str, String)> = values.iter().collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut first_section = None;
+    for line in text.lines() {
+        let content = strip_comment(line).trim();
+        if first_section.is_none() && content.starts_with('[') {
+            first_section = Some(lines.len());
+        }
+        let key = content.split_once('=').map(|(key, _)| key.trim());
+        let index = pending.iter().position(|(k, _)| Some(*k) == key);
+        match index.filter(|_| first_section.is_none()) {
+            Some(index) => {
+                let (key, value) = pending.remove(index);
+                lines.push(format!("{} = {}", key, value));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    let new_lines = pending.iter().map(|(key, value)| format!("{} = {}", key, value));
+    match first_section {
+        Some(index) => {
+            lines.splice(index..index, new_lines);
+        }
+        None => lines.extend(new_lines),
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+/// Returns the path of the config file, first writing the defaults to it if it is missing
+pub fn ensure_exists() -> Result<PathBuf, &'static str> {
+    let path = config_path().ok_or("Failed to locate the config file")?;
+    if !path.exists() {
+        std::fs::write(&path, DEFAULT_CONFIG).map_err(|_| "Failed to write the config file")?;
+    }
+    Ok(path)
+}
+
+/// Backs up the config file next to it and replaces it with the defaults
+///
+/// Returns the backup's path, or `None` if there was no file to back up.
+pub fn reset() -> Result<Option<PathBuf>, &'static str> {
+    let path = config_path().ok_or("Failed to locate the config file")?;
+    let backup = if path.exists() {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".bak");
+        let backup = path.with_file_name(name);
+        std::fs::copy(&path, &backup).map_err(|_| "Failed to back up the config file")?;
+        Some(backup)
+    } else {
+        None
+    };
+    std::fs::write(&path, DEFAULT_CONFIG).map_err(|_| "Failed to write the config file")?;
+    Ok(backup)
+}
+
+/// Writes the given settings to `path`, e.g. to carry them to another machine
+pub fn export(config: &Config, path: &Path) -> Result<(), String> {
+    std::fs::write(path, config.to_text())
+        .map_err(|_| format!("Failed to write {}", path.display()))
+}
+
+/// Replaces the config file with the one at `path`, if it parses
+///
+/// The file is copied as is, comments included; a file with an error leaves
+/// the current config untouched.
+pub fn import(path: &Path) -> Result<(), String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|_| format!("Failed to read {}", path.display()))?;
+    parse(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+    let target = config_path().ok_or("Failed to locate the config file")?;
+    std::fs::write(&target, text).map_err(|_| "Failed to write the config file".to_string())
+}
+
+/// Parses config file contents
+fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    // Keys after a `[monitor.*]` header go to that section; other sections are skipped
+    let mut section: Option<Option<usize>> = None;
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(parse_section(&mut config.monitor_sections, header.trim()));
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", index + 1))?;
+        let (key, value) = (key.trim(), value.trim());
+
+        match section {
+            Some(Some(monitor)) => {
+                let section = &mut config.monitor_sections[monitor];
+                parse_section_key(section, key, value)
+                    .map_err(|e| format!("[monitor.{}] {}", section.device, e))?;
+                continue;
+            }
+            Some(None) => continue,
+            None => {}
+        }
+
+        match key {
+            "reveal_delay_ms" => config.reveal_delay_ms = parse_u64(key, value)?,
+            "hold_threshold_ms" => config.hold_threshold_ms = parse_u64(key, value)?,
+            "reveal_mode" => config.reveal_mode = parse_reveal_mode(key, value)?,
+            "hover_edge" => {
+                config.hover_edge = match parse_string(key, value)?.as_str() {
+                    "auto" => None,
+                    "top" => Some(Edge::Top),
+                    "bottom" => Some(Edge::Bottom),
+                    "left" => Some(Edge::Left),
+                    "right" => Some(Edge::Right),
+                    _ => return Err(format!("{} must be auto, top, bottom, left or right", key)),
+                }
+            }
+            "autostart_scope" => {
+                config.autostart_scope = match parse_string(key, value)?.as_str() {
+                    "current_user" => AutostartScope::CurrentUser,
+                    "all_users" => AutostartScope::AllUsers,
+                    _ => return Err(format!("{} must be current_user or all_users", key)),
+                }
+            }
+            "hover_whole_bar" => config.hover_whole_bar = parse_bool(key, value)?,
+            "hide_style" => {
+                config.hide_style = match parse_string(key, value)?.as_str() {
+                    "hide" => HideStyle::Hide,
+                    "transparent" => HideStyle::Transparent,
+                    _ => return Err(format!("{} must be hide or transparent", key)),
+                }
+            }
+            "animation_style" => {
+                config.animation_style = match parse_string(key, value)?.as_str() {
+                    "slide" => AnimationStyle::Slide,
+                    "fade" => AnimationStyle::Fade,
+                    _ => return Err(format!("{} must be slide or fade", key)),
+                }
+            }
+            "hide_alpha" => config.hide_alpha = parse_u8(key, value)?,
+            "poll_interval_ms" => {
+                config.poll_interval_ms = parse_interval(key, value, MIN_POLL_INTERVAL_MS, false)?
+            }
+            "watchdog_interval_ms" => {
+                config.watchdog_interval_ms =
+                    parse_interval(key, value, MIN_WATCHDOG_INTERVAL_MS, true)?
+            }
+            "external_watchdog" => config.external_watchdog = parse_bool(key, value)?,
+            "watchdog_hang_ms" => {
+                config.watchdog_hang_ms = parse_interval(key, value, MIN_WATCHDOG_HANG_MS, false)?
+            }
+            "hide_retry_count" => config.hide_retry_count = parse_u32(key, value)?,
+            "animation_ms" => config.animation_ms = parse_u64(key, value)?,
+            "min_visible_ms" => config.min_visible_ms = parse_u64(key, value)?,
+            "peek_ms" => config.peek_ms = parse_u64(key, value)?,
+            "instant" => config.instant = parse_bool(key, value)?,
+            "logging" => config.logging = parse_bool(key, value)?,
+            "log_max_kb" => config.log_max_kb = parse_u64(key, value)?,
+            "log_backups" => config.log_backups = parse_u32(key, value)?,
+            "reveal_on_toast" => config.reveal_on_toast = parse_bool(key, value)?,
+            "reveal_on_low_battery" => config.reveal_on_low_battery = parse_bool(key, value)?,
+            "low_battery_percent" => match parse_u32(key, value)? {
+                percent @ 1..=100 => config.low_battery_percent = percent,
+                _ => return Err(format!("{} must be from 1 to 100", key)),
+            },
+            "reveal_on_volume_osd" => config.reveal_on_volume_osd = parse_bool(key, value)?,
+            "keep_on_snap_layouts" => config.keep_on_snap_layouts = parse_bool(key, value)?,
+            "reveal_on_snip" => config.reveal_on_snip = parse_bool(key, value)?,
+            "keep_on_input_switcher" => config.keep_on_input_switcher = parse_bool(key, value)?,
+            "reveal_on_keyboard" => config.reveal_on_keyboard = parse_bool(key, value)?,
+            "reveal_on_dialogs" => config.reveal_on_dialogs = parse_string_list(key, value)?,
+            "show_on_desktop" => config.show_on_desktop = parse_bool(key, value)?,
+            "block_exclusive_fullscreen" => {
+                config.block_exclusive_fullscreen = parse_bool(key, value)?
+            }
+            "borderless_peek_ms" => config.borderless_peek_ms = parse_u64(key, value)?,
+            "smart_hide" => config.smart_hide = parse_bool(key, value)?,
+            "hide_only_in" => config.hide_only_in = parse_string_list(key, value)?,
+            "idle_reveal_after_ms" => config.idle_reveal_after_ms = parse_u64(key, value)?,
+            "idle_reveal_ms" => config.idle_reveal_ms = parse_u64(key, value)?,
+            "inactive_hide_ms" => config.inactive_hide_ms = parse_u64(key, value)?,
+            "per_monitor_reveal" => config.per_monitor_reveal = parse_bool(key, value)?,
+            "keep_primary_visible" => config.keep_primary_visible = parse_bool(key, value)?,
+            "hide_secondary_bars" => config.hide_secondary_bars = parse_bool(key, value)?,
+            "always_visible_monitors" => {
+                config.always_visible_monitors = parse_monitors(key, value)?
+            }
+            "on_show_command" => config.on_show_command = parse_string(key, value)?,
+            "on_hide_command" => config.on_hide_command = parse_string(key, value)?,
+            "tray_tooltip" => config.tray_tooltip = parse_string(key, value)?,
+            "tray_menu" => config.tray_menu = parse_menu(key, value)?,
+            // Written by the hider itself; anything unexpected means not paused
+            "paused" => config.paused = value == "true",
+            "reveal_hotkey" => {
+                config.reveal_hotkey = HotkeySpec::parse(&parse_string(key, value)?)
+                    .ok_or_else(|| format!("{} is not a valid key combination", key))?
+            }
+            "win_scan_codes" => config.win_scan_codes = parse_scan_codes(key, value)?,
+            "log_key_codes" => config.log_key_codes = parse_bool(key, value)?,
+            // An empty string turns the registered hotkeys off
+            "fallback_hotkey" => config.fallback_hotkey = parse_registered_hotkey(key, value)?,
+            "quit_hotkey" => config.quit_hotkey = parse_registered_hotkey(key, value)?,
+            "mode_cycle_hotkey" => {
+                config.mode_cycle_hotkey = parse_registered_hotkey(key, value)?
+            }
+            "mode_cycle" => config.mode_cycle = parse_cycle(key, value)?,
+            // Unknown keys are ignored so newer config files still load
+            _ => {}
+        }
+    }
+
+    // Applied last, so it wins wherever the individual delays appear in the file
+    if config.instant {
+        config.reveal_delay_ms = 0;
+        config.hold_threshold_ms = 0;
+        config.min_visible_ms = 0;
+        config.animation_ms = 0;
+        for section in &mut config.monitor_sections {
+            section.reveal_delay_ms = section.reveal_delay_ms.map(|_| 0);
+            section.min_visible_ms = section.min_visible_ms.map(|_| 0);
+        }
+    }
+
+    Ok(config)
+}
+
+/// Starts the section with the given header, returning the index of its monitor
+/// section, or `None` for a section this version doesn't know
+///
+/// A monitor is given by number (`monitor.2`) or device name (`monitor.DISPLAY2`);
+/// a repeated header continues the earlier section.
+fn parse_section(sections: &mut Vec<MonitorSection>, header: &str) -> Option<usize> {
+    let name = header.strip_prefix("monitor.")?.trim().trim_matches('"');
+    let device = match name.parse::<u32>() {
+        Ok(number) => format!("DISPLAY{}", number),
+        Err(_) => name.trim_start_matches(['\\', '.']).to_ascii_uppercase(),
+    };
+    if let Some(index) = sections.iter().position(|section| section.device == device) {
+        return Some(index);
+    }
+    sections.push(MonitorSection::new(device));
+    Some(sections.len() - 1)
+}
+
+/// Parses a key of a `[monitor.*]` section, of which only a few can differ per monitor
+fn parse_section_key(section: &mut MonitorSection, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "reveal_mode" => section.reveal_mode = Some(parse_reveal_mode(key, value)?),
+        "reveal_delay_ms" => section.reveal_delay_ms = Some(parse_u64(key, value)?),
+        "min_visible_ms" => section.min_visible_ms = Some(parse_u64(key, value)?),
+        _ => return Err(format!("{} can't be set per monitor", key)),
+    }
+    Ok(())
+}
+
+fn parse_reveal_mode(key: &str, value: &str) -> Result<RevealMode, String> {
+    match parse_string(key, value)?.as_str() {
+        "winkey" => Ok(RevealMode::WinKey),
+        "edge_hover" => Ok(RevealMode::EdgeHover),
+        "both" => Ok(RevealMode::Both),
+        _ => Err(format!("{} must be winkey, edge_hover or both", key)),
+    }
+}
+
+/// Removes a trailing `#` comment that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            // Only basic strings have escapes; a literal string ends at its next quote
+            (Some('"'), _) if escaped => escaped = false,
+            (Some('"'), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parses a `RegisterHotKey` combination, where an empty string means none
+fn parse_registered_hotkey(key: &str, value: &str) -> Result<Option<HotkeySpec>, String> {
+    match parse_string(key, value)?.as_str() {
+        "" => Ok(None),
+        text => HotkeySpec::parse(text)
+            .filter(|spec| spec.key.is_some())
+            .map(Some)
+            .ok_or_else(|| format!("{} must include a non-modifier key", key)),
+    }
+}
+
+/// Formats monitor device names as a config file list, e.g. `["DISPLAY2"]`
+pub fn format_monitors(monitors: &[String]) -> String {
+    format_string_list(monitors.iter().map(String::as_str))
+}
+
+/// Formats strings as a config file list, e.g. `["explorer.exe", "mstsc.exe"]`
+fn format_string_list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    let items: Vec<String> = items.map(format_string).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Formats a basic string, escaping what `parse_string` unescapes
+fn format_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the `hover_edge` name of an edge, where `None` follows the dock edge
+fn edge_name(edge: Option<Edge>) -> &'static str {
+    match edge {
+        None => "auto",
+        Some(Edge::Top) => "top",
+        Some(Edge::Bottom) => "bottom",
+        Some(Edge::Left) => "left",
+        Some(Edge::Right) => "right",
+    }
+}
+
+/// Parses a list of monitors given by number (`2`) or device name (`"DISPLAY2"`)
+///
+/// Both forms are normalized to the uppercase device name without the `\\.\` prefix.
+fn parse_monitors(key: &str, value: &str) -> Result<Vec<String>, String> {
+    let items = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(|| format!("{} must be a list, e.g. [2, \"DISPLAY3\"]", key))?;
+    items
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.parse::<u32>() {
+            Ok(number) => Ok(format!("DISPLAY{}", number)),
+            Err(_) => Ok(parse_string(key, item)?
+                .trim_start_matches(['\\', '.'])
+                .to_ascii_uppercase()),
+        })
+        .collect()
+}
+
+/// Formats scan codes as a config file list of hex numbers, e.g. `[0xE05B]`
+fn format_scan_codes(codes: &[u32]) -> String {
+    let items: Vec<String> = codes.iter().map(|code| format!("0x{:X}", code)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Parses a list of up to `MAX_WIN_SCAN_CODES` scan codes, in decimal or `0x` hex
+fn parse_scan_codes(key: &str, value: &str) -> Result<Vec<u32>, String> {
+    let invalid = || format!("{} must be a list of scan codes, e.g. [0xE05B]", key);
+    let items = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let codes = items
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let code = match item.strip_prefix("0x").or_else(|| item.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => item.parse(),
+            };
+            // Zero marks an unused slot in the hook
+            code.ok().filter(|&code| code != 0).ok_or_else(invalid)
+        })
+        .collect::<Result<Vec<u32>, String>>()?;
+    if codes.len() > MAX_WIN_SCAN_CODES {
+        return Err(format!("{} takes at most {} scan codes", key, MAX_WIN_SCAN_CODES));
+    }
+    Ok(codes)
+}
+
+/// Parses a list of quoted strings, e.g. `["explorer.exe"]`
+fn parse_string_list(key: &str, value: &str) -> Result<Vec<String>, String> {
+    let items = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(|| format!("{} must be a list, e.g. [\"explorer.exe\"]", key))?;
+    items
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse_string(key, item))
+        .collect()
+}
+
+/// Parses the tray menu entries, adding Quit at the end if it was left out
+///
+/// Without Quit in the menu the only way out would be the quit hotkey, which
+/// can be turned off.
+fn parse_menu(key: &str, value: &str) -> Result<Vec<MenuItem>, String> {
+    let mut items = parse_string_list(key, value)?
+        .iter()
+        .map(|name| {
+            MenuItem::from_name(name)
+                .ok_or_else(|| format!("{} has an unknown menu entry \"{}\"", key, name))
+        })
+        .collect::<Result<Vec<MenuItem>, String>>()?;
+    if !items.contains(&MenuItem::Quit) {
+        items.push(MenuItem::Quit);
+    }
+    Ok(items)
+}
+
+/// Parses the modes of the mode cycle, which needs at least one
+fn parse_cycle(key: &str, value: &str) -> Result<Vec<CycleMode>, String> {
+    let modes = parse_string_list(key, value)?
+        .iter()
+        .map(|name| {
+            CycleMode::from_name(name)
+                .ok_or_else(|| format!("{} has an unknown mode \"{}\"", key, name))
+        })
+        .collect::<Result<Vec<CycleMode>, String>>()?;
+    if modes.is_empty() {
+        return Err(format!("{} needs at least one mode", key));
+    }
+    Ok(modes)
+}
+
+fn parse_u64(key: &str, value: &str) -> Result<u64, String> {
+    value
+        .replace('_', "")
+        .parse()
+        .map_err(|_| format!("{} must be a non-negative integer", key))
+}
+
+/// Parses a timer interval of at least `min_ms`, or zero if `zero_disables` is set
+fn parse_interval(key: &str, value: &str, min_ms: u64, zero_disables: bool) -> Result<u64, String> {
+    let ms = parse_u64(key, value)?;
+    if ms < min_ms && !(zero_disables && ms == 0) {
+        return Err(format!("{} must be at least {}", key, min_ms));
+    }
+    Ok(ms)
+}
+
+fn parse_u32(key: &str, value: &str) -> Result<u32, String> {
+    value
+        .replace('_', "")
+        .parse()
+        .map_err(|_| format!("{} must be a non-negative integer", key))
+}
+
+fn parse_u8(key: &str, value: &str) -> Result<u8, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be an integer from 0 to 255", key))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false", key)),
+    }
+}
+
+/// Parses a basic string (`"..."`, with escapes) or a literal string (`'...'`, taken as is)
+fn parse_string(key: &str, value: &str) -> Result<String, String> {
+    let not_quoted = || format!("{} must be a quoted string", key);
+    if let Some(literal) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        if literal.contains('\'') {
+            return Err(not_quoted());
+        }
+        return Ok(literal.to_string());
+    }
+
+    let basic = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(not_quoted)?;
+    let mut text = String::with_capacity(basic.len());
+    let mut chars = basic.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\\') => text.push('\\'),
+                Some('"') => text.push('"'),
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                _ => {
+                    return Err(format!(
+                        "{} has an unsupported escape; write \\\\ for a backslash or use '...'",
+                        key
+                    ))
+                }
+            },
+            '"' => return Err(not_quoted()),
+            c => text.push(c),
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_empty_text_gives_the_defaults() {
+        let config = parse("# only a comment\n\n").unwrap();
+        assert_eq!(config.reveal_delay_ms, DEFAULT_REVEAL_DELAY_MS);
+        assert!(config.reveal_mode == RevealMode::Both);
+        assert!(config.hover_edge.is_none());
+    }
+
+    #[test]
+    fn parse_reads_every_kind_of_value() {
+        let config = parse(
+            "reveal_delay_ms = 1_000\n\
+             reveal_mode = \"edge_hover\"\n\
+             hover_edge = \"top\" # trailing comment\n\
+             hide_style = \"transparent\"\n\
+             hide_alpha = 32\n\
+             animation_style = \"fade\"\n\
+             logging = true\n\
+             always_visible_monitors = [2, \"display3\"]\n\
+             on_show_command = \"notify #1\"\n\
+             reveal_hotkey = \"Ctrl+F9\"\n\
+             fallback_hotkey = \"\"\n\
+             some_future_key = 1\n",
+        )
+        .unwrap();
+        assert_eq!(config.reveal_delay_ms, 1000);
+        assert!(config.reveal_mode == RevealMode::EdgeHover);
+        assert_eq!(config.hover_edge, Some(Edge::Top));
+        assert!(config.hide_style == HideStyle::Transparent);
+        assert_eq!(config.hide_alpha, 32);
+        assert!(config.animation_style == AnimationStyle::Fade);
+        assert!(config.logging);
+        assert_eq!(config.always_visible_monitors, ["DISPLAY2", "DISPLAY3"]);
+        assert_eq!(config.on_show_command, "notify #1");
+        assert_eq!(config.reveal_hotkey, HotkeySpec::parse("Ctrl+F9").unwrap());
+        assert!(config.fallback_hotkey.is_none());
+    }
+
+    #[test]
+    fn parse_reports_the_bad_line() {
+        let error = parse("reveal_delay_ms = 100\nnot a setting\n").err().unwrap();
+        assert!(error.contains("line 2"), "{}", error);
+        assert!(parse("reveal_delay_ms = -1").is_err());
+        assert!(parse("reveal_mode = winkey").is_err());
+        assert!(parse("hide_alpha = 256").is_err());
+        assert!(parse("logging = yes").is_err());
+        assert!(parse("quit_hotkey = \"Ctrl+Alt\"").is_err());
+    }
+
+    #[test]
+    fn default_config_matches_the_built_in_defaults() {
+        let written = parse(DEFAULT_CONFIG).unwrap();
+        let default = Config::default();
+        assert_eq!(written.reveal_delay_ms, default.reveal_delay_ms);
+        assert_eq!(written.hold_threshold_ms, default.hold_threshold_ms);
+        assert!(written.reveal_mode == default.reveal_mode);
+        assert_eq!(written.hover_edge, default.hover_edge);
+        assert_eq!(written.reveal_hotkey, default.reveal_hotkey);
+        assert_eq!(written.fallback_hotkey, default.fallback_hotkey);
+        assert_eq!(written.quit_hotkey, default.quit_hotkey);
+        assert!(written.hide_style == default.hide_style);
+        assert_eq!(written.hide_alpha, default.hide_alpha);
+        assert_eq!(written.animation_ms, default.animation_ms);
+        assert_eq!(written.min_visible_ms, default.min_visible_ms);
+        assert_eq!(written.peek_ms, default.peek_ms);
+        assert_eq!(written.logging, default.logging);
+    }
+
+    #[test]
+    fn instant_zeroes_every_delay() {
+        let config = parse("instant = true\nreveal_delay_ms = 800\nhold_threshold_ms = 200\n")
+            .unwrap();
+        assert!(config.instant);
+        assert_eq!(config.reveal_delay_ms, 0);
+        assert_eq!(config.hold_threshold_ms, 0);
+        assert_eq!(config.min_visible_ms, 0);
+        assert_eq!(config.animation_ms, 0);
+        assert_eq!(config.peek_ms, DEFAULT_PEEK_MS);
+    }
+
+    #[test]
+    fn validate_path_needs_an_existing_folder() {
+        let dir = std::env::temp_dir();
+        assert_eq!(validate_path(&dir.join("custom.toml")).unwrap(), dir.join("custom.toml"));
+        assert!(validate_path(&dir).is_err());
+        assert!(validate_path(&dir.join("no-such-folder").join("custom.toml")).is_err());
+        assert!(validate_path(Path::new("")).is_err());
+        assert!(validate_path(Path::new("relative.toml")).unwrap().is_absolute());
+    }
+
+    #[test]
+    fn update_text_rewrites_lines_in_place() {
+        let text = "# settings\nreveal_delay_ms = 400 # ms\npaused = false\n";
+        let values = [("paused", "true".to_string()), ("peek_ms", "500".to_string())];
+        assert_eq!(
+            update_text(text, &values),
+            "# settings\nreveal_delay_ms = 400 # ms\npaused = true\npeek_ms = 500\n"
+        );
+    }
+
+    #[test]
+    fn update_text_leaves_sections_alone() {
+        let text = "reveal_delay_ms = 400\n\n[monitor.DISPLAY2]\nreveal_mode = \"winkey\"\n";
+        let values = [("reveal_mode", "\"both\"".to_string())];
+        assert_eq!(
+            update_text(text, &values),
+            "reveal_delay_ms = 400\n\nreveal_mode = \"both\"\n[monitor.DISPLAY2]\n\
+             reveal_mode = \"winkey\"\n"
+        );
+    }
+
+    #[test]
+    fn parse_reads_monitor_sections() {
+        let config = parse(
+            "reveal_delay_ms = 400\n\
+             [monitor.2]\n\
+             reveal_mode = \"edge_hover\"\n\
+             reveal_delay_ms = 1000\n\
+             [future.section]\n\
+             anything = 1\n\
+             [monitor.display2]\n\
+             min_visible_ms = 0\n",
+        )
+        .unwrap();
+        assert_eq!(config.reveal_delay_ms, 400);
+        assert_eq!(
+            config.monitor_sections,
+            [MonitorSection {
+                device: "DISPLAY2".to_string(),
+                reveal_mode: Some(RevealMode::EdgeHover),
+                reveal_delay_ms: Some(1000),
+                min_visible_ms: Some(0),
+            }]
+        );
+        assert!(parse("[monitor.3]\nhide_style = \"hide\"\n").is_err());
+        let text = config.to_text();
+        assert_eq!(parse(&text).unwrap().monitor_sections, config.monitor_sections);
+    }
+
+    #[test]
+    fn update_text_ignores_keys_inside_comments() {
+        let text = "# paused = true\n";
+        let values = [("paused", "false".to_string())];
+        assert_eq!(update_text(text, &values), "# paused = true\npaused = false\n");
+    }
+
+    #[test]
+    fn strip_comment_keeps_hashes_in_strings() {
+        assert_eq!(strip_comment("key = 1 # note"), "key = 1 ");
+        assert_eq!(strip_comment("key = \"a # b\" # note"), "key = \"a # b\" ");
+        assert_eq!(strip_comment("# whole line"), "");
+        assert_eq!(strip_comment("key = 1"), "key = 1");
+        assert_eq!(strip_comment(r#"key = "a \" # b" # note"#), r#"key = "a \" # b" "#);
+        assert_eq!(strip_comment(r"key = 'C:\#1\' # note"), r"key = 'C:\#1\' ");
+    }
+
+    #[test]
+    fn parse_string_handles_escapes_and_literals() {
+        assert_eq!(parse_string("k", r#""C:\\Tools\\dim.exe""#).unwrap(), r"C:\Tools\dim.exe");
+        assert_eq!(parse_string("k", r#""say \"hi\"""#).unwrap(), r#"say "hi""#);
+        let literal = parse_string("k", r"'C:\Tools\dim.exe --off'").unwrap();
+        assert_eq!(literal, r"C:\Tools\dim.exe --off");
+        assert_eq!(parse_string("k", r#"'"quoted" as is'"#).unwrap(), r#""quoted" as is"#);
+        assert!(parse_string("k", r#""C:\Tools""#).is_err());
+        assert!(parse_string("k", r#""a\""#).is_err());
+        assert!(parse_string("k", r#""a"b""#).is_err());
+        assert!(parse_string("k", "'a'b'").is_err());
+        assert!(parse_string("k", "unquoted").is_err());
+    }
+
+    #[test]
+    fn parse_monitors_accepts_numbers_and_names() {
+        assert_eq!(
+            parse_monitors("k", r#"[1, '\\.\display2', "DISPLAY3"]"#).unwrap(),
+            ["DISPLAY1", "DISPLAY2", "DISPLAY3"]
+        );
+        assert!(parse_monitors("k", "[]").unwrap().is_empty());
+        assert!(parse_monitors("k", "2").is_err());
+        assert!(parse_monitors("k", "[display2]").is_err());
+        let monitors = ["DISPLAY1".to_string(), "DISPLAY3".to_string()];
+        assert_eq!(parse_monitors("k", &format_monitors(&monitors)).unwrap(), monitors);
+    }
+
+    #[test]
+    fn parse_string_list_needs_quoted_items() {
+        assert_eq!(
+            parse_string_list("k", r#"["explorer.exe", 'osk.exe']"#).unwrap(),
+            ["explorer.exe", "osk.exe"]
+        );
+        assert!(parse_string_list("k", "[]").unwrap().is_empty());
+        assert!(parse_string_list("k", "[explorer.exe]").is_err());
+        assert!(parse_string_list("k", r#""explorer.exe""#).is_err());
+    }
+
+    #[test]
+    fn parse_menu_always_keeps_quit() {
+        assert_eq!(
+            parse_menu("k", r#"["settings", "open_config"]"#).unwrap(),
+            [MenuItem::Settings, MenuItem::OpenConfig, MenuItem::Quit]
+        );
+        let menu = parse_menu("k", r#"["quit", "about"]"#).unwrap();
+        assert_eq!(menu, [MenuItem::Quit, MenuItem::About]);
+        assert!(parse_menu("k", r#"["settings", "exit"]"#).is_err());
+    }
+
+    #[test]
+    fn to_text_round_trips_through_parse() {
+        let config = parse(
+            "reveal_mode = \"winkey\"\n\
+             hover_edge = \"left\"\n\
+             fallback_hotkey = \"\"\n\
+             mode_cycle_hotkey = \"Ctrl+Alt+M\"\n\
+             mode_cycle = [\"paused\", \"both\"]\n\
+             hide_style = \"transparent\"\n\
+             animation_style = \"fade\"\n\
+             hide_only_in = [\"mstsc.exe\"]\n\
+             always_visible_monitors = [2]\n\
+             on_show_command = 'cmd /c \"C:\\tools\\show.bat\" # 1'\n\
+             autostart_scope = \"all_users\"\n\
+             tray_menu = [\"export\", \"separator\", \"import\"]\n",
+        )
+        .unwrap();
+        let text = config.to_text();
+        let reparsed = parse(&text).unwrap();
+        assert_eq!(reparsed.to_text(), text);
+        assert!(reparsed.fallback_hotkey.is_none());
+        assert_eq!(reparsed.on_show_command, r#"cmd /c "C:\tools\show.bat" # 1"#);
+        assert_eq!(reparsed.always_visible_monitors, ["DISPLAY2"]);
+        let defaults = Config::default().to_text();
+        assert_eq!(parse(&defaults).unwrap().to_text(), defaults);
+    }
+
+    #[test]
+    fn parse_scan_codes_reads_hex_and_decimal() {
+        assert_eq!(parse_scan_codes("k", "[0xE05B, 58]").unwrap(), [0xE05B, 58]);
+        assert!(parse_scan_codes("k", "[]").unwrap().is_empty());
+        assert!(parse_scan_codes("k", "[0]").is_err());
+        assert!(parse_scan_codes("k", "[0xZZ]").is_err());
+        assert!(parse_scan_codes("k", "[1, 2, 3, 4, 5]").is_err());
+        assert_eq!(format_scan_codes(&[0xE05B, 58]), "[0xE05B, 0x3A]");
+    }
+
+    #[test]
+    fn intervals_below_the_minimum_are_rejected() {
+        assert_eq!(parse("poll_interval_ms = 2000").unwrap().poll_interval_ms, 2000);
+        assert!(parse("poll_interval_ms = 50").is_err());
+        assert!(parse("poll_interval_ms = 0").is_err());
+        assert_eq!(parse("watchdog_interval_ms = 0").unwrap().watchdog_interval_ms, 0);
+        assert!(parse("watchdog_interval_ms = 200").is_err());
+    }
+
+    #[test]
+    fn mode_cycle_wraps_around_and_starts_over_from_an_unlisted_mode() {
+        let cycle = parse_cycle("k", r#"["winkey", "paused"]"#).unwrap();
+        let winkey = CycleMode::Reveal(RevealMode::WinKey);
+        assert_eq!(CycleMode::next(&cycle, RevealMode::WinKey, false), Some(CycleMode::Paused));
+        assert_eq!(CycleMode::next(&cycle, RevealMode::WinKey, true), Some(winkey));
+        assert_eq!(CycleMode::next(&cycle, RevealMode::EdgeHover, false), Some(winkey));
+        assert!(parse_cycle("k", "[]").is_err());
+        assert!(parse_cycle("k", r#"["inverted"]"#).is_err());
+    }
+}
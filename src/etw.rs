@@ -0,0 +1,78 @@
+//! ETW tracing module
+//!
+//! Emits diagnostic events through an Event Tracing for Windows provider when
+//! built with the `etw` feature. Without the feature every call is a no-op.
+
+#[cfg(feature = "etw")]
+mod provider {
+    use std::ptr::null;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use windows_sys::core::GUID;
+    use windows_sys::Win32::System::Diagnostics::Etw::{
+        EventRegister, EventUnregister, EventWriteString, REGHANDLE,
+    };
+
+    /// Provider GUID: {fad45e9f-a679-4b12-9fb7-eff4a4bcb166}
+    const PROVIDER_ID: GUID = GUID::from_u128(0xfad45e9f_a679_4b12_9fb7_eff4a4bcb166);
+
+    static REG_HANDLE: AtomicU64 = AtomicU64::new(0);
+
+    pub fn register() {
+        unsafe {
+            let mut handle = 0u64;
+            if EventRegister(&PROVIDER_ID, None, null(), &mut handle) == 0 {
+                REG_HANDLE.store(handle, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn unregister() {
+        let handle = REG_HANDLE.swap(0, Ordering::SeqCst);
+        if handle != 0 {
+            unsafe {
+                EventUnregister(handle as REGHANDLE);
+            }
+        }
+    }
+
+    pub fn write(level: u8, message: &str) {
+        let handle = REG_HANDLE.load(Ordering::SeqCst);
+        if handle == 0 {
+            return;
+        }
+        let wide: Vec<u16> = message.encode_utf16().chain(std::iter::once(0)).collect();
+        unsafe {
+            EventWriteString(handle as REGHANDLE, level, 0, wide.as_ptr());
+        }
+    }
+}
+
+// Standard ETW trace levels
+#[cfg(feature = "etw")]
+const LEVEL_ERROR: u8 = 2;
+#[cfg(feature = "etw")]
+const LEVEL_INFO: u8 = 4;
+
+/// Registers the ETW provider
+pub fn register() {
+    #[cfg(feature = "etw")]
+    provider::register();
+}
+
+/// Unregisters the ETW provider
+pub fn unregister() {
+    #[cfg(feature = "etw")]
+    provider::unregister();
+}
+
+/// Writes an informational event
+pub fn info(_message: &str) {
+    #[cfg(feature = "etw")]
+    provider::write(LEVEL_INFO, _message);
+}
+
+/// Writes an error event
+pub fn error(_message: &str) {
+    #[cfg(feature = "etw")]
+    provider::write(LEVEL_ERROR, _message);
+}
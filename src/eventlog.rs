@@ -0,0 +1,85 @@
+//! Event log module
+//!
+//! Reports startup and config reload failures to the Windows Application event
+//! log, since a windows-subsystem app has no console for `eprintln!`.
+//! Interactive sessions also get a message box.
+
+use std::ptr::{null, null_mut};
+use windows_sys::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+};
+use windows_sys::Win32::System::StationsAndDesktops::{
+    GetProcessWindowStation, GetUserObjectInformationW, UOI_FLAGS, USEROBJECTFLAGS,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    MessageBoxW, MB_ICONERROR, MB_OK, MB_SETFOREGROUND, WSF_VISIBLE,
+};
+
+/// Event source name, shown in the Source column of Event Viewer
+const SOURCE_NAME: &str = "Taskbar Hider";
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Writes an error to the event log and shows it to an interactive user
+pub fn report_error(message: &str) {
+    write_event(message);
+    if is_interactive() {
+        let text = wide_string(message);
+        let title = wide_string(SOURCE_NAME);
+        unsafe {
+            MessageBoxW(
+                null_mut(),
+                text.as_ptr(),
+                title.as_ptr(),
+                MB_OK | MB_ICONERROR | MB_SETFOREGROUND,
+            );
+        }
+    }
+}
+
+/// Adds an error entry to the Application event log
+fn write_event(message: &str) {
+    let source = wide_string(SOURCE_NAME);
+    let text = wide_string(message);
+    let strings = [text.as_ptr()];
+    unsafe {
+        let handle = RegisterEventSourceW(null(), source.as_ptr());
+        if handle.is_null() {
+            return;
+        }
+        ReportEventW(
+            handle,
+            EVENTLOG_ERROR_TYPE,
+            0,
+            0,
+            null_mut(),
+            strings.len() as u16,
+            0,
+            strings.as_ptr(),
+            null(),
+        );
+        DeregisterEventSource(handle);
+    }
+}
+
+/// Returns true if the process runs on a window station a user can see
+fn is_interactive() -> bool {
+    unsafe {
+        let station = GetProcessWindowStation();
+        if station.is_null() {
+            return false;
+        }
+        let mut flags: USEROBJECTFLAGS = std::mem::zeroed();
+        GetUserObjectInformationW(
+            station,
+            UOI_FLAGS,
+            &mut flags as *mut _ as *mut _,
+            size_of_val(&flags) as u32,
+            null_mut(),
+        ) != 0
+            && flags.dwFlags & WSF_VISIBLE as u32 != 0
+    }
+}
@@ -0,0 +1,262 @@
+//! System flyout module
+//!
+//! Watches shell windows such as the volume OSD appearing and going away, so
+//! the taskbar can stay up while they are open. Some flyouts reveal a hidden
+//! taskbar; others only keep a visible one from hiding under them.
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    PostMessageW, CHILDID_SELF, EVENT_OBJECT_DESTROY, EVENT_OBJECT_HIDE, EVENT_OBJECT_SHOW,
+    OBJID_WINDOW, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_USER,
+};
+use crate::shell;
+use taskbar_hider::compat;
+use taskbar_hider::config::Config;
+use taskbar_hider::winapi::Error;
+
+/// Posted when a watched flyout opens or closes; `wparam` holds the `OPEN_` flags of
+/// the flyouts still open
+pub const WM_FLYOUT_CHANGED: u32 = WM_USER + 170;
+/// A flyout that reveals the taskbar is open
+pub const OPEN_REVEALING: usize = 1;
+/// A flyout that keeps an already visible taskbar up is open
+pub const OPEN_HOLDING: usize = 2;
+
+const SHELL_EXE: &str = "explorer.exe";
+/// Host of the volume, brightness and media OSD
+const VOLUME_OSD_CLASS: &str = "NativeHWNDHost";
+/// XAML island the Windows 11 shell hosts its newer flyouts in
+const XAML_HOST_CLASS: &str = "XamlExplorerHostIslandWindow";
+const CORE_WINDOW_CLASS: &str = "Windows.UI.Core.CoreWindow";
+/// Process showing the Win+Shift+S screen clip overlay
+const SNIP_HOST_EXE: &str = "screenclippinghost.exe";
+/// Standard dialog box class, as used by the shutdown and other system prompts
+const DIALOG_CLASS: &str = "#32770";
+/// Windows 10 host of the Win+Space input language switcher
+const INPUT_SWITCH_CLASS: &str = "Shell_InputSwitchTopLevelWindow";
+/// The desktop On-Screen Keyboard
+const OSK_CLASS: &str = "OSKMainClass";
+const OSK_EXE: &str = "osk.exe";
+/// The touch keyboard shown by TabTip
+const TOUCH_KEYBOARD_CLASS: &str = "IPTip_Main_Window";
+const TOUCH_KEYBOARD_EXE: &str = "tabtip.exe";
+
+/// A shell window the taskbar can stay up for
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Flyout {
+    /// The volume / audio device OSD shown by the media keys
+    VolumeOsd,
+    /// The Windows 11 snap layouts flyout
+    SnapLayouts,
+    /// The screen clip overlay, so the taskbar can be captured
+    SnipOverlay,
+    /// A dialog box shown by one of the configured processes
+    Dialog,
+    /// The Win+Space input language switcher
+    InputSwitcher,
+    /// The On-Screen Keyboard or touch keyboard, so its tray button stays in reach
+    OnScreenKeyboard,
+}
+
+impl Flyout {
+    /// Returns true if the flyout reveals a hidden taskbar instead of only keeping it up
+    fn reveals(self) -> bool {
+        matches!(
+            self,
+            Flyout::VolumeOsd | Flyout::SnipOverlay | Flyout::Dialog | Flyout::OnScreenKeyboard
+        )
+    }
+
+    /// Returns the window class and owning executable of each window hosting the
+    /// flyout on this Windows version
+    fn hosts(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Flyout::VolumeOsd => &[(VOLUME_OSD_CLASS, SHELL_EXE)],
+            // The island also hosts Snap Assist and Task View, which only keep a
+            // visible bar up as well
+            Flyout::SnapLayouts if compat::is_windows_11() => &[(XAML_HOST_CLASS, SHELL_EXE)],
+            Flyout::SnapLayouts => &[],
+            Flyout::SnipOverlay => &[(CORE_WINDOW_CLASS, SNIP_HOST_EXE)],
+            // Matched against the configured processes instead
+            Flyout::Dialog => &[],
+            // Windows 11 shows the switcher in the same island as snap layouts
+            Flyout::InputSwitcher if compat::is_windows_11() => &[(XAML_HOST_CLASS, SHELL_EXE)],
+            Flyout::InputSwitcher => &[(INPUT_SWITCH_CLASS, SHELL_EXE)],
+            Flyout::OnScreenKeyboard => {
+                &[(OSK_CLASS, OSK_EXE), (TOUCH_KEYBOARD_CLASS, TOUCH_KEYBOARD_EXE)]
+            }
+        }
+    }
+
+    /// Returns true if the window is this flyout
+    fn matches(self, hwnd: HWND, class: &str) -> bool {
+        if self == Flyout::Dialog {
+            return class == DIALOG_CLASS && is_dialog_owner(hwnd);
+        }
+        // The class check is cheap and filters out nearly every other window
+        self.hosts().iter().any(|&(host_class, exe)| {
+            class == host_class && shell::owner_exe_name(hwnd).as_deref() == Some(exe)
+        })
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+const FLYOUTS: &[Flyout] = &[
+    Flyout::VolumeOsd,
+    Flyout::SnapLayouts,
+    Flyout::SnipOverlay,
+    Flyout::Dialog,
+    Flyout::InputSwitcher,
+    Flyout::OnScreenKeyboard,
+];
+
+/// Returns the flyouts the config keeps the taskbar up for
+pub fn configured(config: &Config) -> Vec<Flyout> {
+    let mut flyouts = Vec::new();
+    if config.reveal_on_volume_osd {
+        flyouts.push(Flyout::VolumeOsd);
+    }
+    if config.keep_on_snap_layouts {
+        flyouts.push(Flyout::SnapLayouts);
+    }
+    if config.reveal_on_snip {
+        flyouts.push(Flyout::SnipOverlay);
+    }
+    if !config.reveal_on_dialogs.is_empty() {
+        flyouts.push(Flyout::Dialog);
+    }
+    if config.keep_on_input_switcher {
+        flyouts.push(Flyout::InputSwitcher);
+    }
+    if config.reveal_on_keyboard {
+        flyouts.push(Flyout::OnScreenKeyboard);
+    }
+    flyouts
+}
+
+static HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static NOTIFY_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+/// Watched flyouts as `Flyout::bit` flags
+static WATCHED: AtomicU32 = AtomicU32::new(0);
+/// Executables whose dialog boxes reveal the taskbar, lowercase
+static DIALOG_OWNERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// Flyout windows currently open, stored as addresses since `HWND` isn't `Send`
+static OPEN: Mutex<Vec<(usize, Flyout)>> = Mutex::new(Vec::new());
+
+/// Returns true if the dialog's process is one of the configured dialog owners
+fn is_dialog_owner(hwnd: HWND) -> bool {
+    let Some(exe) = shell::owner_exe_name(hwnd) else {
+        return false;
+    };
+    DIALOG_OWNERS.lock().is_ok_and(|owners| owners.contains(&exe))
+}
+
+/// Sets the executables, e.g. `explorer.exe`, whose dialog boxes reveal the taskbar
+pub fn set_dialog_owners(owners: &[String]) {
+    if let Ok(mut dialog_owners) = DIALOG_OWNERS.lock() {
+        *dialog_owners = owners.iter().map(|owner| owner.to_ascii_lowercase()).collect();
+    }
+}
+
+/// Returns the watched flyout the window is, if any
+fn watched_flyout(hwnd: HWND) -> Option<Flyout> {
+    let watched = WATCHED.load(Ordering::Relaxed);
+    let class = shell::window_class(hwnd);
+    FLYOUTS
+        .iter()
+        .copied()
+        .find(|flyout| watched & flyout.bit() != 0 && flyout.matches(hwnd, &class))
+}
+
+/// Returns the `OPEN_` flags for the open flyouts
+fn open_flags(open: &[(usize, Flyout)]) -> usize {
+    open.iter().fold(0, |flags, &(_, flyout)| {
+        flags | if flyout.reveals() { OPEN_REVEALING } else { OPEN_HOLDING }
+    })
+}
+
+/// Window shown, hidden or destroyed event callback
+unsafe extern "system" fn flyout_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    id_child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    if id_object != OBJID_WINDOW || id_child != CHILDID_SELF as i32 || hwnd.is_null() {
+        return;
+    }
+    let Ok(mut open) = OPEN.lock() else {
+        return;
+    };
+
+    let was_open = open_flags(&open);
+    let position = open.iter().position(|&(open_hwnd, _)| open_hwnd == hwnd as usize);
+    if event == EVENT_OBJECT_SHOW {
+        // A destroyed window can't be classified, so only shown ones are checked
+        if position.is_some() {
+            return;
+        }
+        let Some(flyout) = watched_flyout(hwnd) else {
+            return;
+        };
+        open.push((hwnd as usize, flyout));
+    } else {
+        let Some(index) = position else {
+            return;
+        };
+        open.remove(index);
+    }
+
+    let now_open = open_flags(&open);
+    if now_open != was_open {
+        PostMessageW(NOTIFY_HWND.load(Ordering::SeqCst) as HWND, WM_FLYOUT_CHANGED, now_open, 0);
+    }
+}
+
+/// Start watching for the given flyouts
+pub fn install(notify_hwnd: HWND, flyouts: &[Flyout]) -> Result<(), Error> {
+    unsafe {
+        NOTIFY_HWND.store(notify_hwnd as *mut _, Ordering::SeqCst);
+        let watched = flyouts.iter().fold(0, |bits, flyout| bits | flyout.bit());
+        WATCHED.store(watched, Ordering::SeqCst);
+
+        let hook = SetWinEventHook(
+            EVENT_OBJECT_DESTROY,
+            EVENT_OBJECT_HIDE,
+            null_mut(),
+            Some(flyout_event_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+        );
+        if hook.is_null() {
+            return Err(Error::last("Failed to install flyout watcher"));
+        }
+
+        HOOK_HANDLE.store(hook, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Stop watching for flyouts and forget the open ones
+pub fn uninstall() {
+    unsafe {
+        let hook = HOOK_HANDLE.swap(null_mut(), Ordering::SeqCst);
+        if !hook.is_null() {
+            UnhookWinEvent(hook);
+        }
+    }
+    if let Ok(mut open) = OPEN.lock() {
+        open.clear();
+    }
+}
@@ -0,0 +1,126 @@
+//! Foreground window module
+//!
+//! Watches foreground window changes so the taskbar can stay up while the
+//! bare desktop is focused, and optionally the foreground window moving or
+//! resizing so smart hide can tell whether it covers the taskbar.
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, PostMessageW, EVENT_OBJECT_LOCATIONCHANGE, EVENT_SYSTEM_FOREGROUND,
+    EVENT_SYSTEM_MOVESIZEEND, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+    WM_USER,
+};
+use crate::shell;
+use taskbar_hider::winapi::Error;
+
+/// Posted when the foreground window changes, moves or resizes; `wparam` is 1 if it
+/// is the desktop
+pub const WM_FOREGROUND_CHANGED: u32 = WM_USER + 150;
+
+static HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+/// Location change hook, only installed while moves are tracked
+static MOVE_HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static NOTIFY_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+
+/// Tells the main window about the foreground window
+unsafe fn notify(hwnd: HWND) {
+    let desktop = shell::is_desktop_window(hwnd);
+    PostMessageW(
+        NOTIFY_HWND.load(Ordering::SeqCst) as HWND,
+        WM_FOREGROUND_CHANGED,
+        desktop as usize,
+        0,
+    );
+}
+
+/// Foreground change and move/size end event callback
+unsafe extern "system" fn foreground_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    _id_object: i32,
+    _id_child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    // The hook's range also covers menu and capture events
+    if event != EVENT_SYSTEM_FOREGROUND && event != EVENT_SYSTEM_MOVESIZEEND {
+        return;
+    }
+    // Clicking the taskbar focuses it; that shouldn't count as leaving the desktop
+    if hwnd.is_null() || shell::is_taskbar_window(hwnd) {
+        return;
+    }
+    notify(hwnd);
+}
+
+/// Location change event callback, for maximizing and restoring the foreground window
+unsafe extern "system" fn location_event_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    _id_child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    // Fires for every caret and cursor move too, so only the foreground window counts
+    if id_object == OBJID_WINDOW && !hwnd.is_null() && hwnd == GetForegroundWindow() {
+        notify(hwnd);
+    }
+}
+
+/// Start watching for foreground changes, and also for moves if `track_moves` is set
+pub fn install(notify_hwnd: HWND, track_moves: bool) -> Result<(), Error> {
+    unsafe {
+        NOTIFY_HWND.store(notify_hwnd as *mut _, Ordering::SeqCst);
+
+        let last_event =
+            if track_moves { EVENT_SYSTEM_MOVESIZEEND } else { EVENT_SYSTEM_FOREGROUND };
+        let hook = SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            last_event,
+            null_mut(),
+            Some(foreground_event_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+        );
+        if hook.is_null() {
+            return Err(Error::last("Failed to install foreground watcher"));
+        }
+        HOOK_HANDLE.store(hook, Ordering::SeqCst);
+
+        if track_moves {
+            let hook = SetWinEventHook(
+                EVENT_OBJECT_LOCATIONCHANGE,
+                EVENT_OBJECT_LOCATIONCHANGE,
+                null_mut(),
+                Some(location_event_proc),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            );
+            if hook.is_null() {
+                return Err(Error::last("Failed to install foreground move watcher"));
+            }
+            MOVE_HOOK_HANDLE.store(hook, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+/// Stop watching for foreground changes
+pub fn uninstall() {
+    unsafe {
+        for handle in [&HOOK_HANDLE, &MOVE_HOOK_HANDLE] {
+            let hook = handle.swap(null_mut(), Ordering::SeqCst);
+            if !hook.is_null() {
+                UnhookWinEvent(hook);
+            }
+        }
+    }
+}
@@ -1,21 +1,156 @@
 //! Keyboard hook module
 //!
-//! Installs a low-level keyboard hook to track Windows key state.
+//! Installs a low-level keyboard hook to track the state of the reveal hotkey
+//! (the Windows key by default, also matched by scan code if configured), and a
+//! desktop-switch event hook so key state can be resynchronized after the secure
+//! desktop (Ctrl+Alt+Del, UAC) has been shown.
 
+use std::mem::size_of;
 use std::ptr::null_mut;
-use std::sync::atomic::{AtomicPtr, Ordering};
-use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
-use windows_sys::Win32::UI::Input::KeyboardAndMouse::{VK_LWIN, VK_RWIN};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows_sys::Win32::System::StationsAndDesktops::{
+    CloseDesktop, OpenInputDesktop, DESKTOP_SWITCHDESKTOP,
+};
+use windows_sys::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, GetLastInputInfo, RegisterHotKey, UnregisterHotKey, LASTINPUTINFO, MOD_ALT,
+    MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, VK_ESCAPE, VK_LCONTROL, VK_LMENU, VK_LSHIFT,
+    VK_LBUTTON, VK_LWIN, VK_MBUTTON, VK_RBUTTON, VK_RCONTROL, VK_RMENU, VK_RSHIFT, VK_RWIN,
+    VK_XBUTTON1, VK_XBUTTON2,
+};
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    CallNextHookEx, PostMessageW, SetWindowsHookExW, UnhookWindowsHookEx,
-    KBDLLHOOKSTRUCT, WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_USER,
+    CallNextHookEx, GetCursorPos, PostMessageW, SetWindowsHookExW, UnhookWindowsHookEx,
+    EVENT_SYSTEM_DESKTOPSWITCH, KBDLLHOOKSTRUCT, LLKHF_EXTENDED, WH_KEYBOARD_LL,
+    WINEVENT_OUTOFCONTEXT,
+    WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_USER,
 };
+use taskbar_hider::config::MAX_WIN_SCAN_CODES;
+use taskbar_hider::hotkey::HotkeySpec;
+use taskbar_hider::winapi::Error;
 
 pub const WM_WINKEY_DOWN: u32 = WM_USER + 100;
 pub const WM_WINKEY_UP: u32 = WM_USER + 101;
+pub const WM_DESKTOP_SWITCH: u32 = WM_USER + 102;
+/// A key went down while key code logging is on; wparam is the vk, lparam the scan code
+pub const WM_KEY_CODES: u32 = WM_USER + 103;
+
+/// `RegisterHotKey` ID of the fallback reveal hotkey
+pub const FALLBACK_HOTKEY_ID: i32 = 1;
+/// `RegisterHotKey` ID of the quit hotkey
+pub const QUIT_HOTKEY_ID: i32 = 2;
+/// `RegisterHotKey` ID of the mode cycle hotkey
+pub const MODE_CYCLE_HOTKEY_ID: i32 = 3;
+
+/// Modifier keys tracked by the hook; a key's index is its bit in `KEYS_DOWN`
+const MODIFIER_KEYS: [(u16, u32); 8] = [
+    (VK_LWIN, MOD_WIN),
+    (VK_RWIN, MOD_WIN),
+    (VK_LCONTROL, MOD_CONTROL),
+    (VK_RCONTROL, MOD_CONTROL),
+    (VK_LMENU, MOD_ALT),
+    (VK_RMENU, MOD_ALT),
+    (VK_LSHIFT, MOD_SHIFT),
+    (VK_RSHIFT, MOD_SHIFT),
+];
+const TRIGGER_KEY_BIT: u32 = 1 << MODIFIER_KEYS.len();
+/// Set while a key matched by one of `WIN_SCAN_CODES` is down; counts as the Windows key
+const SCAN_WIN_KEY_BIT: u32 = TRIGGER_KEY_BIT << 1;
+/// Extended keys such as the Windows keys report their scan code with this prefix
+const EXTENDED_SCAN_PREFIX: u32 = 0xE000;
 
 static HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static DESKTOP_HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
 static NOTIFY_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+static SPEC_MODIFIERS: AtomicU32 = AtomicU32::new(MOD_WIN);
+static SPEC_KEY: AtomicU32 = AtomicU32::new(0);
+/// Scan codes that also count as the Windows key; zero marks an unused slot
+static WIN_SCAN_CODES: [AtomicU32; MAX_WIN_SCAN_CODES] =
+    [const { AtomicU32::new(0) }; MAX_WIN_SCAN_CODES];
+/// Report the codes of every pressed key so they can be logged
+static LOG_KEY_CODES: AtomicBool = AtomicBool::new(false);
+static KEYS_DOWN: AtomicU32 = AtomicU32::new(0);
+static HOTKEY_ACTIVE: AtomicBool = AtomicBool::new(false);
+static CTRL_ESCAPE_ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Tick count of the last key event the hook saw
+static LAST_KEY_TIME: AtomicU32 = AtomicU32::new(0);
+/// `GetLastInputInfo` time and packed cursor position at the previous health check
+static LAST_INPUT_TIME: AtomicU32 = AtomicU32::new(0);
+static LAST_CURSOR: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the `MOD_*` flags of the modifier keys in `down`
+fn held_modifiers(down: u32) -> u32 {
+    let scan_win = if down & SCAN_WIN_KEY_BIT != 0 { MOD_WIN } else { 0 };
+    MODIFIER_KEYS
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| down & (1 << i) != 0)
+        .fold(scan_win, |mods, (_, &(_, flag))| mods | flag)
+}
+
+/// Returns true if `scan` is one of the configured Windows key scan codes
+fn is_win_scan_code(scan: u32) -> bool {
+    WIN_SCAN_CODES.iter().any(|code| code.load(Ordering::Relaxed) == scan)
+}
+
+/// Sets the scan codes that also count as the Windows key, and whether the codes
+/// of every pressed key are reported with `WM_KEY_CODES`
+///
+/// Codes past `MAX_WIN_SCAN_CODES` are ignored; the config rejects them anyway.
+pub fn set_win_scan_codes(codes: &[u32], log_key_codes: bool) {
+    for (i, slot) in WIN_SCAN_CODES.iter().enumerate() {
+        slot.store(codes.get(i).copied().unwrap_or(0), Ordering::SeqCst);
+    }
+    LOG_KEY_CODES.store(log_key_codes, Ordering::SeqCst);
+}
+
+/// Returns true if the keys in `down` satisfy the configured combination
+fn is_combination_held(down: u32) -> bool {
+    let held_modifiers = held_modifiers(down);
+    let required = SPEC_MODIFIERS.load(Ordering::Relaxed);
+    let key_held = SPEC_KEY.load(Ordering::Relaxed) == 0 || down & TRIGGER_KEY_BIT != 0;
+    held_modifiers & required == required && key_held
+}
+
+/// Reports Ctrl+Esc, which opens the Start menu, like a press of the hotkey
+///
+/// Escape on its own, with other modifiers, or while the hotkey is already held,
+/// is left alone.
+fn handle_ctrl_escape(message: u32) {
+    if SUSPENDED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let msg = match message {
+        WM_KEYDOWN | WM_SYSKEYDOWN => {
+            // Ctrl+Shift+Esc opens Task Manager instead
+            let ctrl_only = held_modifiers(KEYS_DOWN.load(Ordering::Relaxed)) == MOD_CONTROL;
+            if !ctrl_only || HOTKEY_ACTIVE.load(Ordering::Relaxed) {
+                return;
+            }
+            // Ignore auto-repeat while Escape stays down
+            if CTRL_ESCAPE_ACTIVE.swap(true, Ordering::Relaxed) {
+                return;
+            }
+            WM_WINKEY_DOWN
+        }
+        WM_KEYUP | WM_SYSKEYUP => {
+            // The hotkey's own release already hides the bar if it took over
+            if !CTRL_ESCAPE_ACTIVE.swap(false, Ordering::Relaxed)
+                || HOTKEY_ACTIVE.load(Ordering::Relaxed)
+            {
+                return;
+            }
+            WM_WINKEY_UP
+        }
+        _ => return,
+    };
+    unsafe {
+        PostMessageW(NOTIFY_HWND.load(Ordering::Relaxed) as HWND, msg, 0, 0);
+    }
+}
 
 /// Low-level keyboard hook callback
 unsafe extern "system" fn keyboard_hook_proc(
@@ -23,40 +158,269 @@ unsafe extern "system" fn keyboard_hook_proc(
     wparam: WPARAM,
     lparam: LPARAM,
 ) -> LRESULT {
-    if code >= 0 {
-        let kbd = &*(lparam as *const KBDLLHOOKSTRUCT);
-        let vk = kbd.vkCode as u16;
-
-        if vk == VK_LWIN || vk == VK_RWIN {
-            let hwnd = NOTIFY_HWND.load(Ordering::SeqCst) as HWND;
-            let msg = match wparam as u32 {
-                WM_KEYDOWN | WM_SYSKEYDOWN => Some(WM_WINKEY_DOWN),
-                WM_KEYUP | WM_SYSKEYUP => Some(WM_WINKEY_UP),
-                _ => None,
-            };
-
-            if let Some(m) = msg {
-                PostMessageW(hwnd, m, 0, 0);
-            }
+    if code < 0 {
+        return CallNextHookEx(null_mut(), code, wparam, lparam);
+    }
+
+    // Hot path for every keystroke system-wide: bail out before any other work
+    let info = &*(lparam as *const KBDLLHOOKSTRUCT);
+    LAST_KEY_TIME.store(info.time, Ordering::Relaxed);
+    let vk = info.vkCode;
+    let extended = if info.flags & LLKHF_EXTENDED != 0 { EXTENDED_SCAN_PREFIX } else { 0 };
+    let scan = info.scanCode | extended;
+    let key_down = matches!(wparam as u32, WM_KEYDOWN | WM_SYSKEYDOWN);
+    if key_down && LOG_KEY_CODES.load(Ordering::Relaxed) {
+        let hwnd = NOTIFY_HWND.load(Ordering::Relaxed) as HWND;
+        PostMessageW(hwnd, WM_KEY_CODES, vk as WPARAM, scan as LPARAM);
+    }
+    // A configured scan code wins over the vk, which a remapped key reports differently
+    let bit = match MODIFIER_KEYS.iter().position(|&(key, _)| key as u32 == vk) {
+        _ if scan != 0 && is_win_scan_code(scan) => SCAN_WIN_KEY_BIT,
+        Some(i) => 1 << i,
+        None if vk == SPEC_KEY.load(Ordering::Relaxed) => TRIGGER_KEY_BIT,
+        None if vk == VK_ESCAPE as u32 => {
+            handle_ctrl_escape(wparam as u32);
+            return CallNextHookEx(null_mut(), code, wparam, lparam);
+        }
+        None => return CallNextHookEx(null_mut(), code, wparam, lparam),
+    };
+
+    if !SUSPENDED.load(Ordering::Relaxed) {
+        let down = match wparam as u32 {
+            WM_KEYDOWN | WM_SYSKEYDOWN => KEYS_DOWN.fetch_or(bit, Ordering::Relaxed) | bit,
+            WM_KEYUP | WM_SYSKEYUP => KEYS_DOWN.fetch_and(!bit, Ordering::Relaxed) & !bit,
+            _ => KEYS_DOWN.load(Ordering::Relaxed),
+        };
+
+        // Only report transitions of the whole combination
+        let active = is_combination_held(down);
+        if active != HOTKEY_ACTIVE.swap(active, Ordering::Relaxed) {
+            let hwnd = NOTIFY_HWND.load(Ordering::Relaxed) as HWND;
+            let msg = if active { WM_WINKEY_DOWN } else { WM_WINKEY_UP };
+            PostMessageW(hwnd, msg, 0, 0);
         }
     }
 
     CallNextHookEx(null_mut(), code, wparam, lparam)
 }
 
-/// Install the keyboard hook
-pub fn install(notify_hwnd: HWND) -> Result<(), &'static str> {
+/// Desktop switch event callback
+unsafe extern "system" fn desktop_switch_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    _hwnd: HWND,
+    _id_object: i32,
+    _id_child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    let hwnd = NOTIFY_HWND.load(Ordering::SeqCst) as HWND;
+    PostMessageW(hwnd, WM_DESKTOP_SWITCH, 0, 0);
+}
+
+/// Returns true if the input desktop is the one this process runs on
+fn is_input_desktop_ours() -> bool {
+    unsafe {
+        // The secure desktop denies access to ordinary user processes
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+        if desktop.is_null() {
+            return false;
+        }
+        CloseDesktop(desktop);
+        true
+    }
+}
+
+/// Suspends or resumes the hook after a desktop switch.
+///
+/// Returns true when input is back on our desktop and key state should be
+/// resynchronized.
+pub fn handle_desktop_switch() -> bool {
+    let ours = is_input_desktop_ours();
+    SUSPENDED.store(!ours, Ordering::SeqCst);
+    ours
+}
+
+/// Rebuilds the tracked key state from the physical keyboard.
+///
+/// Returns true if the reveal combination is currently held.
+pub fn resync_key_state() -> bool {
+    let is_down = |vk: u32| unsafe { GetAsyncKeyState(vk as i32) < 0 };
+
+    let mut down = 0;
+    for (i, &(vk, _)) in MODIFIER_KEYS.iter().enumerate() {
+        if is_down(vk as u32) {
+            down |= 1 << i;
+        }
+    }
+    let key = SPEC_KEY.load(Ordering::SeqCst);
+    if key != 0 && is_down(key) {
+        down |= TRIGGER_KEY_BIT;
+    }
+    // Keys matched by scan code can't be queried, so they count as released
+
+    KEYS_DOWN.store(down, Ordering::SeqCst);
+    CTRL_ESCAPE_ACTIVE.store(false, Ordering::SeqCst);
+    let active = is_combination_held(down);
+    HOTKEY_ACTIVE.store(active, Ordering::SeqCst);
+    active
+}
+
+/// Install the keyboard hook, reporting presses of the given combination
+///
+/// Returns `Ok(false)` without installing a second hook if one is already in
+/// place; the new window and combination still take effect.
+pub fn install(notify_hwnd: HWND, spec: HotkeySpec) -> Result<bool, Error> {
     unsafe {
         NOTIFY_HWND.store(notify_hwnd as *mut _, Ordering::SeqCst);
+        SPEC_MODIFIERS.store(spec.modifiers, Ordering::SeqCst);
+        SPEC_KEY.store(spec.key.map_or(0, u32::from), Ordering::SeqCst);
+
+        if !HOOK_HANDLE.load(Ordering::SeqCst).is_null() {
+            return Ok(false);
+        }
 
         let hook = SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_hook_proc), null_mut(), 0);
         if hook.is_null() {
-            return Err("Failed to install keyboard hook");
+            return Err(Error::last("Failed to install keyboard hook"));
         }
 
         HOOK_HANDLE.store(hook, Ordering::SeqCst);
-        Ok(())
+
+        // Desktop switch tracking is best-effort; the keyboard hook works without it
+        let desktop_hook = SetWinEventHook(
+            EVENT_SYSTEM_DESKTOPSWITCH,
+            EVENT_SYSTEM_DESKTOPSWITCH,
+            null_mut(),
+            Some(desktop_switch_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT,
+        );
+        DESKTOP_HOOK_HANDLE.store(desktop_hook, Ordering::SeqCst);
+
+        Ok(true)
+    }
+}
+
+/// Checks that the hook still receives input
+///
+/// Windows silently removes a low-level hook whose callback times out. The
+/// hook is taken as lost if input arrived since the last check, after the
+/// last key it saw, and that input wasn't from the mouse. The mouse hook's
+/// timestamp tells when it is installed; otherwise a cursor move or a button
+/// press does, and a wheel turn can still look like a missed key.
+pub fn check_health() -> bool {
+    if HOOK_HANDLE.load(Ordering::SeqCst).is_null() {
+        return true;
+    }
+
+    let mut info = LASTINPUTINFO {
+        cbSize: size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    let mut cursor = POINT { x: 0, y: 0 };
+    if unsafe { GetLastInputInfo(&mut info) == 0 || GetCursorPos(&mut cursor) == 0 } {
+        return true;
+    }
+    // Every probe runs on each check, so the next one only sees newer input
+    let packed_cursor = (cursor.x as u32 as u64) << 32 | cursor.y as u32 as u64;
+    let new_input = LAST_INPUT_TIME.swap(info.dwTime, Ordering::SeqCst) != info.dwTime;
+    let cursor_moved = LAST_CURSOR.swap(packed_cursor, Ordering::SeqCst) != packed_cursor;
+    let clicked = mouse_button_pressed();
+
+    // The hook sees nothing while the secure desktop is up
+    if SUSPENDED.load(Ordering::SeqCst) || !new_input {
+        return true;
+    }
+    // Tick counts wrap after 49 days, so compare the difference
+    let unseen_ms = info.dwTime.wrapping_sub(LAST_KEY_TIME.load(Ordering::SeqCst)) as i32;
+    if unseen_ms <= 0 {
+        return true;
+    }
+    match crate::mouse::last_input_time() {
+        Some(mouse_time) => info.dwTime.wrapping_sub(mouse_time) as i32 <= 0,
+        None => cursor_moved || clicked,
+    }
+}
+
+/// Returns true if a mouse button is down or was pressed since the last call
+fn mouse_button_pressed() -> bool {
+    [VK_LBUTTON, VK_RBUTTON, VK_MBUTTON, VK_XBUTTON1, VK_XBUTTON2]
+        .into_iter()
+        .map(|vk| unsafe { GetAsyncKeyState(vk as i32) } != 0)
+        .fold(false, |pressed, button| pressed | button)
+}
+
+/// Removes and re-installs the keyboard hook with the current window and combination
+pub fn reinstall() -> Result<(), Error> {
+    let notify_hwnd = NOTIFY_HWND.load(Ordering::SeqCst) as HWND;
+    let spec = HotkeySpec {
+        modifiers: SPEC_MODIFIERS.load(Ordering::SeqCst),
+        key: u16::try_from(SPEC_KEY.load(Ordering::SeqCst)).ok().filter(|&key| key != 0),
+    };
+    uninstall();
+    install(notify_hwnd, spec).map(|_| ())
+}
+
+/// Registers the fallback hotkey, reported to `hwnd` as `WM_HOTKEY`
+///
+/// Unlike the hook it survives games that take over keyboard input, but it
+/// needs a non-modifier key and only reports presses, not releases.
+pub fn register_fallback(hwnd: HWND, spec: HotkeySpec) -> Result<(), Error> {
+    let key = spec.key.ok_or(Error::Failed("The fallback hotkey needs a non-modifier key"))?;
+    // Without MOD_NOREPEAT, auto-repeat keeps reporting the hotkey while it is held
+    if unsafe { RegisterHotKey(hwnd, FALLBACK_HOTKEY_ID, spec.modifiers, key as u32) } == 0 {
+        return Err(Error::last("Failed to register the fallback hotkey"));
     }
+    Ok(())
+}
+
+/// Unregisters the fallback hotkey
+pub fn unregister_fallback(hwnd: HWND) {
+    unsafe {
+        UnregisterHotKey(hwnd, FALLBACK_HOTKEY_ID);
+    }
+}
+
+/// Registers the hotkey that quits the hider
+pub fn register_quit_hotkey(hwnd: HWND, spec: HotkeySpec) -> Result<(), Error> {
+    let key = spec.key.ok_or(Error::Failed("The quit hotkey needs a non-modifier key"))?;
+    let modifiers = spec.modifiers | MOD_NOREPEAT;
+    if unsafe { RegisterHotKey(hwnd, QUIT_HOTKEY_ID, modifiers, key as u32) } == 0 {
+        return Err(Error::last("Failed to register the quit hotkey"));
+    }
+    Ok(())
+}
+
+/// Unregisters the quit hotkey
+pub fn unregister_quit_hotkey(hwnd: HWND) {
+    unsafe {
+        UnregisterHotKey(hwnd, QUIT_HOTKEY_ID);
+    }
+}
+
+/// Registers the hotkey that switches to the next mode of the mode cycle
+pub fn register_mode_cycle_hotkey(hwnd: HWND, spec: HotkeySpec) -> Result<(), Error> {
+    let key = spec.key.ok_or(Error::Failed("The mode cycle hotkey needs a non-modifier key"))?;
+    // Holding the combination shouldn't race through the modes
+    let modifiers = spec.modifiers | MOD_NOREPEAT;
+    if unsafe { RegisterHotKey(hwnd, MODE_CYCLE_HOTKEY_ID, modifiers, key as u32) } == 0 {
+        return Err(Error::last("Failed to register the mode cycle hotkey"));
+    }
+    Ok(())
+}
+
+/// Unregisters the mode cycle hotkey
+pub fn unregister_mode_cycle_hotkey(hwnd: HWND) {
+    unsafe {
+        UnregisterHotKey(hwnd, MODE_CYCLE_HOTKEY_ID);
+    }
+}
+
+/// Returns true while the hook sees the reveal hotkey held
+pub fn is_hotkey_active() -> bool {
+    HOTKEY_ACTIVE.load(Ordering::SeqCst)
 }
 
 /// Uninstall the keyboard hook
@@ -66,5 +430,10 @@ pub fn uninstall() {
         if !hook.is_null() {
             UnhookWindowsHookEx(hook);
         }
+
+        let desktop_hook = DESKTOP_HOOK_HANDLE.swap(null_mut(), Ordering::SeqCst);
+        if !desktop_hook.is_null() {
+            UnhookWinEvent(desktop_hook);
+        }
     }
 }
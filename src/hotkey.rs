@@ -0,0 +1,167 @@
+//! Hotkey module
+//!
+//! Parses key combinations such as `Win`, `Ctrl+Alt` or `Ctrl+F9` as written
+//! in the config file.
+
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    MOD_ALT, MOD_CONTROL, MOD_SHIFT, MOD_WIN, VK_APPS, VK_CAPITAL, VK_ESCAPE, VK_F1, VK_INSERT,
+    VK_OEM_3, VK_PAUSE, VK_SCROLL, VK_SPACE, VK_TAB,
+};
+
+/// Key combination that reveals the taskbar
+///
+/// Every `MOD_*` flag in `modifiers` must be held (either side), plus `key`
+/// when one is set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HotkeySpec {
+    pub modifiers: u32,
+    pub key: Option<u16>,
+}
+
+impl Default for HotkeySpec {
+    /// The Windows key on its own
+    fn default() -> Self {
+        HotkeySpec {
+            modifiers: MOD_WIN,
+            key: None,
+        }
+    }
+}
+
+impl HotkeySpec {
+    /// Parses a combination such as `Win`, `Ctrl+Alt` or `Ctrl+F9`
+    pub fn parse(text: &str) -> Option<HotkeySpec> {
+        let mut spec = HotkeySpec {
+            modifiers: 0,
+            key: None,
+        };
+
+        for part in text.split('+') {
+            match part.trim().to_ascii_lowercase().as_str() {
+                "win" | "windows" => spec.modifiers |= MOD_WIN,
+                "ctrl" | "control" => spec.modifiers |= MOD_CONTROL,
+                "alt" => spec.modifiers |= MOD_ALT,
+                "shift" => spec.modifiers |= MOD_SHIFT,
+                name => {
+                    // Only one non-modifier key is supported
+                    if spec.key.is_some() {
+                        return None;
+                    }
+                    spec.key = Some(key_from_name(name)?);
+                }
+            }
+        }
+
+        if spec.modifiers == 0 && spec.key.is_none() {
+            None
+        } else {
+            Some(spec)
+        }
+    }
+
+    /// Formats the combination the way `parse` reads it, e.g. `Ctrl+Shift+F9`
+    pub fn to_text(&self) -> String {
+        let mut parts: Vec<String> = [
+            (MOD_WIN, "Win"),
+            (MOD_CONTROL, "Ctrl"),
+            (MOD_ALT, "Alt"),
+            (MOD_SHIFT, "Shift"),
+        ]
+        .iter()
+        .filter(|(flag, _)| self.modifiers & flag != 0)
+        .map(|(_, name)| name.to_string())
+        .collect();
+        parts.extend(self.key.and_then(key_name));
+        parts.join("+")
+    }
+}
+
+/// Maps a lowercase key name to its virtual key code
+fn key_from_name(name: &str) -> Option<u16> {
+    match name {
+        "space" => Some(VK_SPACE),
+        "tab" => Some(VK_TAB),
+        "esc" | "escape" => Some(VK_ESCAPE),
+        "`" | "grave" => Some(VK_OEM_3),
+        "capslock" => Some(VK_CAPITAL),
+        "scrolllock" => Some(VK_SCROLL),
+        "pause" => Some(VK_PAUSE),
+        "insert" => Some(VK_INSERT),
+        "apps" | "menu" => Some(VK_APPS),
+        _ => {
+            if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+                return (1..=24).contains(&n).then(|| VK_F1 + n - 1);
+            }
+            // Letters and digits map directly to their uppercase ASCII code
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphanumeric() => Some(c.to_ascii_uppercase() as u16),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Returns the name `key_from_name` maps to a virtual key code
+fn key_name(key: u16) -> Option<String> {
+    let name = match key {
+        VK_SPACE => "Space",
+        VK_TAB => "Tab",
+        VK_ESCAPE => "Esc",
+        VK_OEM_3 => "`",
+        VK_CAPITAL => "CapsLock",
+        VK_SCROLL => "ScrollLock",
+        VK_PAUSE => "Pause",
+        VK_INSERT => "Insert",
+        VK_APPS => "Apps",
+        k if (VK_F1..VK_F1 + 24).contains(&k) => return Some(format!("F{}", k - VK_F1 + 1)),
+        // Letters and digits are their uppercase ASCII code
+        k if matches!(k, 0x30..=0x39 | 0x41..=0x5A) => return Some((k as u8 as char).to_string()),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{VK_F9, VK_Q};
+
+    #[test]
+    fn parse_reads_modifiers_and_a_key() {
+        let spec = HotkeySpec::parse("Ctrl + Shift+F9").unwrap();
+        assert_eq!(spec.modifiers, MOD_CONTROL | MOD_SHIFT);
+        assert_eq!(spec.key, Some(VK_F9));
+        assert_eq!(HotkeySpec::parse("win").unwrap(), HotkeySpec::default());
+        assert_eq!(HotkeySpec::parse("Windows+Alt").unwrap().modifiers, MOD_WIN | MOD_ALT);
+        assert_eq!(HotkeySpec::parse("Win+Shift+Q").unwrap().key, Some(VK_Q));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_or_extra_keys() {
+        assert!(HotkeySpec::parse("").is_none());
+        assert!(HotkeySpec::parse("Ctrl+Nope").is_none());
+        assert!(HotkeySpec::parse("Ctrl+A+B").is_none());
+    }
+
+    #[test]
+    fn to_text_round_trips_through_parse() {
+        for text in ["Win", "Ctrl+Alt", "Ctrl+Shift+F9", "Win+`", "Win+Shift+Q", "Alt+Space"] {
+            let spec = HotkeySpec::parse(text).unwrap();
+            assert_eq!(spec.to_text(), text);
+            assert_eq!(HotkeySpec::parse(&spec.to_text()), Some(spec));
+        }
+    }
+
+    #[test]
+    fn key_from_name_maps_named_keys() {
+        assert_eq!(key_from_name("`"), Some(VK_OEM_3));
+        assert_eq!(key_from_name("esc"), Some(VK_ESCAPE));
+        assert_eq!(key_from_name("f1"), Some(VK_F1));
+        assert_eq!(key_from_name("f24"), Some(VK_F1 + 23));
+        assert_eq!(key_from_name("f25"), None);
+        assert_eq!(key_from_name("a"), Some(b'A' as u16));
+        assert_eq!(key_from_name("7"), Some(b'7' as u16));
+        assert_eq!(key_from_name("ab"), None);
+    }
+}
@@ -0,0 +1,220 @@
+//! IPC module
+//!
+//! Serves the `\\.\pipe\clean-taskbar-<session>` named pipe so scripts and
+//! hotkey managers can control the hider. The session id keeps apart the
+//! instances of users signed in at the same time. Each connection writes one
+//! text command:
+//!
+//! - `pause` - keep the taskbar visible
+//! - `resume` - go back to hiding it
+//! - `toggle` - switch between paused and hiding
+//! - `quit` - exit the hider
+//!
+//! Commands are case-insensitive and surrounding whitespace is ignored, so
+//! `echo toggle > \\.\pipe\clean-taskbar-1` works from a command prompt.
+//! Unknown commands are dropped.
+
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_PIPE_CONNECTED, GENERIC_WRITE, HANDLE, HWND,
+    INVALID_HANDLE_VALUE, WPARAM,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, ReadFile, FILE_FLAG_FIRST_PIPE_INSTANCE, OPEN_EXISTING, PIPE_ACCESS_INBOUND,
+};
+use windows_sys::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT,
+};
+use windows_sys::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+use windows_sys::Win32::System::Threading::GetCurrentProcessId;
+use windows_sys::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_USER};
+use taskbar_hider::winapi::Error;
+
+/// Posted to the main window with a `Command` in `wparam`
+pub const WM_IPC_COMMAND: u32 = WM_USER + 130;
+
+const PIPE_PREFIX: &str = r"\\.\pipe\clean-taskbar";
+const MAX_COMMAND_LEN: usize = 64;
+
+static STOPPING: AtomicBool = AtomicBool::new(false);
+static SERVER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// A command received over the pipe
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Pause,
+    Resume,
+    Toggle,
+    Quit,
+}
+
+impl Command {
+    const ALL: [Command; 4] = [Command::Pause, Command::Resume, Command::Toggle, Command::Quit];
+
+    /// Parses a command name
+    fn parse(text: &str) -> Option<Command> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "pause" => Some(Command::Pause),
+            "resume" => Some(Command::Resume),
+            "toggle" => Some(Command::Toggle),
+            "quit" => Some(Command::Quit),
+            _ => None,
+        }
+    }
+
+    /// Recovers the command posted with `WM_IPC_COMMAND`
+    pub fn from_wparam(wparam: WPARAM) -> Option<Command> {
+        Command::ALL.get(wparam).copied()
+    }
+}
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Returns the pipe name for the current session, shared by the server and its clients
+pub fn pipe_name() -> String {
+    let mut session = 0;
+    // Only fails for a process that has already exited
+    unsafe {
+        ProcessIdToSessionId(GetCurrentProcessId(), &mut session);
+    }
+    format!("{}-{}", PIPE_PREFIX, session)
+}
+
+/// Creates one instance of the pipe
+fn create_pipe(first: bool) -> HANDLE {
+    let name = wide_string(&pipe_name());
+    let open_mode = if first {
+        PIPE_ACCESS_INBOUND | FILE_FLAG_FIRST_PIPE_INSTANCE
+    } else {
+        PIPE_ACCESS_INBOUND
+    };
+    unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            open_mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            0,
+            MAX_COMMAND_LEN as u32,
+            0,
+            null(),
+        )
+    }
+}
+
+/// Reads a single command from a connected client
+fn read_command(pipe: HANDLE) -> Option<Command> {
+    let mut buf = [0u8; MAX_COMMAND_LEN];
+    let mut read = 0;
+    let ok =
+        unsafe { ReadFile(pipe, buf.as_mut_ptr(), buf.len() as u32, &mut read, null_mut()) };
+    if ok == 0 {
+        return None;
+    }
+    Command::parse(&String::from_utf8_lossy(&buf[..read as usize]))
+}
+
+/// Accepts clients one at a time until `stop` is called
+fn serve(mut pipe: HANDLE, notify_hwnd: HWND) {
+    loop {
+        let connected = unsafe {
+            ConnectNamedPipe(pipe, null_mut()) != 0 || GetLastError() == ERROR_PIPE_CONNECTED
+        };
+        if STOPPING.load(Ordering::SeqCst) {
+            unsafe {
+                CloseHandle(pipe);
+            }
+            return;
+        }
+
+        if connected {
+            if let Some(command) = read_command(pipe) {
+                unsafe {
+                    PostMessageW(notify_hwnd, WM_IPC_COMMAND, command as WPARAM, 0);
+                }
+            }
+        }
+
+        unsafe {
+            DisconnectNamedPipe(pipe);
+            CloseHandle(pipe);
+        }
+        pipe = create_pipe(false);
+        if pipe == INVALID_HANDLE_VALUE {
+            return;
+        }
+    }
+}
+
+/// Starts the pipe server on a background thread
+pub fn start(notify_hwnd: HWND) -> Result<(), Error> {
+    // Creating the first instance here surfaces a name clash to the caller
+    let pipe = create_pipe(true);
+    if pipe == INVALID_HANDLE_VALUE {
+        return Err(Error::last("Failed to create IPC pipe"));
+    }
+
+    // Raw handles aren't Send; pass them across as integers
+    let (pipe, hwnd) = (pipe as usize, notify_hwnd as usize);
+    let server = std::thread::Builder::new()
+        .name("ipc".to_string())
+        .spawn(move || serve(pipe as HANDLE, hwnd as HWND))
+        .map_err(|_| Error::Failed("Failed to start IPC thread"))?;
+
+    if let Ok(mut guard) = SERVER.lock() {
+        *guard = Some(server);
+    }
+    Ok(())
+}
+
+/// Stops the pipe server and waits for its thread to exit
+pub fn stop() {
+    let Some(server) = SERVER.lock().ok().and_then(|mut guard| guard.take()) else {
+        return;
+    };
+    STOPPING.store(true, Ordering::SeqCst);
+
+    // Connect once ourselves to wake the thread blocked in ConnectNamedPipe
+    let name = wide_string(&pipe_name());
+    let client = unsafe {
+        CreateFileW(name.as_ptr(), GENERIC_WRITE, 0, null(), OPEN_EXISTING, 0, null_mut())
+    };
+    if client == INVALID_HANDLE_VALUE {
+        // Joining could block forever; the thread ends with the process instead
+        return;
+    }
+    unsafe {
+        CloseHandle(client);
+    }
+    let _ = server.join();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ignores_case_and_whitespace() {
+        assert_eq!(Command::parse("pause"), Some(Command::Pause));
+        assert_eq!(Command::parse("  Resume\r\n"), Some(Command::Resume));
+        assert_eq!(Command::parse("TOGGLE"), Some(Command::Toggle));
+        assert_eq!(Command::parse("quit\n"), Some(Command::Quit));
+        assert_eq!(Command::parse("restart"), None);
+        assert_eq!(Command::parse(""), None);
+    }
+
+    #[test]
+    fn from_wparam_round_trips() {
+        for command in Command::ALL {
+            assert_eq!(Command::from_wparam(command as WPARAM), Some(command));
+        }
+        assert_eq!(Command::from_wparam(Command::ALL.len()), None);
+    }
+}
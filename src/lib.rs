@@ -0,0 +1,12 @@
+//! Taskbar Hider library
+//!
+//! The taskbar control and configuration behind the `taskbar-hider` binary,
+//! kept apart from its tray icon, hooks and message loop so they can be
+//! reused and tested on their own.
+
+pub mod compat;
+pub mod config;
+pub mod hotkey;
+pub mod reveal;
+pub mod taskbar;
+pub mod winapi;
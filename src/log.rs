@@ -0,0 +1,143 @@
+//! Logging module
+//!
+//! Appends timestamped lines to `%LOCALAPPDATA%\clean-taskbar\log.txt` when
+//! logging is enabled in the config. Disabled by default. A full log is rotated
+//! to `log.1.txt`, `log.2.txt` and so on.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::SYSTEMTIME;
+use windows_sys::Win32::System::SystemInformation::GetLocalTime;
+
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+/// Size at which the log is rotated; zero lets it grow unbounded
+static MAX_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Number of rotated logs kept next to the current one
+static BACKUPS: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the path of the log file
+pub fn log_path() -> Option<PathBuf> {
+    let local_appdata = std::env::var_os("LOCALAPPDATA")?;
+    Some(PathBuf::from(local_appdata).join("clean-taskbar").join("log.txt"))
+}
+
+/// Opens the log file if logging is enabled, or closes it if it was turned off
+///
+/// Once the file reaches `max_kb` it is rotated, keeping `backups` older logs.
+pub fn init(enabled: bool, max_kb: u64, backups: u32) {
+    if !enabled {
+        if let Ok(mut guard) = LOG_FILE.lock() {
+            *guard = None;
+        }
+        return;
+    }
+    let Some(path) = log_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    MAX_BYTES.store(max_kb.saturating_mul(1024), Ordering::SeqCst);
+    BACKUPS.store(backups, Ordering::SeqCst);
+    if let Ok(mut guard) = LOG_FILE.lock() {
+        *guard = open(&path);
+    }
+}
+
+fn open(path: &Path) -> Option<File> {
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+/// Returns the path of the `n`th rotated log, e.g. `log.1.txt`
+fn backup_path(path: &Path, n: u32) -> PathBuf {
+    path.with_file_name(format!("log.{}.txt", n))
+}
+
+/// Shifts the rotated logs up by one and moves the current log to `log.1.txt`
+///
+/// The oldest one falls off the end; with no backups the log simply starts over.
+fn rotate(path: &Path, backups: u32) {
+    if backups == 0 {
+        let _ = std::fs::remove_file(path);
+        return;
+    }
+    let _ = std::fs::remove_file(backup_path(path, backups));
+    for n in (1..backups).rev() {
+        let _ = std::fs::rename(backup_path(path, n), backup_path(path, n + 1));
+    }
+    let _ = std::fs::rename(path, backup_path(path, 1));
+}
+
+/// Writes an informational line
+pub fn log_info(message: &str) {
+    write_line("INFO", message);
+}
+
+/// Writes an error line
+pub fn log_error(message: &str) {
+    write_line("ERROR", message);
+}
+
+fn write_line(level: &str, message: &str) {
+    let Ok(mut guard) = LOG_FILE.lock() else {
+        return;
+    };
+    let Some(file) = guard.as_mut() else {
+        return;
+    };
+
+    let t = unsafe {
+        let mut t: SYSTEMTIME = std::mem::zeroed();
+        GetLocalTime(&mut t);
+        t
+    };
+    let _ = writeln!(
+        file,
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03} [{}] {}",
+        t.wYear, t.wMonth, t.wDay, t.wHour, t.wMinute, t.wSecond, t.wMilliseconds, level, message
+    );
+
+    let max_bytes = MAX_BYTES.load(Ordering::Relaxed);
+    if max_bytes == 0 || file.metadata().map_or(true, |m| m.len() < max_bytes) {
+        return;
+    }
+    let Some(path) = log_path() else {
+        return;
+    };
+    // Windows can't rename a file that is still open
+    *guard = None;
+    rotate(&path, BACKUPS.load(Ordering::Relaxed));
+    *guard = open(&path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_keeps_the_newest_backups() {
+        let dir = std::env::temp_dir().join(format!("clean-taskbar-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.txt");
+        let read = |n| std::fs::read_to_string(backup_path(&path, n)).ok();
+
+        for text in ["first", "second", "third"] {
+            std::fs::write(&path, text).unwrap();
+            rotate(&path, 2);
+        }
+        assert!(!path.exists());
+        assert_eq!(read(1).as_deref(), Some("third"));
+        assert_eq!(read(2).as_deref(), Some("second"));
+        assert_eq!(read(3), None);
+
+        std::fs::write(&path, "fourth").unwrap();
+        rotate(&path, 0);
+        assert!(!path.exists());
+        assert_eq!(read(1).as_deref(), Some("third"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
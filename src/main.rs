@@ -5,57 +5,282 @@
 
 #![windows_subsystem = "windows"]
 
+mod autostart;
+mod commands;
+mod etw;
+mod eventlog;
+mod flyout;
+mod foreground;
 mod hooks;
-mod taskbar;
+mod ipc;
+mod log;
+mod mouse;
+mod overlay;
+mod power;
+mod recovery;
+mod settings;
+mod shell;
+mod toast;
 mod tray;
+mod vdesktop;
+mod watchdog;
 
 use std::mem::size_of;
+use std::path::Path;
 use std::ptr::{null, null_mut};
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
-use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, LocalFree, ERROR_ALREADY_EXISTS, HANDLE, HWND, LPARAM, LRESULT,
+    WPARAM,
+};
+use windows_sys::Win32::Graphics::Gdi::HMONITOR;
+use windows_sys::Win32::System::Environment::GetCommandLineW;
 use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::System::SystemInformation::GetTickCount;
+use windows_sys::Win32::System::Threading::CreateMutexW;
+use windows_sys::Win32::UI::HiDpi::{
+    SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+};
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+use windows_sys::Win32::UI::Shell::CommandLineToArgvW;
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, KillTimer,
-    PostQuitMessage, RegisterClassExW, RegisterWindowMessageW, SetTimer, TranslateMessage,
-    HWND_MESSAGE, MSG, WNDCLASSEXW, WM_COMMAND, WM_DESTROY, WM_TIMER, WS_OVERLAPPED,
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetForegroundWindow,
+    GetMessageW, GetWindowLongPtrW, KillTimer, PostMessageW, PostQuitMessage, RegisterClassExW,
+    RegisterWindowMessageW, SetTimer, SetWindowLongPtrW, SystemParametersInfoW, TranslateMessage,
+    CREATESTRUCTW, GWLP_USERDATA, MSG, SPI_GETKEYBOARDDELAY, SPI_SETWORKAREA, WNDCLASSEXW,
+    WM_COMMAND, WM_DESTROY, WM_DISPLAYCHANGE, WM_DPICHANGED, WM_ENDSESSION, WM_HOTKEY,
+    WM_NCCREATE, WM_NCDESTROY, WM_POWERBROADCAST, WM_QUERYENDSESSION, WM_SETTINGCHANGE, WM_TIMER,
+    WM_USER, PBT_POWERSETTINGCHANGE, WS_OVERLAPPED,
 };
+use power::PowerChange;
+use taskbar_hider::reveal::{Decision, IdleWatch, LowBatteryWatch, RevealState};
+use taskbar_hider::winapi::{Error, SystemApi};
+use taskbar_hider::{compat, config, taskbar};
+
+/// Posted to re-run the visibility evaluation once a burst of events settles
+const WM_REEVALUATE: u32 = WM_USER + 2;
 
 // Timing constants
-const WIN_KEY_DELAY_MS: u64 = 400;
 const TIMER_ID_HIDE_TASKBAR: usize = 1;
+const TIMER_ID_TOOLTIP: usize = 2;
+const TIMER_ID_HIDE_RETRY: usize = 3;
+const HIDE_RETRY_INTERVAL_MS: u32 = 50;
+const TIMER_ID_ANIMATION: usize = 4;
+const ANIMATION_TICK_MS: u32 = 10;
+const TOOLTIP_THROTTLE_MS: u32 = 250;
+const TIMER_ID_MIN_VISIBLE: usize = 5;
+const TIMER_ID_FALLBACK_RELEASE: usize = 6;
+const TIMER_ID_PEEK: usize = 7;
+const TIMER_ID_EXPLORER_RESTART: usize = 8;
+const TIMER_ID_HOLD: usize = 9;
+const TIMER_ID_HOOK_HEALTH: usize = 10;
+const HOOK_HEALTH_INTERVAL_MS: u32 = 60_000;
+const TIMER_ID_DISPLAY_CHANGE: usize = 11;
+const TIMER_ID_OVERLAY: usize = 12;
+const OVERLAY_REFRESH_MS: u32 = 250;
+const TIMER_ID_IDLE: usize = 13;
+const TIMER_ID_WATCHDOG: usize = 14;
+const TIMER_ID_HEARTBEAT: usize = 15;
+/// Re-evaluates the independent monitors once a release delay or minimum visible time ends
+const TIMER_ID_MONITORS: usize = 16;
+/// Explorer rebuilds its taskbars a while after a monitor or scaling change
+const DISPLAY_CHANGE_SETTLE_MS: u32 = 500;
+/// Explorer can broadcast TaskbarCreated several times while it starts up
+const EXPLORER_RESTART_SETTLE_MS: u32 = 500;
+/// Added to the keyboard's auto-repeat delay, so a held fallback hotkey re-arms the timer
+const FALLBACK_RELEASE_MARGIN_MS: u32 = 150;
 
-// Global state
-static TASKBAR_SHOULD_BE_VISIBLE: AtomicBool = AtomicBool::new(false);
-static WIN_KEY_HELD: AtomicBool = AtomicBool::new(false);
+/// State of the running hider, owned by the main window
+///
+/// `run` hands it to `CreateWindowExW`; the window keeps it in `GWLP_USERDATA`
+/// and frees it in `WM_NCDESTROY`.
+struct AppState {
+    hwnd: HWND,
+    taskbar: taskbar::TaskbarController,
+    /// Taskbars currently revealed; a subset of the managed ones after a per-monitor reveal
+    revealed_hwnds: Vec<HWND>,
+    /// Slide animation in progress, driven by TIMER_ID_ANIMATION
+    slide: Option<taskbar::Slide>,
+    reveal: RevealState,
+    /// Screen edge that triggers a hover reveal; `None` follows the taskbar's dock edge
+    hover_edge: Option<taskbar::Edge>,
+    /// Monitor under the cursor when the edge hover last started
+    hover_monitor: HMONITOR,
+    taskbar_created_msg: u32,
+    /// Registered message other tools broadcast to make the hider reload its config
+    reload_msg: u32,
+    reevaluate_pending: bool,
+    hide_retries_left: u32,
+    hide_retry_count: u32,
+    animation_ms: u64,
+    animation_style: config::AnimationStyle,
+    peek_ms: u64,
+    show_on_desktop: bool,
+    smart_hide: bool,
+    /// Lowercase executables the taskbar is only hidden in; empty hides it everywhere
+    hide_only_in: Vec<String>,
+    /// Modes the mode cycle hotkey steps through
+    mode_cycle: Vec<config::CycleMode>,
+    idle: IdleWatch,
+    /// How long the taskbar stays up when input resumes after being idle
+    idle_reveal_ms: u64,
+    /// Time without input after which a revealed taskbar hides; zero turns this off
+    inactive_hide_ms: u64,
+    /// Nothing but pausing reveals the taskbar over an exclusive fullscreen app
+    block_exclusive_fullscreen: bool,
+    /// Peek length for an edge hover over a borderless fullscreen app; zero ignores it
+    borderless_peek_ms: u64,
+    battery: LowBatteryWatch,
+    /// The running peek was started by a low battery, so plugging in ends it
+    battery_peek: bool,
+    /// Settings last applied, kept for "Export settings"
+    config: config::Config,
+    /// Monitors with a `[monitor.*]` section, each revealed by its own state
+    monitors: Vec<MonitorReveal>,
+}
+
+/// A monitor whose taskbar is revealed independently of the others
+///
+/// The shared triggers are copied from the main state on each evaluation; the
+/// edge hover is routed to the hovered monitor's state only.
+struct MonitorReveal {
+    /// Device name, e.g. `DISPLAY2`
+    device: String,
+    reveal: RevealState,
+}
 
-static mut TASKBAR_HWND: HWND = null_mut();
-static mut MAIN_HWND: HWND = null_mut();
-static mut TASKBAR_CREATED_MSG: u32 = 0;
-static mut WIN_KEY_RELEASE_TIME: u64 = 0;
+/// Single-instance mutex, released when dropped
+struct InstanceMutex(HANDLE);
+
+impl InstanceMutex {
+    /// Takes the mutex, or returns `None` if another instance already holds it
+    fn acquire() -> Result<Option<InstanceMutex>, Error> {
+        let mutex_name = wide_string("Local\\CleanTaskbarInstance");
+        let handle = unsafe { CreateMutexW(null(), 0, mutex_name.as_ptr()) };
+        if handle.is_null() {
+            return Err(Error::last("Failed to create instance mutex"));
+        }
+        let mutex = InstanceMutex(handle);
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            return Ok(None);
+        }
+        Ok(Some(mutex))
+    }
+}
+
+impl Drop for InstanceMutex {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
 
 /// Encodes a string as a null-terminated wide string
 fn wide_string(s: &str) -> Vec<u16> {
     s.encode_utf16().chain(std::iter::once(0)).collect()
 }
 
+/// Returns the command-line arguments, excluding the program name
+///
+/// Read through `GetCommandLineW` since the windows-subsystem binary has no console.
+fn command_line_args() -> Vec<String> {
+    unsafe {
+        let mut count = 0;
+        let argv = CommandLineToArgvW(GetCommandLineW(), &mut count);
+        if argv.is_null() {
+            return Vec::new();
+        }
+        let args = (1..count.max(0) as usize)
+            .map(|i| {
+                let arg = *argv.add(i);
+                let len = (0..).take_while(|&j| *arg.add(j) != 0).count();
+                String::from_utf16_lossy(std::slice::from_raw_parts(arg, len))
+            })
+            .collect();
+        LocalFree(argv as _);
+        args
+    }
+}
+
+/// Returns the flags this instance was started with, for the autostart entry
+fn launch_flags() -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(path) = config::path_override() {
+        flags.push("--config".to_string());
+        flags.push(path.display().to_string());
+    }
+    if command_line_args().iter().any(|arg| arg == "--keep-primary") {
+        flags.push("--keep-primary".to_string());
+    }
+    flags
+}
+
+/// Returns the monitors the config reveals on their own
+fn independent_monitors(config: &config::Config) -> Vec<String> {
+    config.monitor_sections.iter().map(|section| section.device.clone()).collect()
+}
+
+/// Returns true if the primary taskbar stays shown, from `--keep-primary` or the config
+fn keeps_primary(config: &config::Config) -> bool {
+    config.keep_primary_visible || command_line_args().iter().any(|arg| arg == "--keep-primary")
+}
+
 fn main() {
+    etw::register();
     if let Err(e) = run() {
-        eprintln!("Error: {}", e);
+        etw::error(&e);
+        log::log_error(&e);
+        eventlog::report_error(&e);
     }
+    etw::unregister();
 }
 
-fn run() -> Result<(), &'static str> {
+fn run() -> Result<(), String> {
+    // The watchdog is a second copy of the hider and must not take the instance mutex
+    if watchdog::run_if_requested(&command_line_args()) {
+        return Ok(());
+    }
+
+    // Single-instance guard: a second copy must not touch hooks or the tray,
+    // and simply exits since the running one already does the job
+    let Some(_instance_mutex) = InstanceMutex::acquire()? else {
+        return Ok(());
+    };
+
     unsafe {
+        // Physical pixels everywhere, so edge checks match each monitor's real bounds.
+        // Fails harmlessly on Windows versions without per-monitor v2 awareness.
+        SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+
         let instance = GetModuleHandleW(null());
         if instance.is_null() {
-            return Err("Failed to get module handle");
+            return Err(Error::last("Failed to get module handle").into());
         }
 
-        // Initialize taskbar control
-        TASKBAR_HWND = taskbar::init()?;
+        // An unusable --config path leaves the default location in use
+        let args = command_line_args();
+        let path_error = args.iter().position(|arg| arg == "--config").and_then(|i| {
+            let path = args.get(i + 1).map_or("", String::as_str);
+            let error = config::set_path(Path::new(path)).err()?;
+            Some(format!("{}; using the default config file", error))
+        });
+
+        // A malformed file is reported like a failed reload, then the defaults are used
+        let (config, config_error) = match config::try_load() {
+            Ok(config) => (config, None),
+            Err(e) => (config::Config::default(), Some(e)),
+        };
+        log::init(config.logging, config.log_max_kb, config.log_backups);
+        log::log_info("Starting");
+        for e in [path_error, config_error].into_iter().flatten() {
+            etw::error(&e);
+            log::log_error(&e);
+            eventlog::report_error(&e);
+        }
 
-        // Create main message window
+        // Create main window. It is a hidden top-level window rather than a
+        // message-only one, which would miss broadcasts like WM_DISPLAYCHANGE.
         let class_name = wide_string("TaskbarHiderMain");
         let wc = WNDCLASSEXW {
             cbSize: size_of::<WNDCLASSEXW>() as u32,
@@ -72,10 +297,14 @@ fn run() -> Result<(), &'static str> {
             hIconSm: null_mut(),
         };
 
-        RegisterClassExW(&wc);
+        if RegisterClassExW(&wc) == 0 {
+            return Err(Error::last("Failed to register window class").into());
+        }
 
+        // Owned by the window from WM_NCCREATE on, which frees it in WM_NCDESTROY
+        let state = Box::into_raw(Box::new(AppState::new()));
         let window_name = wide_string("TaskbarHider");
-        MAIN_HWND = CreateWindowExW(
+        let hwnd = CreateWindowExW(
             0,
             class_name.as_ptr(),
             window_name.as_ptr(),
@@ -84,74 +313,274 @@ fn run() -> Result<(), &'static str> {
             0,
             0,
             0,
-            HWND_MESSAGE,
+            null_mut(),
             null_mut(),
             instance,
-            null(),
+            state as *const _,
         );
 
-        if MAIN_HWND.is_null() {
-            return Err("Failed to create main window");
+        // Left alone on failure, since WM_NCDESTROY may already have freed it
+        if hwnd.is_null() {
+            return Err(Error::last("Failed to create main window").into());
         }
 
-        // Register for TaskbarCreated message (Explorer restart detection)
-        let taskbar_created = wide_string("TaskbarCreated");
-        TASKBAR_CREATED_MSG = RegisterWindowMessageW(taskbar_created.as_ptr());
+        let result = start(state, &config).map(|tray_icon| {
+            // Message loop
+            let mut msg: MSG = std::mem::zeroed();
+            while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+            drop(tray_icon);
+        });
+
+        // WM_DESTROY restores the taskbar, also after a failed start
+        DestroyWindow(hwnd);
+        result
+    }
+}
+
+/// Takes over the taskbars and installs the hooks and tray icon
+///
+/// `state` is only borrowed per step, since the window it belongs to may
+/// receive messages in between.
+unsafe fn start(
+    state: *mut AppState,
+    config: &config::Config,
+) -> Result<Option<tray::TrayIcon>, String> {
+    let hwnd = (*state).hwnd;
+    (*state).apply_config(config);
 
-        // Install keyboard hook
-        hooks::install(MAIN_HWND)?;
+    (*state).taskbar.set_keep_primary(keeps_primary(config));
+    (*state).taskbar.set_hide_style(config.hide_style, config.hide_alpha);
+    (*state).taskbar.set_always_visible(config.always_visible_monitors.clone());
+    (*state).taskbar.set_independent(independent_monitors(config));
+    (*state).taskbar.set_secondary_hidden(config.hide_secondary_bars);
+    // SHAppBarMessage is unreliable under Wine; hiding the window alone is enough there
+    (*state).taskbar.set_autohide_allowed(!compat::is_wine());
 
-        // Add tray icon
-        if !tray::add_tray_icon(MAIN_HWND) {
-            return Err("Failed to add tray icon");
+    // A previous run that was killed left the taskbar hidden
+    if recovery::restore_if_stale() {
+        log::log_info("Restored the taskbar after an unclean exit");
+    }
+
+    // Initialize taskbar control
+    if let Err(e) = (*state).taskbar.init() {
+        if !taskbar::is_standard_shell() {
+            let message = "A replacement shell is running; there is no Explorer taskbar to hide";
+            return Err(message.into());
         }
+        return Err(e.into());
+    }
+    recovery::write_marker((*state).taskbar.autohide_set_by_us());
+    log::log_info(&format!("Found {} taskbar(s)", (*state).taskbar.hwnds().len()));
+
+    // Paused at last exit: leave the taskbar up until the user resumes
+    if config.paused {
+        let state = &mut *state;
+        state.reveal.paused = true;
+        state.reveal.shown(get_current_time_ms());
+        state.revealed_hwnds = state.taskbar.shared_hwnds();
+        state.taskbar.show_bars(&state.revealed_hwnds.clone());
+    }
+
+    // TaskbarController::init hid the bars once; keep at it in case Explorer re-shows them
+    (*state).start_hide_retries();
 
-        // Message loop
-        let mut msg: MSG = std::mem::zeroed();
-        while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
-            TranslateMessage(&msg);
-            DispatchMessageW(&msg);
+    // Register for TaskbarCreated message (Explorer restart detection)
+    let taskbar_created = wide_string("TaskbarCreated");
+    (*state).taskbar_created_msg = RegisterWindowMessageW(taskbar_created.as_ptr());
+    let reload = wide_string("CleanTaskbarReload");
+    (*state).reload_msg = RegisterWindowMessageW(reload.as_ptr());
+
+    (*state).install_hooks(config)?;
+    SetTimer(hwnd, TIMER_ID_HOOK_HEALTH, HOOK_HEALTH_INTERVAL_MS, None);
+    // The independent monitors pick up a restored pause on their first evaluation
+    (*state).request_visibility_update();
+
+    // The watchdog is a safety net; the hider works without it
+    if config.external_watchdog {
+        match watchdog::spawn(config.watchdog_hang_ms) {
+            Ok(()) => {
+                log::log_info("External watchdog started");
+                SetTimer(hwnd, TIMER_ID_HEARTBEAT, watchdog::HEARTBEAT_INTERVAL_MS, None);
+            }
+            Err(e) => log::log_error(&e.to_string()),
         }
+    }
 
-        // Cleanup
-        cleanup();
+    // External control is optional; the hider works without it
+    match ipc::start(hwnd) {
+        Ok(()) => log::log_info(&format!("IPC pipe {} listening", ipc::pipe_name())),
+        Err(e) => log::log_error(&e.to_string()),
+    }
 
-        Ok(())
+    // Without it a desktop switch is only caught by the next hide or reveal
+    match vdesktop::start(hwnd) {
+        Ok(()) => log::log_info("Watching virtual desktop switches"),
+        Err(e) => log::log_error(&e.to_string()),
+    }
+
+    if command_line_args().iter().any(|arg| arg == "--debug-overlay") {
+        (*state).set_overlay(true);
+    }
+
+    // Add tray icon
+    tray::refresh_monitors();
+    // Wine's notification area is optional, so only treat a failure as fatal natively
+    match tray::TrayIcon::add(hwnd, GetModuleHandleW(null())) {
+        Ok(tray_icon) => Ok(Some(tray_icon)),
+        Err(e) if compat::is_wine() => {
+            log::log_error(&e.to_string());
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
     }
 }
 
-fn cleanup() {
-    unsafe {
-        hooks::uninstall();
-        tray::remove_tray_icon(MAIN_HWND);
-        taskbar::cleanup(TASKBAR_HWND);
+/// Re-reads the config file and applies it to the running instance
+///
+/// A malformed file is reported and the current settings are kept. The report
+/// may show a message box, so `state` isn't borrowed until it's done.
+unsafe fn reload_config(state: *mut AppState) {
+    let config = match config::try_load() {
+        Ok(config) => config,
+        Err(e) => {
+            etw::error(&e);
+            log::log_error(&e);
+            eventlog::report_error(&e);
+            return;
+        }
+    };
+    (*state).apply_reloaded_config(&config);
+}
+
+/// Turns "Start with Windows" on or off, falling back to the current user when an
+/// all-users entry needs administrator rights
+fn toggle_autostart(hwnd: HWND) {
+    let flags = launch_flags();
+    let mut result = autostart::set_enabled(!autostart::is_enabled(), &flags);
+    if let Err(e) = &result {
+        if autostart::needs_elevation(e) {
+            log::log_info("Not elevated; registering autostart for the current user only");
+            result = autostart::enable_for_current_user(&flags);
+            if result.is_ok() {
+                tray::show_balloon(
+                    hwnd,
+                    "Starting for all users needs administrator rights; \
+                     enabled for the current user instead",
+                );
+            }
+        }
+    }
+    if let Err(e) = result {
+        etw::error(&e.to_string());
+        log::log_error(&e.to_string());
     }
 }
 
-fn update_taskbar_visibility() {
-    unsafe {
-        let should_show = WIN_KEY_HELD.load(Ordering::SeqCst) || is_within_delay_period();
+/// Switches to the next mode of the mode cycle, saving it for the next launch
+unsafe fn cycle_mode(state: *mut AppState) {
+    let (reveal_mode, paused) = ((*state).reveal.reveal_mode, (*state).reveal.paused);
+    let Some(next) = config::CycleMode::next(&(*state).mode_cycle, reveal_mode, paused) else {
+        return;
+    };
+    log::log_info(&format!("Mode switched to {}", next.label()));
+    match next {
+        config::CycleMode::Paused => (*state).set_paused(true),
+        config::CycleMode::Reveal(mode) => {
+            (*state).set_paused(false);
+            // A reload reinstalls the hooks the new mode needs
+            if mode != reveal_mode {
+                let value = format!("\"{}\"", mode.as_str());
+                if let Err(e) = config::update(&[("reveal_mode", value)]) {
+                    etw::error(e);
+                    log::log_error(e);
+                    return;
+                }
+                reload_config(state);
+            }
+        }
+    }
+    tray::show_balloon((*state).hwnd, &format!("Mode: {}", next.label()));
+}
 
-        let currently_visible = TASKBAR_SHOULD_BE_VISIBLE.load(Ordering::SeqCst);
+/// Replaces the config file with the defaults, keeping a backup, and applies them
+unsafe fn reset_config(state: *mut AppState) {
+    match config::reset() {
+        Ok(backup) => {
+            if let Some(backup) = backup {
+                log::log_info(&format!("Config backed up to {}", backup.display()));
+            }
+            log::log_info("Config reset to defaults");
+            reload_config(state);
+        }
+        Err(e) => {
+            etw::error(e);
+            log::log_error(e);
+            eventlog::report_error(e);
+        }
+    }
+}
 
-        if should_show && !currently_visible {
-            taskbar::show_taskbar(TASKBAR_HWND);
-            TASKBAR_SHOULD_BE_VISIBLE.store(true, Ordering::SeqCst);
-        } else if !should_show && currently_visible {
-            taskbar::hide_taskbar(TASKBAR_HWND);
-            TASKBAR_SHOULD_BE_VISIBLE.store(false, Ordering::SeqCst);
+/// Saves the settings in effect to a file picked by the user
+unsafe fn export_settings(state: *mut AppState) {
+    let (hwnd, config) = ((*state).hwnd, (*state).effective_config());
+    // The file dialog is modal, so no borrow of `state` is held across it
+    let exported = match tray::pick_settings_file(hwnd, true) {
+        Ok(Some(path)) => config::export(&config, &path).map(|()| path),
+        Ok(None) => return,
+        Err(e) => Err(e.to_string()),
+    };
+    match exported {
+        Ok(path) => tray::show_balloon(hwnd, &format!("Settings exported to {}", path.display())),
+        Err(e) => {
+            log::log_error(&e);
+            tray::show_balloon(hwnd, &e);
         }
     }
 }
 
-fn is_within_delay_period() -> bool {
-    unsafe {
-        if WIN_KEY_RELEASE_TIME == 0 {
-            return false;
+/// Replaces the settings with a file picked by the user and applies them
+unsafe fn import_settings(state: *mut AppState) {
+    let hwnd = (*state).hwnd;
+    let imported = match tray::pick_settings_file(hwnd, false) {
+        Ok(Some(path)) => config::import(&path),
+        Ok(None) => return,
+        Err(e) => Err(e.to_string()),
+    };
+    match imported {
+        Ok(()) => {
+            log::log_info("Settings imported");
+            reload_config(state);
+            tray::show_balloon(hwnd, "Settings imported");
         }
+        Err(e) => {
+            log::log_error(&e);
+            tray::show_balloon(hwnd, &e);
+        }
+    }
+}
 
-        let now = get_current_time_ms();
-        now < WIN_KEY_RELEASE_TIME + WIN_KEY_DELAY_MS
+/// Returns how long a fallback hotkey may go without a repeat before it counts as released
+///
+/// The auto-repeat delay setting runs from 0 to 3, about 250 to 1000 ms.
+fn fallback_release_ms() -> u32 {
+    let mut delay: u32 = 1;
+    unsafe {
+        SystemParametersInfoW(SPI_GETKEYBOARDDELAY, 0, &mut delay as *mut u32 as *mut _, 0);
+    }
+    (delay.min(3) + 1) * 250 + FALLBACK_RELEASE_MARGIN_MS
+}
+
+/// Returns how long ago the last keyboard or mouse input was, in milliseconds
+fn input_idle_ms() -> u32 {
+    let mut info = LASTINPUTINFO { cbSize: size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+    unsafe {
+        if GetLastInputInfo(&mut info) == 0 {
+            return 0;
+        }
+        GetTickCount().wrapping_sub(info.dwTime)
     }
 }
 
@@ -162,64 +591,1258 @@ fn get_current_time_ms() -> u64 {
         .unwrap_or(0)
 }
 
+impl AppState {
+    /// Creates the state for a hider that manages no taskbars yet
+    fn new() -> AppState {
+        AppState {
+            hwnd: null_mut(),
+            taskbar: taskbar::TaskbarController::new(SystemApi),
+            revealed_hwnds: Vec::new(),
+            slide: None,
+            reveal: RevealState::new(),
+            hover_edge: None,
+            hover_monitor: null_mut(),
+            taskbar_created_msg: 0,
+            reload_msg: 0,
+            reevaluate_pending: false,
+            hide_retries_left: 0,
+            hide_retry_count: config::DEFAULT_HIDE_RETRY_COUNT,
+            animation_ms: config::DEFAULT_ANIMATION_MS,
+            animation_style: config::AnimationStyle::Slide,
+            peek_ms: config::DEFAULT_PEEK_MS,
+            show_on_desktop: false,
+            smart_hide: false,
+            hide_only_in: Vec::new(),
+            mode_cycle: Vec::new(),
+            idle: IdleWatch::new(0),
+            idle_reveal_ms: 0,
+            inactive_hide_ms: 0,
+            block_exclusive_fullscreen: false,
+            borderless_peek_ms: 0,
+            battery: LowBatteryWatch::new(0),
+            battery_peek: false,
+            config: config::Config::default(),
+            monitors: Vec::new(),
+        }
+    }
+
+    /// Takes over the timings and reveal settings from the config
+    fn apply_config(&mut self, config: &config::Config) {
+        self.reveal.apply_config(config);
+        self.hide_retry_count = config.hide_retry_count;
+        self.animation_ms = config.animation_ms;
+        self.animation_style = config.animation_style;
+        self.peek_ms = config.peek_ms;
+        self.show_on_desktop = config.show_on_desktop;
+        self.smart_hide = config.smart_hide;
+        self.hide_only_in =
+            config.hide_only_in.iter().map(|exe| exe.to_ascii_lowercase()).collect();
+        self.mode_cycle = config.mode_cycle.clone();
+        self.idle = IdleWatch::new(config.idle_reveal_after_ms);
+        self.idle_reveal_ms = config.idle_reveal_ms;
+        self.inactive_hide_ms = config.inactive_hide_ms;
+        self.block_exclusive_fullscreen = config.block_exclusive_fullscreen;
+        self.borderless_peek_ms = config.borderless_peek_ms;
+        let low_battery_percent =
+            if config.reveal_on_low_battery { config.low_battery_percent } else { 0 };
+        self.battery = LowBatteryWatch::new(low_battery_percent);
+        tray::set_custom_tooltip(&config.tray_tooltip);
+        tray::set_menu_items(&config.tray_menu);
+        commands::set_commands(&config.on_show_command, &config.on_hide_command);
+        autostart::set_scope(config.autostart_scope);
+        self.hover_edge = config.hover_edge;
+        self.config = config.clone();
+        self.monitors = config
+            .monitor_sections
+            .iter()
+            .map(|section| {
+                let mut reveal = RevealState::new();
+                reveal.apply_config(config);
+                reveal.apply_section(section);
+                MonitorReveal { device: section.device.clone(), reveal }
+            })
+            .collect();
+    }
+
+    /// Returns true if the hotkey reveals the shared taskbars or any independent one
+    fn uses_winkey(&self) -> bool {
+        let mut modes = self.monitors.iter().map(|monitor| monitor.reveal.reveal_mode);
+        self.reveal.reveal_mode.uses_winkey() || modes.any(config::RevealMode::uses_winkey)
+    }
+
+    /// Returns true if an edge hover reveals the shared taskbars or any independent one
+    fn uses_edge_hover(&self) -> bool {
+        let mut modes = self.monitors.iter().map(|monitor| monitor.reveal.reveal_mode);
+        self.reveal.reveal_mode.uses_edge_hover() || modes.any(config::RevealMode::uses_edge_hover)
+    }
+
+    /// Returns the settings in effect, including changes made from the tray or hotkeys
+    fn effective_config(&self) -> config::Config {
+        let mut config = self.config.clone();
+        config.paused = self.reveal.paused;
+        config.reveal_mode = self.reveal.reveal_mode;
+        config.always_visible_monitors = self.taskbar.always_visible().to_vec();
+        config
+    }
+
+    /// Returns the screen edge that triggers a hover reveal
+    fn hover_edge(&self) -> taskbar::Edge {
+        self.hover_edge.unwrap_or_else(|| taskbar::primary_edge(&SystemApi))
+    }
+
+    /// Installs the hooks needed by the reveal mode
+    unsafe fn install_hooks(&mut self, config: &config::Config) -> Result<(), String> {
+        if self.uses_winkey() {
+            // Key codes only go to the log, so there's no point reporting them without it
+            let log_key_codes = config.log_key_codes && config.logging;
+            hooks::set_win_scan_codes(&config.win_scan_codes, log_key_codes);
+            if hooks::install(self.hwnd, config.reveal_hotkey)? {
+                log::log_info("Keyboard hook installed");
+            } else {
+                log::log_info("Keyboard hook already installed; hotkey updated");
+            }
+
+            // Another app may already own the combination; the hook still works then
+            if let Some(spec) = config.fallback_hotkey {
+                match hooks::register_fallback(self.hwnd, spec) {
+                    Ok(()) => log::log_info("Fallback hotkey registered"),
+                    Err(e) => log::log_error(&e.to_string()),
+                }
+            }
+        }
+        // Another app may own the combination; quitting from the tray still works
+        if let Some(spec) = config.quit_hotkey {
+            match hooks::register_quit_hotkey(self.hwnd, spec) {
+                Ok(()) => log::log_info("Quit hotkey registered"),
+                Err(e) => log::log_error(&e.to_string()),
+            }
+        }
+        if let Some(spec) = config.mode_cycle_hotkey {
+            match hooks::register_mode_cycle_hotkey(self.hwnd, spec) {
+                Ok(()) => log::log_info("Mode cycle hotkey registered"),
+                Err(e) => log::log_error(&e.to_string()),
+            }
+        }
+        if config.reveal_on_toast {
+            // Toast reveals are a convenience; a failure shouldn't stop the hider
+            match toast::install(self.hwnd) {
+                Ok(()) => log::log_info("Toast watcher installed"),
+                Err(e) => log::log_error(&e.to_string()),
+            }
+        }
+        if config.reveal_on_low_battery {
+            // Windows reports the current charge and power source right away
+            match power::register(self.hwnd) {
+                Ok(()) => log::log_info("Power notifications registered"),
+                Err(e) => log::log_error(&e.to_string()),
+            }
+        }
+        if config.show_on_desktop || config.smart_hide || !config.hide_only_in.is_empty() {
+            match foreground::install(self.hwnd, config.smart_hide) {
+                Ok(()) => log::log_info("Foreground watcher installed"),
+                Err(e) => log::log_error(&e.to_string()),
+            }
+            self.on_foreground_changed(shell::is_desktop_foreground());
+        }
+        if config.watchdog_interval_ms > 0 {
+            let interval_ms = u32::try_from(config.watchdog_interval_ms).unwrap_or(u32::MAX);
+            SetTimer(self.hwnd, TIMER_ID_WATCHDOG, interval_ms, None);
+        }
+        if config.idle_reveal_after_ms > 0 || config.inactive_hide_ms > 0 {
+            let interval_ms = u32::try_from(config.poll_interval_ms).unwrap_or(u32::MAX);
+            SetTimer(self.hwnd, TIMER_ID_IDLE, interval_ms, None);
+        }
+        let flyouts = flyout::configured(config);
+        flyout::set_dialog_owners(&config.reveal_on_dialogs);
+        if !flyouts.is_empty() {
+            match flyout::install(self.hwnd, &flyouts) {
+                Ok(()) => log::log_info("Flyout watcher installed"),
+                Err(e) => log::log_error(&e.to_string()),
+            }
+        }
+        if self.uses_edge_hover() {
+            mouse::set_edge(self.hover_edge());
+            mouse::set_whole_bar(config.hover_whole_bar);
+            mouse::set_taskbars(self.taskbar.hwnds());
+            mouse::install(self.hwnd)?;
+            log::log_info("Mouse hook installed");
+        }
+        Ok(())
+    }
+
+    /// Applies a freshly loaded config to the running instance
+    unsafe fn apply_reloaded_config(&mut self, config: &config::Config) {
+        self.apply_config(config);
+        log::init(config.logging, config.log_max_kb, config.log_backups);
+
+        // The style and the kept monitors decide which bars are managed and how
+        self.taskbar.set_keep_primary(keeps_primary(config));
+        self.taskbar.set_hide_style(config.hide_style, config.hide_alpha);
+        self.taskbar.set_always_visible(config.always_visible_monitors.clone());
+        self.taskbar.set_independent(independent_monitors(config));
+        self.taskbar.set_secondary_hidden(config.hide_secondary_bars);
+        self.reinit_taskbars();
+
+        // Reinstall the hooks so a changed mode or hotkey takes effect
+        hooks::uninstall();
+        hooks::unregister_fallback(self.hwnd);
+        hooks::unregister_quit_hotkey(self.hwnd);
+        hooks::unregister_mode_cycle_hotkey(self.hwnd);
+        KillTimer(self.hwnd, TIMER_ID_IDLE);
+        KillTimer(self.hwnd, TIMER_ID_WATCHDOG);
+        mouse::uninstall();
+        toast::uninstall();
+        power::unregister();
+        foreground::uninstall();
+        flyout::uninstall();
+        KillTimer(self.hwnd, TIMER_ID_FALLBACK_RELEASE);
+        KillTimer(self.hwnd, TIMER_ID_HOLD);
+        // A press still short of the threshold ends like an early release
+        if self.reveal.win_key_pending && self.reveal.release(get_current_time_ms()) {
+            self.arm_release_delay();
+        }
+        self.reveal.win_key_held = false;
+        self.reveal.edge_hover_active = false;
+        self.reveal.desktop_focused = false;
+        self.reveal.flyout_open = false;
+        self.reveal.flyout_holding = false;
+        self.reveal.taskbar_uncovered = false;
+        self.reveal.unlisted_app = false;
+        self.reveal.user_away = false;
+        if let Err(e) = self.install_hooks(config) {
+            etw::error(&e);
+            log::log_error(&e);
+        }
+        log::log_info("Config reloaded");
+
+        self.request_visibility_update();
+        self.schedule_tooltip_update();
+    }
+
+    fn cleanup(&mut self) {
+        ipc::stop();
+        vdesktop::stop();
+        hooks::uninstall();
+        mouse::uninstall();
+        toast::uninstall();
+        power::unregister();
+        foreground::uninstall();
+        flyout::uninstall();
+        hooks::unregister_fallback(self.hwnd);
+        hooks::unregister_quit_hotkey(self.hwnd);
+        hooks::unregister_mode_cycle_hotkey(self.hwnd);
+        log::log_info("Hooks uninstalled");
+        self.set_overlay(false);
+        self.finish_slide();
+        self.taskbar.cleanup();
+        recovery::clear_marker();
+    }
+
+    /// Schedules a single deferred visibility update, dropping duplicate requests
+    fn request_visibility_update(&mut self) {
+        if !std::mem::replace(&mut self.reevaluate_pending, true) {
+            unsafe {
+                PostMessageW(self.hwnd, WM_REEVALUATE, 0, 0);
+            }
+        }
+    }
+
+    fn update_taskbar_visibility(&mut self) {
+        let now = get_current_time_ms();
+        let fullscreen = shell::foreground_fullscreen();
+        let over_fullscreen = fullscreen.is_some();
+        self.reveal.fullscreen_blocked =
+            self.block_exclusive_fullscreen && fullscreen == Some(shell::Fullscreen::Exclusive);
+        match self.reveal.evaluate(now, over_fullscreen) {
+            Decision::Unchanged => {}
+            Decision::Show { all } if self.reveal.is_visible() => {
+                // Only some bars are up; bring up the rest, or the newly hovered monitor's
+                if self.revealed_hwnds.len() < self.taskbar.shared_hwnds().len() {
+                    self.refresh_taskbars();
+                    let wanted = if all {
+                        self.taskbar.shared_hwnds()
+                    } else {
+                        self.hovered_taskbars()
+                    };
+                    let missing: Vec<HWND> = wanted
+                        .into_iter()
+                        .filter(|hwnd| !self.revealed_hwnds.contains(hwnd))
+                        .collect();
+                    if !missing.is_empty() {
+                        self.taskbar.show_bars(&missing);
+                        self.revealed_hwnds.extend(missing);
+                    }
+                }
+            }
+            Decision::Show { all } => {
+                self.refresh_taskbars();
+                self.revealed_hwnds = if all {
+                    self.taskbar.shared_hwnds()
+                } else {
+                    self.hovered_taskbars()
+                };
+                self.apply_visibility(true);
+                self.reveal.shown(now);
+                let event = if self.reveal.paused {
+                    "Taskbar shown (paused)"
+                } else if self.reveal.peek_active {
+                    "Taskbar shown (peek)"
+                } else if self.reveal.desktop_focused {
+                    "Taskbar shown (desktop focused)"
+                } else if self.reveal.flyout_open {
+                    "Taskbar shown (flyout open)"
+                } else if self.reveal.taskbar_uncovered {
+                    "Taskbar shown (not covered)"
+                } else if self.reveal.unlisted_app {
+                    "Taskbar shown (app not listed)"
+                } else if self.reveal.reveals_all() {
+                    "Taskbar shown (trigger: winkey)"
+                } else {
+                    "Taskbar shown (trigger: edge hover)"
+                };
+                etw::info(event);
+                log::log_info(event);
+                if let Err(e) = commands::run_on_show() {
+                    log::log_error(&e.to_string());
+                }
+                self.schedule_tooltip_update();
+            }
+            Decision::HideAfter(remaining) => {
+                let remaining = u32::try_from(remaining).unwrap_or(u32::MAX);
+                unsafe {
+                    SetTimer(self.hwnd, TIMER_ID_MIN_VISIBLE, remaining, None);
+                }
+            }
+            Decision::Hide => {
+                self.refresh_taskbars();
+                self.apply_visibility(false);
+                self.revealed_hwnds = Vec::new();
+                self.reveal.hidden();
+                etw::info("Taskbar hidden");
+                log::log_info("Taskbar hidden");
+                if let Err(e) = commands::run_on_hide() {
+                    log::log_error(&e.to_string());
+                }
+                self.schedule_tooltip_update();
+            }
+        }
+        self.update_monitors(now, over_fullscreen);
+    }
+
+    /// Shows or hides the taskbars of the independently revealed monitors
+    fn update_monitors(&mut self, now: u64, over_fullscreen: bool) {
+        // The soonest time a running delay ends and an evaluation is due again
+        let mut next_check: Option<u64> = None;
+        for monitor in &mut self.monitors {
+            monitor.reveal.follow_shared(&self.reveal, now);
+            match monitor.reveal.evaluate(now, over_fullscreen) {
+                Decision::Show { .. } if monitor.reveal.is_visible() => {}
+                Decision::Show { .. } => {
+                    let bars = self.taskbar.bars_on(&monitor.device);
+                    self.taskbar.show_independent(&bars);
+                    monitor.reveal.shown(now);
+                    log::log_info(&format!("Taskbar on {} shown", monitor.device));
+                }
+                Decision::HideAfter(remaining) => {
+                    next_check = Some(next_check.map_or(remaining, |ms| ms.min(remaining)));
+                }
+                Decision::Hide => {
+                    let bars = self.taskbar.bars_on(&monitor.device);
+                    self.taskbar.hide_independent(&bars);
+                    monitor.reveal.hidden();
+                    log::log_info(&format!("Taskbar on {} hidden", monitor.device));
+                }
+                Decision::Unchanged => {}
+            }
+            if let Some(remaining) = monitor.reveal.delay_remaining(now) {
+                next_check = Some(next_check.map_or(remaining, |ms| ms.min(remaining)));
+            }
+        }
+        if let Some(ms) = next_check {
+            let ms = u32::try_from(ms).unwrap_or(u32::MAX).saturating_add(50);
+            unsafe {
+                SetTimer(self.hwnd, TIMER_ID_MONITORS, ms, None);
+            }
+        }
+    }
+
+    /// Routes an edge hover to the hovered monitor's state, ending the hover of the others
+    fn on_edge_hover(&mut self, monitor: HMONITOR) {
+        self.hover_monitor = monitor;
+        self.reveal.user_away = false;
+        // A borderless fullscreen app suppresses the hover itself, but may allow a peek
+        if self.borderless_peek_ms > 0
+            && !self.reveal.peek_active
+            && shell::foreground_fullscreen() == Some(shell::Fullscreen::Borderless)
+        {
+            self.start_peek_for(self.borderless_peek_ms);
+        }
+        let now = get_current_time_ms();
+        let api = self.taskbar.api();
+        let hovered = self.monitors.iter().position(|independent| {
+            let bars = self.taskbar.bars_on(&independent.device);
+            !taskbar::on_monitor(api, &bars, monitor).is_empty()
+        });
+        for (index, independent) in self.monitors.iter_mut().enumerate() {
+            let active = hovered == Some(index);
+            let reveal = &mut independent.reveal;
+            if active {
+                reveal.cancel_release_delay();
+            } else if reveal.edge_hover_active && reveal.is_visible() {
+                reveal.start_release_delay(now);
+            }
+            reveal.edge_hover_active = active;
+        }
+
+        if hovered.is_none() {
+            self.reveal.edge_hover_active = true;
+            self.cancel_release_delay();
+        } else if std::mem::take(&mut self.reveal.edge_hover_active) && self.reveal.is_visible() {
+            // The cursor moved along the edge from a shared bar onto an independent one
+            self.start_release_delay();
+        }
+        self.request_visibility_update();
+    }
+
+    /// Ends the edge hover, starting the release delay of whichever state it revealed
+    fn on_edge_leave(&mut self) {
+        let now = get_current_time_ms();
+        for independent in &mut self.monitors {
+            let reveal = &mut independent.reveal;
+            if std::mem::take(&mut reveal.edge_hover_active) && reveal.is_visible() {
+                reveal.start_release_delay(now);
+            }
+        }
+        // A hover suppressed by a fullscreen app never revealed the bar
+        if std::mem::take(&mut self.reveal.edge_hover_active) && self.reveal.is_visible() {
+            self.start_release_delay();
+        }
+        self.request_visibility_update();
+    }
+
+    /// Picks up taskbars Explorer recreated without a TaskbarCreated broadcast
+    ///
+    /// The revealed handles died with the old windows, so they are rebuilt
+    /// from the new ones the same way a reveal would pick them.
+    fn refresh_taskbars(&mut self) {
+        if !self.taskbar.refresh() {
+            return;
+        }
+        let message =
+            format!("Taskbar windows were recreated; found {}", self.taskbar.hwnds().len());
+        etw::info(&message);
+        log::log_info(&message);
+        mouse::set_taskbars(self.taskbar.hwnds());
+
+        self.revealed_hwnds = if !self.reveal.is_visible() {
+            Vec::new()
+        } else if self.reveal.reveals_all() || !self.reveal.per_monitor_reveal {
+            self.taskbar.shared_hwnds()
+        } else {
+            self.hovered_taskbars()
+        };
+    }
+
+    /// Returns the shared taskbars on the hovered monitor, or all of them if none is there
+    fn hovered_taskbars(&self) -> Vec<HWND> {
+        let shared = self.taskbar.shared_hwnds();
+        let hwnds = taskbar::on_monitor(self.taskbar.api(), &shared, self.hover_monitor);
+        if hwnds.is_empty() {
+            shared
+        } else {
+            hwnds
+        }
+    }
+
+    /// Shows the revealed taskbars or hides them all, sliding or fading them when an
+    /// animation duration is set
+    fn apply_visibility(&mut self, show: bool) {
+        self.finish_slide();
+
+        // A faded-out bar stays docked and layered, so it is neither slid nor faded
+        let duration_ms = self.animation_ms;
+        if duration_ms == 0 || self.taskbar.is_transparent_style() {
+            if show {
+                self.taskbar.show_bars(&self.revealed_hwnds);
+            } else {
+                self.taskbar.hide();
+                self.start_hide_retries();
+            }
+            return;
+        }
+
+        let api = self.taskbar.api();
+        let shared;
+        let hwnds = if show {
+            &self.revealed_hwnds[..]
+        } else {
+            shared = self.taskbar.shared_hwnds();
+            &shared[..]
+        };
+        let slide = match self.animation_style {
+            config::AnimationStyle::Fade => taskbar::animate_fade(api, hwnds, show, duration_ms),
+            config::AnimationStyle::Slide if show => taskbar::animate_show(api, hwnds, duration_ms),
+            config::AnimationStyle::Slide => taskbar::animate_hide(api, hwnds, duration_ms),
+        };
+        self.slide = Some(slide);
+        unsafe {
+            SetTimer(self.hwnd, TIMER_ID_ANIMATION, ANIMATION_TICK_MS, None);
+        }
+    }
+
+    /// Advances the running slide, finishing it once it reaches the end
+    fn step_slide(&mut self) {
+        if self.slide.as_ref().is_none_or(|slide| slide.step(self.taskbar.api())) {
+            self.finish_slide();
+        }
+    }
+
+    /// Completes any running slide immediately
+    fn finish_slide(&mut self) {
+        let Some(slide) = self.slide.take() else {
+            return;
+        };
+        unsafe {
+            KillTimer(self.hwnd, TIMER_ID_ANIMATION);
+        }
+        slide.finish(self.taskbar.api());
+        if !slide.is_showing() {
+            self.start_hide_retries();
+        }
+    }
+
+    /// Re-enumerates the taskbars and re-applies the current visibility to them
+    fn reinit_taskbars(&mut self) {
+        self.finish_slide();
+        match self.taskbar.init() {
+            Ok(()) => {
+                log::log_info(&format!("Found {} taskbar(s)", self.taskbar.hwnds().len()));
+                recovery::write_marker(self.taskbar.autohide_set_by_us());
+                if self.reveal.is_visible() {
+                    self.revealed_hwnds = self.taskbar.shared_hwnds();
+                    self.taskbar.show_bars(&self.revealed_hwnds.clone());
+                } else {
+                    self.start_hide_retries();
+                }
+                // Init hid every bar, including the independent ones still revealed
+                for monitor in self.monitors.iter().filter(|m| m.reveal.is_visible()) {
+                    let bars = self.taskbar.bars_on(&monitor.device);
+                    self.taskbar.show_independent(&bars);
+                }
+            }
+            Err(e) => log::log_error(e),
+        }
+        mouse::set_edge(self.hover_edge());
+        mouse::set_taskbars(self.taskbar.hwnds());
+    }
+
+    /// Re-attempts the hide on a short timer instead of blocking the message loop
+    fn start_hide_retries(&mut self) {
+        // A faded bar is still visible, and Explorer doesn't undo the fade
+        if self.taskbar.is_transparent_style() {
+            return;
+        }
+        self.hide_retries_left = self.hide_retry_count;
+        if self.hide_retries_left > 0 {
+            unsafe {
+                SetTimer(self.hwnd, TIMER_ID_HIDE_RETRY, HIDE_RETRY_INTERVAL_MS, None);
+            }
+        }
+    }
+
+    /// Hides the taskbar again if a virtual desktop switch brought it back
+    fn reassert_hidden(&mut self) {
+        if self.reveal.is_visible() {
+            return;
+        }
+        self.finish_slide();
+        self.taskbar.hide();
+        self.start_hide_retries();
+    }
+
+    /// Handles one hide retry tick, stopping once the bars stay hidden
+    fn retry_hide(&mut self) {
+        let done = self.reveal.is_visible()
+            || !self.taskbar.is_any_visible()
+            || self.hide_retries_left == 0;
+
+        if done {
+            unsafe {
+                KillTimer(self.hwnd, TIMER_ID_HIDE_RETRY);
+            }
+        } else {
+            self.hide_retries_left -= 1;
+            self.taskbar.hide();
+        }
+    }
+
+    /// Corrects bars that something we get no message for showed again, and our
+    /// auto-hide being turned off
+    fn check_intended_state(&mut self) {
+        // A running animation moves the bars on purpose
+        if self.slide.is_some() {
+            return;
+        }
+        if self.taskbar.reassert_autohide() {
+            log::log_info("Watchdog: auto-hide was turned off, restored it");
+        }
+        // A faded bar is meant to stay visible
+        let reappeared = !self.reveal.is_visible()
+            && !self.taskbar.is_transparent_style()
+            && self.taskbar.is_any_visible();
+        if reappeared {
+            etw::info("Watchdog: taskbar reappeared, hiding it again");
+            log::log_info("Watchdog: taskbar reappeared, hiding it again");
+            self.taskbar.hide();
+        }
+    }
+
+    /// Re-checks the foreground window for the desktop, smart hide and per-app triggers
+    fn on_foreground_changed(&mut self, desktop: bool) {
+        self.reveal.desktop_focused = self.show_on_desktop && desktop;
+        self.reveal.unlisted_app = !self.hide_only_in.is_empty()
+            && shell::owner_exe_name(unsafe { GetForegroundWindow() })
+                .is_none_or(|exe| !self.hide_only_in.contains(&exe));
+        self.reveal.taskbar_uncovered = self.smart_hide
+            && shell::foreground_rect().is_none_or(|rect| {
+                !taskbar::is_covered(self.taskbar.api(), self.taskbar.hwnds(), rect)
+            });
+        self.request_visibility_update();
+    }
+
+    /// Shows the taskbar for the configured peek duration
+    fn start_peek(&mut self) {
+        self.start_peek_for(self.peek_ms);
+    }
+
+    /// Reveals the taskbar when input resumes after an idle period, and hides it
+    /// while there is no input for `inactive_hide_ms`
+    fn check_idle(&mut self) {
+        let idle_ms = u64::from(input_idle_ms());
+        let away = self.inactive_hide_ms > 0 && idle_ms >= self.inactive_hide_ms;
+        if away != self.reveal.user_away {
+            self.reveal.user_away = away;
+            if away {
+                log::log_info("No input for a while, hiding");
+            }
+            self.request_visibility_update();
+            self.schedule_tooltip_update();
+        }
+        if self.idle.update(idle_ms) {
+            log::log_info("Input resumed after being idle, revealing");
+            self.start_peek_for(self.idle_reveal_ms);
+        }
+    }
+
+    /// Shows the taskbar for the given time, like a peek
+    fn start_peek_for(&mut self, duration_ms: u64) {
+        let peek_ms = u32::try_from(duration_ms).unwrap_or(u32::MAX);
+        self.reveal.peek_active = true;
+        self.battery_peek = false;
+        unsafe {
+            SetTimer(self.hwnd, TIMER_ID_PEEK, peek_ms, None);
+        }
+        self.request_visibility_update();
+        self.schedule_tooltip_update();
+    }
+
+    /// Ends a peek early or once its timer fires
+    fn stop_peek(&mut self) {
+        self.reveal.peek_active = false;
+        self.battery_peek = false;
+        unsafe {
+            KillTimer(self.hwnd, TIMER_ID_PEEK);
+        }
+        self.request_visibility_update();
+        self.schedule_tooltip_update();
+    }
+
+    /// Peeks when the battery turns low, ending the peek early once it no longer is
+    ///
+    /// # Safety
+    /// `lparam` must be that of a `PBT_POWERSETTINGCHANGE` broadcast.
+    unsafe fn on_power_change(&mut self, lparam: LPARAM) {
+        let low = match power::read_change(lparam) {
+            Some(PowerChange::BatteryPercent(percent)) => self.battery.set_percent(percent),
+            Some(PowerChange::OnBattery(on_battery)) => self.battery.set_on_battery(on_battery),
+            None => return,
+        };
+        if low {
+            log::log_info("Battery low, revealing");
+            self.start_peek();
+            self.battery_peek = true;
+        } else if self.battery_peek && !self.battery.is_low() {
+            self.stop_peek();
+        }
+    }
+
+    /// Pauses or resumes hiding and remembers the choice for the next launch
+    fn set_paused(&mut self, paused: bool) {
+        if std::mem::replace(&mut self.reveal.paused, paused) != paused {
+            if let Err(e) = config::update(&[("paused", paused.to_string())]) {
+                etw::error(e);
+                log::log_error(e);
+            }
+        }
+        self.request_visibility_update();
+        self.schedule_tooltip_update();
+    }
+
+    /// Hides or keeps the taskbar on a monitor and remembers the choice for the next launch
+    fn toggle_monitor(&mut self, device: &str) {
+        let mut kept = self.taskbar.always_visible().to_vec();
+        match kept.iter().position(|name| name == device) {
+            Some(index) => {
+                kept.remove(index);
+            }
+            None => kept.push(device.to_string()),
+        }
+        let value = config::format_monitors(&kept);
+        self.taskbar.set_always_visible(kept);
+        self.reinit_taskbars();
+        if let Err(e) = config::update(&[("always_visible_monitors", value)]) {
+            etw::error(e);
+            log::log_error(e);
+        }
+    }
+
+    /// Coalesces tooltip refreshes so rapid transitions cause a single update
+    fn schedule_tooltip_update(&self) {
+        unsafe {
+            SetTimer(self.hwnd, TIMER_ID_TOOLTIP, TOOLTIP_THROTTLE_MS, None);
+        }
+    }
+
+    /// Describes the current state and the reveal triggers for the tray tooltip, after
+    /// the app name
+    fn status_text(&self) -> String {
+        let reveal = &self.reveal;
+        let state = if reveal.paused {
+            "Paused"
+        } else if !reveal.is_visible() && reveal.user_away {
+            "Taskbar hidden (no input)"
+        } else if !reveal.is_visible() {
+            "Taskbar hidden"
+        } else if reveal.peek_active {
+            "Shown (temporarily)"
+        } else if reveal.desktop_focused {
+            "Shown (desktop focused)"
+        } else if reveal.flyout_open || reveal.flyout_holding {
+            "Shown (flyout open)"
+        } else if reveal.taskbar_uncovered {
+            "Shown (not covered)"
+        } else if reveal.unlisted_app {
+            "Shown (app not listed)"
+        } else if reveal.win_key_held {
+            "Shown (Windows key held)"
+        } else if reveal.edge_hover_active {
+            "Shown (edge hover)"
+        } else {
+            "Shown (release delay)"
+        };
+        format!("{}; reveal: {}", state, reveal.reveal_mode.label())
+    }
+
+    /// Describes the current mode for the context menu header, after the app name
+    fn menu_header(&self) -> String {
+        let mode = if self.reveal.paused {
+            "paused".to_string()
+        } else {
+            format!("hiding, reveal by {}", self.reveal.reveal_mode.label())
+        };
+        format!("{} - {}", tray::app_name(), mode)
+    }
+
+    /// Describes the state, the active triggers and the foreground window's class
+    /// for the debug overlay
+    fn overlay_text(&self) -> String {
+        let reveal = &self.reveal;
+        let triggers: Vec<&str> = [
+            (reveal.win_key_held, "hotkey held"),
+            (reveal.win_key_pending, "hotkey pending"),
+            (reveal.edge_hover_active, "edge hover"),
+            (reveal.peek_active, "peek"),
+            (reveal.desktop_focused, "desktop"),
+            (reveal.flyout_open || reveal.flyout_holding, "flyout"),
+            (reveal.taskbar_uncovered, "not covered"),
+            (reveal.unlisted_app, "app not listed"),
+            (reveal.user_away, "away"),
+            (reveal.is_within_delay_period(get_current_time_ms()), "release delay"),
+        ]
+        .into_iter()
+        .filter(|&(active, _)| active)
+        .map(|(_, name)| name)
+        .collect();
+        let triggers = if triggers.is_empty() { "none".to_string() } else { triggers.join(", ") };
+        let foreground = shell::window_class(unsafe { GetForegroundWindow() });
+        format!("{}\nTriggers: {}\nForeground: {}", self.status_text(), triggers, foreground)
+    }
+
+    /// Opens or closes the debug overlay and the timer that refreshes it
+    fn set_overlay(&mut self, shown: bool) {
+        unsafe {
+            if shown {
+                overlay::show(self.hwnd);
+                overlay::set_text(&self.overlay_text());
+                SetTimer(self.hwnd, TIMER_ID_OVERLAY, OVERLAY_REFRESH_MS, None);
+            } else {
+                KillTimer(self.hwnd, TIMER_ID_OVERLAY);
+                overlay::hide();
+            }
+        }
+    }
+
+    /// Re-installs the keyboard hook if Windows has silently removed it
+    fn check_hook_health(&mut self) {
+        if hooks::check_health() {
+            return;
+        }
+        // Routine after a sleep or a busy spell, so it isn't reported as an error
+        etw::info("Keyboard hook stopped receiving input, reinstalling");
+        log::log_info("Keyboard hook stopped receiving input, reinstalling");
+        if let Err(e) = hooks::reinstall() {
+            etw::error(&e.to_string());
+            log::log_error(&e.to_string());
+            return;
+        }
+        // Key changes were missed while the hook was gone
+        self.reveal.win_key_held = hooks::resync_key_state();
+        self.request_visibility_update();
+    }
+
+    /// Reveals the taskbar once the reveal state has taken a Windows key hold
+    fn start_win_key_hold(&mut self) {
+        // A hold takes over from a running peek
+        if self.reveal.peek_active {
+            self.stop_peek();
+        }
+        // The hold ended any release delay; its hide timer goes with it
+        unsafe {
+            KillTimer(self.hwnd, TIMER_ID_HIDE_TASKBAR);
+        }
+        self.request_visibility_update();
+    }
+
+    /// Handles the reveal hotkey going down
+    fn on_win_key_down(&mut self) {
+        // The press is input, so don't wait for the idle poll to notice it
+        self.reveal.user_away = false;
+        // Only a hold past the threshold reveals, so shortcuts like Win+E don't flash it
+        if !self.reveal.press() {
+            self.start_win_key_hold();
+            return;
+        }
+        let threshold_ms = u32::try_from(self.reveal.hold_threshold_ms).unwrap_or(u32::MAX);
+        unsafe {
+            // A release delay the press took over mustn't hide the bar meanwhile
+            KillTimer(self.hwnd, TIMER_ID_HIDE_TASKBAR);
+            SetTimer(self.hwnd, TIMER_ID_HOLD, threshold_ms, None);
+        }
+    }
+
+    /// Handles the reveal hotkey going up
+    fn on_win_key_up(&mut self) {
+        unsafe {
+            KillTimer(self.hwnd, TIMER_ID_HOLD);
+        }
+        // Released before the threshold: only a release delay the press took over resumes
+        if self.reveal.release(get_current_time_ms()) {
+            self.arm_release_delay();
+        }
+    }
+
+    /// Keeps the taskbar up for the reveal delay after a trigger is released
+    fn start_release_delay(&mut self) {
+        self.reveal.start_release_delay(get_current_time_ms());
+        self.arm_release_delay();
+    }
+
+    /// Arms the hide timer for a release delay that just started
+    ///
+    /// Re-arming replaces the pending hide timer, so only one is ever outstanding.
+    fn arm_release_delay(&mut self) {
+        // Instant mode has no delay to wait out, so the bar hides on this update
+        if self.reveal.instant {
+            self.request_visibility_update();
+            self.schedule_tooltip_update();
+            return;
+        }
+        let delay_ms = u32::try_from(self.reveal.reveal_delay_ms).unwrap_or(u32::MAX);
+        unsafe {
+            SetTimer(self.hwnd, TIMER_ID_HIDE_TASKBAR, delay_ms.saturating_add(50), None);
+        }
+        self.request_visibility_update();
+        self.schedule_tooltip_update();
+    }
+
+    /// Ends a running release delay and its hide timer once a trigger is active again
+    fn cancel_release_delay(&mut self) {
+        unsafe {
+            KillTimer(self.hwnd, TIMER_ID_HIDE_TASKBAR);
+        }
+        self.reveal.cancel_release_delay();
+    }
+
+    /// Handles a timer of the main window
+    unsafe fn on_timer(&mut self, id: usize) {
+        let hwnd = self.hwnd;
+        if id == TIMER_ID_HIDE_TASKBAR {
+            KillTimer(hwnd, TIMER_ID_HIDE_TASKBAR);
+            self.request_visibility_update();
+        } else if id == TIMER_ID_FALLBACK_RELEASE {
+            KillTimer(hwnd, TIMER_ID_FALLBACK_RELEASE);
+            // If the hook saw the press too, its own release event hides the bar
+            if !hooks::is_hotkey_active() {
+                self.reveal.win_key_held = false;
+                self.start_release_delay();
+            }
+        } else if id == TIMER_ID_EXPLORER_RESTART {
+            KillTimer(hwnd, TIMER_ID_EXPLORER_RESTART);
+            etw::info("Explorer restarted, re-initializing");
+            log::log_info("Explorer restarted, re-initializing");
+            self.reinit_taskbars();
+            if let Err(e) = tray::readd_tray_icon(hwnd, GetModuleHandleW(null())) {
+                log::log_error(&e.to_string());
+            }
+            self.schedule_tooltip_update();
+        } else if id == TIMER_ID_DISPLAY_CHANGE {
+            KillTimer(hwnd, TIMER_ID_DISPLAY_CHANGE);
+            etw::info("Display configuration changed, re-initializing");
+            log::log_info("Display configuration changed, re-initializing");
+            tray::refresh_monitors();
+            self.reinit_taskbars();
+        } else if id == TIMER_ID_HOLD {
+            KillTimer(hwnd, TIMER_ID_HOLD);
+            if self.reveal.hold_elapsed() {
+                self.start_win_key_hold();
+            }
+        } else if id == TIMER_ID_HOOK_HEALTH {
+            self.check_hook_health();
+        } else if id == TIMER_ID_PEEK {
+            self.stop_peek();
+        } else if id == TIMER_ID_MIN_VISIBLE {
+            KillTimer(hwnd, TIMER_ID_MIN_VISIBLE);
+            self.request_visibility_update();
+        } else if id == TIMER_ID_MONITORS {
+            KillTimer(hwnd, TIMER_ID_MONITORS);
+            self.request_visibility_update();
+        } else if id == TIMER_ID_ANIMATION {
+            self.step_slide();
+        } else if id == TIMER_ID_HIDE_RETRY {
+            self.retry_hide();
+        } else if id == TIMER_ID_OVERLAY {
+            overlay::set_text(&self.overlay_text());
+        } else if id == TIMER_ID_IDLE {
+            self.check_idle();
+        } else if id == TIMER_ID_WATCHDOG {
+            self.check_intended_state();
+        } else if id == TIMER_ID_HEARTBEAT {
+            recovery::write_marker(self.taskbar.autohide_set_by_us());
+        } else if id == TIMER_ID_TOOLTIP {
+            KillTimer(hwnd, TIMER_ID_TOOLTIP);
+            tray::set_tooltip(hwnd, &self.status_text());
+        }
+    }
+}
+
 unsafe extern "system" fn window_proc(
     hwnd: HWND,
     msg: u32,
     wparam: WPARAM,
     lparam: LPARAM,
 ) -> LRESULT {
+    // Take ownership of the state passed to CreateWindowExW
+    if msg == WM_NCCREATE {
+        let create = &*(lparam as *const CREATESTRUCTW);
+        let state = create.lpCreateParams as *mut AppState;
+        (*state).hwnd = hwnd;
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, state as isize);
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+
+    let state = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut AppState;
+    if state.is_null() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    if msg == WM_NCDESTROY {
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+        drop(Box::from_raw(state));
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+
+    // The state is borrowed per message and never across the modal loops of the
+    // tray menu, settings window or message boxes, which re-enter this procedure
     match msg {
         // Tray icon messages
         m if m == tray::WM_TRAYICON => {
-            if let Some(result) = tray::handle_tray_message(lparam, hwnd) {
+            let (header, paused) = ((*state).menu_header(), (*state).reveal.paused);
+            let kept = (*state).taskbar.always_visible().to_vec();
+            if let Some(result) = tray::handle_tray_message(lparam, hwnd, &header, paused, &kept) {
                 return result;
             }
         }
 
-        // Menu command (Quit)
-        WM_COMMAND => {
-            if wparam == tray::IDM_QUIT {
+        // Monitor picked in the Monitors submenu
+        WM_COMMAND if tray::monitor_command(wparam).is_some() => {
+            if let Some(device) = tray::monitor_command(wparam) {
+                (*state).toggle_monitor(&device);
+            }
+            return 0;
+        }
+
+        // Menu commands
+        WM_COMMAND => match tray::menu_command(wparam) {
+            Some(config::MenuItem::Quit) => {
                 PostQuitMessage(0);
                 return 0;
             }
+            Some(config::MenuItem::Autostart) => {
+                toggle_autostart(hwnd);
+                return 0;
+            }
+            Some(config::MenuItem::LogonTask) => {
+                let enable = !autostart::is_task_registered();
+                if let Err(e) = autostart::set_task_registered(enable, &launch_flags()) {
+                    etw::error(&e.to_string());
+                    log::log_error(&e.to_string());
+                    tray::show_balloon(hwnd, &e.to_string());
+                }
+                return 0;
+            }
+            Some(config::MenuItem::Settings) => {
+                settings::show(hwnd);
+                return 0;
+            }
+            Some(config::MenuItem::OpenConfig) => {
+                let opened = config::ensure_exists()
+                    .map_err(String::from)
+                    .and_then(|path| tray::open_config(hwnd, &path).map_err(String::from));
+                if let Err(e) = opened {
+                    etw::error(&e);
+                    log::log_error(&e);
+                }
+                return 0;
+            }
+            Some(config::MenuItem::Reload) => {
+                reload_config(state);
+                return 0;
+            }
+            Some(config::MenuItem::Reset) => {
+                // The confirmation is modal, so no borrow of `state` is held across it
+                if tray::confirm_reset(hwnd) {
+                    reset_config(state);
+                }
+                return 0;
+            }
+            Some(config::MenuItem::Export) => {
+                export_settings(state);
+                return 0;
+            }
+            Some(config::MenuItem::Import) => {
+                import_settings(state);
+                return 0;
+            }
+            Some(config::MenuItem::Overlay) => {
+                (*state).set_overlay(!overlay::is_shown());
+                return 0;
+            }
+            Some(config::MenuItem::About) => {
+                tray::show_about(hwnd);
+                return 0;
+            }
+            Some(config::MenuItem::Peek) => {
+                (*state).start_peek();
+                return 0;
+            }
+            Some(config::MenuItem::Toggle) => {
+                let state = &mut *state;
+                state.set_paused(!state.reveal.paused);
+                return 0;
+            }
+            Some(config::MenuItem::Monitors | config::MenuItem::Separator) | None => {}
+        },
+
+        // Command from a script over the IPC pipe
+        ipc::WM_IPC_COMMAND => {
+            let state = &mut *state;
+            match ipc::Command::from_wparam(wparam) {
+                Some(ipc::Command::Pause) => state.set_paused(true),
+                Some(ipc::Command::Resume) => state.set_paused(false),
+                Some(ipc::Command::Toggle) => state.set_paused(!state.reveal.paused),
+                Some(ipc::Command::Quit) => PostQuitMessage(0),
+                None => {}
+            }
+            return 0;
         }
 
         // Windows key down
         m if m == hooks::WM_WINKEY_DOWN => {
-            WIN_KEY_HELD.store(true, Ordering::SeqCst);
-            WIN_KEY_RELEASE_TIME = 0;
-            update_taskbar_visibility();
+            (*state).on_win_key_down();
             return 0;
         }
 
         // Windows key up
         m if m == hooks::WM_WINKEY_UP => {
-            WIN_KEY_HELD.store(false, Ordering::SeqCst);
-            WIN_KEY_RELEASE_TIME = get_current_time_ms();
-            SetTimer(hwnd, TIMER_ID_HIDE_TASKBAR, WIN_KEY_DELAY_MS as u32 + 50, None);
-            update_taskbar_visibility();
+            (*state).on_win_key_up();
+            return 0;
+        }
+
+        // Fallback hotkey pressed, or auto-repeating while held
+        WM_HOTKEY if wparam == hooks::FALLBACK_HOTKEY_ID as WPARAM => {
+            let state = &mut *state;
+            // There is no release event, so treat a lapse in repeats as the release
+            SetTimer(hwnd, TIMER_ID_FALLBACK_RELEASE, fallback_release_ms(), None);
+            if !std::mem::replace(&mut state.reveal.win_key_held, true) {
+                state.cancel_release_delay();
+                state.request_visibility_update();
+            }
+            return 0;
+        }
+
+        // Quit hotkey; shuts down the same way as the tray's Quit item
+        WM_HOTKEY if wparam == hooks::QUIT_HOTKEY_ID as WPARAM => {
+            PostQuitMessage(0);
+            return 0;
+        }
+
+        // Mode cycle hotkey
+        WM_HOTKEY if wparam == hooks::MODE_CYCLE_HOTKEY_ID as WPARAM => {
+            cycle_mode(state);
+            return 0;
+        }
+
+        // A notification toast appeared; show the bar for a peek
+        toast::WM_TOAST_SHOWN => {
+            (*state).start_peek();
+            return 0;
+        }
+
+        // Foreground window changed, moved or resized
+        foreground::WM_FOREGROUND_CHANGED => {
+            (*state).on_foreground_changed(wparam != 0);
+            return 0;
+        }
+
+        // A watched flyout opened, or the last open one of a kind closed
+        flyout::WM_FLYOUT_CHANGED => {
+            let state = &mut *state;
+            state.reveal.flyout_open = wparam & flyout::OPEN_REVEALING != 0;
+            state.reveal.flyout_holding = wparam & flyout::OPEN_HOLDING != 0;
+            if wparam != 0 {
+                state.cancel_release_delay();
+                state.request_visibility_update();
+            } else if state.reveal.is_visible() {
+                state.start_release_delay();
+            }
+            return 0;
+        }
+
+        // Cursor reached the taskbar's screen edge
+        m if m == mouse::WM_EDGE_HOVER => {
+            (*state).on_edge_hover(lparam as HMONITOR);
+            return 0;
+        }
+
+        // Cursor left the screen edge
+        m if m == mouse::WM_EDGE_LEAVE => {
+            (*state).on_edge_leave();
+            return 0;
+        }
+
+        // Settings window saved new values
+        settings::WM_SETTINGS_CHANGED => {
+            reload_config(state);
+            return 0;
+        }
+
+        // Coalesced visibility update
+        WM_REEVALUATE => {
+            let state = &mut *state;
+            state.reevaluate_pending = false;
+            state.update_taskbar_visibility();
+            return 0;
+        }
+
+        // Key pressed while key code logging is on
+        m if m == hooks::WM_KEY_CODES => {
+            log::log_info(&format!("Key down: vk 0x{:02X}, scan code 0x{:X}", wparam, lparam));
+            return 0;
+        }
+
+        // Desktop switched (secure desktop shown or dismissed)
+        m if m == hooks::WM_DESKTOP_SWITCH => {
+            if hooks::handle_desktop_switch() {
+                let state = &mut *state;
+                // Key-up events may have been missed while the hook was blind
+                state.reveal.win_key_held = hooks::resync_key_state();
+                state.cancel_release_delay();
+                state.request_visibility_update();
+            }
+            return 0;
+        }
+
+        // Virtual desktop switched; Explorer may have re-shown the taskbar
+        vdesktop::WM_VIRTUAL_DESKTOP_CHANGED => {
+            let state = &mut *state;
+            state.reassert_hidden();
+            state.request_visibility_update();
             return 0;
         }
 
         // Timer for delayed hide
         WM_TIMER => {
-            if wparam == TIMER_ID_HIDE_TASKBAR {
-                KillTimer(hwnd, TIMER_ID_HIDE_TASKBAR);
-                update_taskbar_visibility();
-            }
+            (*state).on_timer(wparam);
             return 0;
         }
 
         // TaskbarCreated - Explorer restarted
-        m if TASKBAR_CREATED_MSG != 0 && m == TASKBAR_CREATED_MSG => {
-            if let Ok(h) = taskbar::init() {
-                TASKBAR_HWND = h;
+        m if (*state).taskbar_created_msg != 0 && m == (*state).taskbar_created_msg => {
+            // Re-arming the timer folds a burst of broadcasts into one re-init
+            SetTimer(hwnd, TIMER_ID_EXPLORER_RESTART, EXPLORER_RESTART_SETTLE_MS, None);
+            return 0;
+        }
+
+        // CleanTaskbarReload - a script or installer edited the config file
+        m if (*state).reload_msg != 0 && m == (*state).reload_msg => {
+            reload_config(state);
+            return 0;
+        }
+
+        // The work area changes when the taskbar is dragged to another edge
+        WM_SETTINGCHANGE if wparam == SPI_SETWORKAREA as WPARAM => {
+            mouse::set_edge((*state).hover_edge());
+        }
+
+        // Monitor attached or removed, resolution or scaling changed
+        WM_DISPLAYCHANGE | WM_DPICHANGED => {
+            // Re-arming the timer folds a burst of changes into one re-init
+            SetTimer(hwnd, TIMER_ID_DISPLAY_CHANGE, DISPLAY_CHANGE_SETTLE_MS, None);
+            return 0;
+        }
+
+        // Battery charge or power source changed
+        WM_POWERBROADCAST if wparam == PBT_POWERSETTINGCHANGE as WPARAM => {
+            (*state).on_power_change(lparam);
+            return 1;
+        }
+
+        // Never block a shutdown or logoff
+        WM_QUERYENDSESSION => {
+            return 1;
+        }
+
+        // The process may be terminated once this returns, before the message loop exits
+        WM_ENDSESSION => {
+            if wparam != 0 {
+                log::log_info("Session ending, restoring the taskbar");
+                // WM_DESTROY restores the taskbar and stops the timers that could hide it again
+                DestroyWindow(hwnd);
             }
-            tray::add_tray_icon(MAIN_HWND);
             return 0;
         }
 
         WM_DESTROY => {
+            (*state).cleanup();
             PostQuitMessage(0);
             return 0;
         }
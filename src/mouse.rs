@@ -0,0 +1,162 @@
+//! Mouse hook module
+//!
+//! Installs a low-level mouse hook that reports when the cursor touches the
+//! hover edge (by default the one the taskbar is docked to), or optionally
+//! enters the area of a hidden bar, and when it leaves the revealed bars.
+
+use std::mem::size_of;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{
+    GetMonitorInfoW, MonitorFromPoint, PtInRect, HMONITOR, MONITORINFO, MONITOR_DEFAULTTONEAREST,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, GetWindowRect, IsWindowVisible, PostMessageW, SetWindowsHookExW,
+    UnhookWindowsHookEx, MSLLHOOKSTRUCT, WH_MOUSE_LL, WM_MOUSEMOVE, WM_USER,
+};
+use taskbar_hider::taskbar::Edge;
+use taskbar_hider::winapi::Error;
+
+/// Posted when the cursor reaches the edge; `lparam` is the monitor under it
+pub const WM_EDGE_HOVER: u32 = WM_USER + 110;
+pub const WM_EDGE_LEAVE: u32 = WM_USER + 111;
+
+static HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static NOTIFY_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static HOVERING: AtomicBool = AtomicBool::new(false);
+/// Tick count of the last mouse event the hook saw, including clicks and the wheel
+static LAST_INPUT_TIME: AtomicU32 = AtomicU32::new(0);
+/// Monitor the last hover was reported for
+static HOVER_MONITOR: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+/// Managed taskbars, stored as addresses since `HWND` isn't `Send`
+static TASKBARS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static EDGE: AtomicU32 = AtomicU32::new(Edge::Bottom as u32);
+/// The whole area of a hidden bar reveals it, not just the screen edge
+static WHOLE_BAR: AtomicBool = AtomicBool::new(false);
+
+/// Returns true if the point lies on the taskbar's edge of the monitor under it
+///
+/// Each monitor is checked against its own bounds, so mixed resolutions and
+/// scale factors work as long as the process is DPI aware.
+fn is_at_edge(pt: POINT, monitor: HMONITOR) -> bool {
+    unsafe {
+        let mut info: MONITORINFO = std::mem::zeroed();
+        info.cbSize = size_of::<MONITORINFO>() as u32;
+        if GetMonitorInfoW(monitor, &mut info) == 0 {
+            return false;
+        }
+
+        let bounds = info.rcMonitor;
+        match Edge::from_abe(EDGE.load(Ordering::Relaxed)) {
+            Edge::Left => pt.x <= bounds.left,
+            Edge::Top => pt.y <= bounds.top,
+            Edge::Right => pt.x >= bounds.right - 1,
+            Edge::Bottom => pt.y >= bounds.bottom - 1,
+        }
+    }
+}
+
+/// Returns true if the point lies over one of the taskbars, or only the visible
+/// ones if `visible_only` is set
+///
+/// A hidden bar keeps its docked rect, so its area can still be hit tested.
+fn is_over_taskbar(pt: POINT, visible_only: bool) -> bool {
+    let Ok(taskbars) = TASKBARS.lock() else {
+        return false;
+    };
+    taskbars.iter().any(|&hwnd| unsafe {
+        let hwnd = hwnd as HWND;
+        let mut rect: RECT = std::mem::zeroed();
+        (!visible_only || IsWindowVisible(hwnd) != 0)
+            && GetWindowRect(hwnd, &mut rect) != 0
+            && PtInRect(&rect, pt) != 0
+    })
+}
+
+/// Low-level mouse hook callback
+unsafe extern "system" fn mouse_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        LAST_INPUT_TIME.store((*(lparam as *const MSLLHOOKSTRUCT)).time, Ordering::Relaxed);
+    }
+    if code >= 0 && wparam as u32 == WM_MOUSEMOVE {
+        let info = &*(lparam as *const MSLLHOOKSTRUCT);
+        // Once revealed, the whole bar counts as hovering so its buttons can be reached
+        let was_hovering = HOVERING.load(Ordering::Relaxed);
+        let monitor = MonitorFromPoint(info.pt, MONITOR_DEFAULTTONEAREST);
+        let at_edge = is_at_edge(info.pt, monitor)
+            || (was_hovering && is_over_taskbar(info.pt, true))
+            || (WHOLE_BAR.load(Ordering::Relaxed) && is_over_taskbar(info.pt, false));
+
+        // Report transitions into and out of the edge, and moves along it onto
+        // another monitor so a per-monitor reveal can follow the cursor
+        let hwnd = NOTIFY_HWND.load(Ordering::Relaxed) as HWND;
+        if at_edge {
+            let previous = HOVER_MONITOR.swap(monitor, Ordering::Relaxed);
+            if !was_hovering || previous != monitor {
+                HOVERING.store(true, Ordering::Relaxed);
+                PostMessageW(hwnd, WM_EDGE_HOVER, 0, monitor as LPARAM);
+            }
+        } else if was_hovering {
+            HOVERING.store(false, Ordering::Relaxed);
+            HOVER_MONITOR.store(null_mut(), Ordering::Relaxed);
+            PostMessageW(hwnd, WM_EDGE_LEAVE, 0, 0);
+        }
+    }
+
+    CallNextHookEx(null_mut(), code, wparam, lparam)
+}
+
+/// Sets the screen edge that triggers a hover reveal
+pub fn set_edge(edge: Edge) {
+    EDGE.store(edge as u32, Ordering::SeqCst);
+}
+
+/// Makes the whole area of a hidden bar reveal it, instead of only the screen edge
+pub fn set_whole_bar(whole_bar: bool) {
+    WHOLE_BAR.store(whole_bar, Ordering::SeqCst);
+}
+
+/// Sets the taskbars whose rects extend the hover area while they are shown
+pub fn set_taskbars(hwnds: &[HWND]) {
+    if let Ok(mut taskbars) = TASKBARS.lock() {
+        *taskbars = hwnds.iter().map(|&hwnd| hwnd as usize).collect();
+    }
+}
+
+/// Returns the tick count of the last mouse, pen or touch event, if the hook is installed
+pub fn last_input_time() -> Option<u32> {
+    if HOOK_HANDLE.load(Ordering::SeqCst).is_null() {
+        return None;
+    }
+    Some(LAST_INPUT_TIME.load(Ordering::SeqCst))
+}
+
+/// Install the mouse hook
+pub fn install(notify_hwnd: HWND) -> Result<(), Error> {
+    unsafe {
+        NOTIFY_HWND.store(notify_hwnd as *mut _, Ordering::SeqCst);
+
+        let hook = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook_proc), null_mut(), 0);
+        if hook.is_null() {
+            return Err(Error::last("Failed to install mouse hook"));
+        }
+
+        HOOK_HANDLE.store(hook, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Uninstall the mouse hook
+pub fn uninstall() {
+    unsafe {
+        let hook = HOOK_HANDLE.swap(null_mut(), Ordering::SeqCst);
+        if !hook.is_null() {
+            UnhookWindowsHookEx(hook);
+        }
+    }
+    // A reinstalled hook reports the next edge hover afresh
+    HOVERING.store(false, Ordering::SeqCst);
+    HOVER_MONITOR.store(null_mut(), Ordering::SeqCst);
+}
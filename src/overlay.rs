@@ -0,0 +1,143 @@
+//! Debug overlay module
+//!
+//! A small always-on-top window in the corner of the primary monitor showing
+//! the hider's live state. It never takes focus and lets clicks through, so
+//! the foreground and fullscreen checks behind the reveal logic never see it.
+
+use std::mem::size_of;
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{
+    BeginPaint, DrawTextW, EndPaint, GetStockObject, InvalidateRect, SelectObject, SetBkMode,
+    SetTextColor, BLACK_BRUSH, DEFAULT_GUI_FONT, DT_LEFT, DT_NOPREFIX, HBRUSH, PAINTSTRUCT,
+    TRANSPARENT,
+};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, GetClientRect, RegisterClassExW,
+    SetLayeredWindowAttributes, ShowWindow, LWA_ALPHA, SW_SHOWNOACTIVATE, WM_PAINT,
+    WNDCLASSEXW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST,
+    WS_EX_TRANSPARENT, WS_POPUP,
+};
+
+const OVERLAY_ALPHA: u8 = 200;
+const OVERLAY_RECT: (i32, i32, i32, i32) = (8, 8, 420, 64);
+/// Gap between the window edge and the text
+const TEXT_MARGIN: i32 = 6;
+
+static OVERLAY_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static OVERLAY_TEXT: Mutex<String> = Mutex::new(String::new());
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Returns true if the overlay is open
+pub fn is_shown() -> bool {
+    !OVERLAY_HWND.load(Ordering::SeqCst).is_null()
+}
+
+/// Opens the overlay, unless it is already open
+pub fn show(owner: HWND) {
+    if is_shown() {
+        return;
+    }
+    unsafe {
+        let instance = GetModuleHandleW(null());
+        let class_name = wide_string("TaskbarHiderOverlay");
+        let wc = WNDCLASSEXW {
+            cbSize: size_of::<WNDCLASSEXW>() as u32,
+            style: 0,
+            lpfnWndProc: Some(overlay_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: instance,
+            hIcon: null_mut(),
+            hCursor: null_mut(),
+            hbrBackground: GetStockObject(BLACK_BRUSH) as HBRUSH,
+            lpszMenuName: null(),
+            lpszClassName: class_name.as_ptr(),
+            hIconSm: null_mut(),
+        };
+        // Fails harmlessly when the class is already registered
+        RegisterClassExW(&wc);
+
+        let (x, y, width, height) = OVERLAY_RECT;
+        let hwnd = CreateWindowExW(
+            WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE | WS_EX_LAYERED | WS_EX_TRANSPARENT,
+            class_name.as_ptr(),
+            null(),
+            WS_POPUP,
+            x,
+            y,
+            width,
+            height,
+            owner,
+            null_mut(),
+            instance,
+            null(),
+        );
+        if hwnd.is_null() {
+            return;
+        }
+        SetLayeredWindowAttributes(hwnd, 0, OVERLAY_ALPHA, LWA_ALPHA);
+        OVERLAY_HWND.store(hwnd, Ordering::SeqCst);
+        ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+    }
+}
+
+/// Closes the overlay if it is open
+pub fn hide() {
+    let hwnd = OVERLAY_HWND.swap(null_mut(), Ordering::SeqCst);
+    if !hwnd.is_null() {
+        unsafe {
+            DestroyWindow(hwnd);
+        }
+    }
+}
+
+/// Replaces the text shown in the overlay
+pub fn set_text(text: &str) {
+    if let Ok(mut overlay_text) = OVERLAY_TEXT.lock() {
+        if *overlay_text == text {
+            return;
+        }
+        *overlay_text = text.to_string();
+    }
+    let hwnd = OVERLAY_HWND.load(Ordering::SeqCst);
+    if !hwnd.is_null() {
+        unsafe {
+            InvalidateRect(hwnd, null(), 1);
+        }
+    }
+}
+
+unsafe extern "system" fn overlay_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_PAINT {
+        let mut paint: PAINTSTRUCT = std::mem::zeroed();
+        let hdc = BeginPaint(hwnd, &mut paint);
+        SelectObject(hdc, GetStockObject(DEFAULT_GUI_FONT));
+        SetTextColor(hdc, 0x00FF_FFFF);
+        SetBkMode(hdc, TRANSPARENT as i32);
+
+        let mut rect: RECT = std::mem::zeroed();
+        GetClientRect(hwnd, &mut rect);
+        rect.left += TEXT_MARGIN;
+        rect.top += TEXT_MARGIN;
+        let text = OVERLAY_TEXT.lock().map(|text| text.clone()).unwrap_or_default();
+        let mut text: Vec<u16> = text.encode_utf16().collect();
+        DrawTextW(hdc, text.as_mut_ptr(), text.len() as i32, &mut rect, DT_LEFT | DT_NOPREFIX);
+        EndPaint(hwnd, &paint);
+        return 0;
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
@@ -0,0 +1,85 @@
+//! Power notification module
+//!
+//! Subscribes the main window to battery level and power source changes, so
+//! the taskbar can be revealed briefly when the battery runs low. Windows
+//! delivers them as `WM_POWERBROADCAST` with `PBT_POWERSETTINGCHANGE`.
+
+use std::sync::atomic::{AtomicIsize, Ordering};
+use windows_sys::core::GUID;
+use windows_sys::Win32::Foundation::{HWND, LPARAM};
+use windows_sys::Win32::System::Power::{
+    RegisterPowerSettingNotification, UnregisterPowerSettingNotification, HPOWERNOTIFY,
+    POWERBROADCAST_SETTING,
+};
+use windows_sys::Win32::System::SystemServices::{
+    GUID_ACDC_POWER_SOURCE, GUID_BATTERY_PERCENTAGE_REMAINING,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::DEVICE_NOTIFY_WINDOW_HANDLE;
+use taskbar_hider::winapi::Error;
+
+static BATTERY_NOTIFY: AtomicIsize = AtomicIsize::new(0);
+static SOURCE_NOTIFY: AtomicIsize = AtomicIsize::new(0);
+
+/// A power setting change reported to the main window
+pub enum PowerChange {
+    /// Remaining battery charge, in percent
+    BatteryPercent(u32),
+    /// Whether the machine now runs on battery
+    OnBattery(bool),
+}
+
+/// Registers the window for battery level and power source changes
+pub fn register(hwnd: HWND) -> Result<(), Error> {
+    let battery = subscribe(hwnd, &GUID_BATTERY_PERCENTAGE_REMAINING)?;
+    BATTERY_NOTIFY.store(battery, Ordering::SeqCst);
+    let source = subscribe(hwnd, &GUID_ACDC_POWER_SOURCE)?;
+    SOURCE_NOTIFY.store(source, Ordering::SeqCst);
+    Ok(())
+}
+
+fn subscribe(hwnd: HWND, setting: &GUID) -> Result<HPOWERNOTIFY, Error> {
+    let handle = unsafe {
+        RegisterPowerSettingNotification(hwnd as _, setting, DEVICE_NOTIFY_WINDOW_HANDLE)
+    };
+    if handle == 0 {
+        return Err(Error::last("Failed to register for power notifications"));
+    }
+    Ok(handle)
+}
+
+/// Stops the battery level and power source notifications
+pub fn unregister() {
+    for notify in [&BATTERY_NOTIFY, &SOURCE_NOTIFY] {
+        let handle = notify.swap(0, Ordering::SeqCst);
+        if handle != 0 {
+            unsafe {
+                UnregisterPowerSettingNotification(handle);
+            }
+        }
+    }
+}
+
+/// Reads the setting a `PBT_POWERSETTINGCHANGE` broadcast points to
+///
+/// # Safety
+/// `lparam` must be the `POWERBROADCAST_SETTING` of that broadcast.
+pub unsafe fn read_change(lparam: LPARAM) -> Option<PowerChange> {
+    let setting = &*(lparam as *const POWERBROADCAST_SETTING);
+    if setting.DataLength < 4 {
+        return None;
+    }
+    let value = std::ptr::read_unaligned(setting.Data.as_ptr() as *const u32);
+    if same_guid(&setting.PowerSetting, &GUID_BATTERY_PERCENTAGE_REMAINING) {
+        Some(PowerChange::BatteryPercent(value))
+    } else if same_guid(&setting.PowerSetting, &GUID_ACDC_POWER_SOURCE) {
+        // 0 is AC power, 1 the battery and 2 a short-term source like a UPS
+        Some(PowerChange::OnBattery(value != 0))
+    } else {
+        None
+    }
+}
+
+/// windows-sys GUIDs don't implement `PartialEq`
+fn same_guid(a: &GUID, b: &GUID) -> bool {
+    a.data1 == b.data1 && a.data2 == b.data2 && a.data3 == b.data3 && a.data4 == b.data4
+}
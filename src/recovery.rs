@@ -0,0 +1,87 @@
+//! Recovery module
+//!
+//! Leaves a marker file while the taskbar is hidden so that, if the process
+//! is killed before `cleanup` runs, the next launch can put the taskbar back.
+//!
+//! Covered: End Task in Task Manager, `taskkill /f`, crashes and power loss;
+//! the taskbar is restored as soon as the hider starts again, or right away
+//! by the external watchdog if it is on. Without the watchdog the taskbar
+//! stays hidden until then, since a killed process cannot run any code of
+//! its own.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use taskbar_hider::taskbar;
+use taskbar_hider::winapi::SystemApi;
+
+const MARKER_FILE_NAME: &str = "running.marker";
+
+/// Returns the path of the marker file
+fn marker_path() -> Option<PathBuf> {
+    let local_appdata = std::env::var_os("LOCALAPPDATA")?;
+    Some(PathBuf::from(local_appdata).join("clean-taskbar").join(MARKER_FILE_NAME))
+}
+
+/// Restores the taskbar if a previous run exited without cleaning up
+///
+/// Must only be called while holding the single-instance mutex, or once the
+/// instance that wrote the marker is gone, otherwise a running instance's
+/// marker would look stale.
+pub fn restore_if_stale() -> bool {
+    restore_from_marker(true)
+}
+
+/// Restores the taskbar of a running instance that stopped responding, leaving its
+/// marker for its own cleanup
+pub fn restore_for_hung_instance() -> bool {
+    restore_from_marker(false)
+}
+
+/// Returns when the marker was last written, if it exists
+pub fn marker_written_at() -> Option<SystemTime> {
+    std::fs::metadata(marker_path()?).ok()?.modified().ok()
+}
+
+/// Shows the taskbars and undoes the auto-hide the marker says we turned on
+fn restore_from_marker(remove: bool) -> bool {
+    let Some(path) = marker_path() else {
+        return false;
+    };
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return false;
+    };
+
+    let api = SystemApi;
+    let hwnds = taskbar::find_all_taskbars(&api);
+    if contents.trim() == "autohide=1" {
+        for &hwnd in &hwnds {
+            taskbar::set_autohide_mode(&api, hwnd, false);
+        }
+    }
+    taskbar::show_taskbar(&api, &hwnds);
+    taskbar::restore_opacity(&api, &hwnds);
+    if remove {
+        let _ = std::fs::remove_file(&path);
+    }
+    true
+}
+
+/// Records that the taskbar is being managed, and whether we enabled auto-hide
+///
+/// Rewritten on a timer while the external watchdog runs, as a heartbeat.
+pub fn write_marker(autohide_set: bool) {
+    let Some(path) = marker_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(&path, if autohide_set { "autohide=1" } else { "autohide=0" });
+}
+
+/// Removes the marker after a clean shutdown
+pub fn clear_marker() {
+    if let Some(path) = marker_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
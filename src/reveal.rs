@@ -0,0 +1,663 @@
+//! Reveal state module
+//!
+//! Tracks the triggers that keep the taskbar up and decides when it is shown
+//! or hidden. The current time is passed in, so the rules hold without timers.
+
+use crate::config::{self, RevealMode};
+
+/// What to do with the taskbar after an evaluation
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Decision {
+    /// Leave the taskbar as it is
+    Unchanged,
+    /// Show the taskbar; only the hovered monitor's unless `all` is set
+    Show { all: bool },
+    /// Hide the taskbar
+    Hide,
+    /// Hide once the given number of milliseconds has passed
+    HideAfter(u64),
+}
+
+/// Reveal triggers, timings and the current visibility
+pub struct RevealState {
+    pub reveal_mode: RevealMode,
+    /// How long the reveal hotkey must be held before the taskbar appears
+    pub hold_threshold_ms: u64,
+    /// How long the taskbar stays up after a trigger is released
+    pub reveal_delay_ms: u64,
+    /// Shortest time the taskbar stays up once shown
+    pub min_visible_ms: u64,
+    /// Edge hover only reveals the taskbar on the hovered monitor
+    pub per_monitor_reveal: bool,
+    /// No release delay ever keeps the taskbar up
+    pub instant: bool,
+    pub win_key_held: bool,
+    /// The hotkey is down but hasn't been held for `hold_threshold_ms` yet
+    pub win_key_pending: bool,
+    pub edge_hover_active: bool,
+    pub paused: bool,
+    pub peek_active: bool,
+    /// The bare desktop is focused and `show_on_desktop` is on
+    pub desktop_focused: bool,
+    /// A watched system flyout such as the volume OSD is open
+    pub flyout_open: bool,
+    /// A watched flyout that keeps an already visible taskbar up is open
+    pub flyout_holding: bool,
+    /// Smart hide is on and the foreground window leaves the taskbar uncovered
+    pub taskbar_uncovered: bool,
+    /// `hide_only_in` is set and the foreground app isn't one of the listed ones
+    pub unlisted_app: bool,
+    /// There has been no input for `inactive_hide_ms`; only pausing keeps the bar up
+    pub user_away: bool,
+    /// An exclusive fullscreen app is in front and `block_exclusive_fullscreen` is on;
+    /// only pausing keeps the bar up
+    pub fullscreen_blocked: bool,
+    /// When the last trigger was released; zero outside a release delay
+    release_time: u64,
+    /// A press still short of the hold threshold took over a release delay
+    press_holds_delay: bool,
+    visible: bool,
+    shown_at: u64,
+}
+
+impl RevealState {
+    /// Creates the state for a hidden taskbar with the default timings
+    pub fn new() -> RevealState {
+        RevealState {
+            reveal_mode: RevealMode::Both,
+            hold_threshold_ms: 0,
+            reveal_delay_ms: config::DEFAULT_REVEAL_DELAY_MS,
+            min_visible_ms: config::DEFAULT_MIN_VISIBLE_MS,
+            per_monitor_reveal: false,
+            instant: false,
+            win_key_held: false,
+            win_key_pending: false,
+            edge_hover_active: false,
+            paused: false,
+            peek_active: false,
+            desktop_focused: false,
+            flyout_open: false,
+            flyout_holding: false,
+            taskbar_uncovered: false,
+            unlisted_app: false,
+            user_away: false,
+            fullscreen_blocked: false,
+            release_time: 0,
+            press_holds_delay: false,
+            visible: false,
+            shown_at: 0,
+        }
+    }
+
+    /// Takes over the reveal settings from the config
+    pub fn apply_config(&mut self, config: &config::Config) {
+        self.reveal_mode = config.reveal_mode;
+        self.hold_threshold_ms = config.hold_threshold_ms;
+        self.reveal_delay_ms = config.reveal_delay_ms;
+        self.min_visible_ms = config.min_visible_ms;
+        self.per_monitor_reveal = config.per_monitor_reveal;
+        self.instant = config.instant;
+    }
+
+    /// Takes over the overrides of a `[monitor.*]` section, for a monitor revealed
+    /// on its own
+    pub fn apply_section(&mut self, section: &config::MonitorSection) {
+        self.reveal_mode = section.reveal_mode.unwrap_or(self.reveal_mode);
+        self.reveal_delay_ms = section.reveal_delay_ms.unwrap_or(self.reveal_delay_ms);
+        self.min_visible_ms = section.min_visible_ms.unwrap_or(self.min_visible_ms);
+        // The state only covers the one monitor
+        self.per_monitor_reveal = false;
+    }
+
+    /// Copies the triggers that aren't tied to a monitor from the shared state
+    ///
+    /// A released hotkey or a closed flyout starts this state's own release delay,
+    /// as it does for the shared one.
+    pub fn follow_shared(&mut self, shared: &RevealState, now: u64) {
+        let flyout = |state: &RevealState| state.flyout_open || state.flyout_holding;
+        let released = (self.win_key_held && !shared.win_key_held)
+            || (flyout(self) && !flyout(shared));
+        let pressed =
+            (!self.win_key_held && shared.win_key_held) || (!flyout(self) && flyout(shared));
+
+        self.win_key_held = shared.win_key_held;
+        self.paused = shared.paused;
+        self.peek_active = shared.peek_active;
+        self.desktop_focused = shared.desktop_focused;
+        self.flyout_open = shared.flyout_open;
+        self.flyout_holding = shared.flyout_holding;
+        self.taskbar_uncovered = shared.taskbar_uncovered;
+        self.unlisted_app = shared.unlisted_app;
+        self.user_away = shared.user_away;
+        self.fullscreen_blocked = shared.fullscreen_blocked;
+
+        if pressed {
+            self.cancel_release_delay();
+        } else if released && self.visible {
+            self.start_release_delay(now);
+        }
+    }
+
+    /// Returns how long a running release delay still keeps the taskbar up
+    pub fn delay_remaining(&self, now: u64) -> Option<u64> {
+        self.is_within_delay_period(now).then(|| self.release_time + self.reveal_delay_ms - now)
+    }
+
+    /// Returns true if the taskbar is currently shown
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Records that the taskbar was shown
+    pub fn shown(&mut self, now: u64) {
+        self.visible = true;
+        self.shown_at = now;
+    }
+
+    /// Records that the taskbar was hidden
+    pub fn hidden(&mut self) {
+        self.visible = false;
+        self.release_time = 0;
+    }
+
+    /// Handles the reveal hotkey going down
+    ///
+    /// Returns true if the hold threshold has to pass before the taskbar is revealed.
+    /// A press during a release delay keeps the taskbar up until it is released.
+    pub fn press(&mut self) -> bool {
+        if self.hold_threshold_ms == 0 {
+            self.hold();
+            return false;
+        }
+        if self.visible && self.release_time != 0 {
+            self.cancel_release_delay();
+            self.press_holds_delay = true;
+        }
+        self.win_key_pending = true;
+        true
+    }
+
+    /// Handles the hold threshold passing; returns true if the hotkey is still down
+    pub fn hold_elapsed(&mut self) -> bool {
+        if !std::mem::take(&mut self.win_key_pending) {
+            return false;
+        }
+        self.hold();
+        true
+    }
+
+    /// Marks the hotkey as held, which ends any release delay
+    pub fn hold(&mut self) {
+        self.win_key_held = true;
+        self.cancel_release_delay();
+    }
+
+    /// Handles the reveal hotkey going up
+    ///
+    /// Returns true if a release delay started. A press released before the
+    /// hold threshold never revealed the taskbar, so it only restarts a release
+    /// delay it took over.
+    pub fn release(&mut self, now: u64) -> bool {
+        if std::mem::take(&mut self.win_key_pending) {
+            if !std::mem::take(&mut self.press_holds_delay) {
+                return false;
+            }
+            self.start_release_delay(now);
+            return true;
+        }
+        self.win_key_held = false;
+        self.start_release_delay(now);
+        true
+    }
+
+    /// Keeps the taskbar up for the reveal delay from `now`
+    pub fn start_release_delay(&mut self, now: u64) {
+        self.release_time = now;
+    }
+
+    /// Ends a running release delay once a trigger is active again
+    pub fn cancel_release_delay(&mut self) {
+        self.release_time = 0;
+        self.press_holds_delay = false;
+    }
+
+    /// Returns true while a released trigger still keeps the taskbar up
+    pub fn is_within_delay_period(&self, now: u64) -> bool {
+        if self.instant || self.release_time == 0 {
+            return false;
+        }
+
+        now < self.release_time + self.reveal_delay_ms
+    }
+
+    /// Returns true if a trigger that reveals every monitor's taskbar is active
+    pub fn reveals_all(&self) -> bool {
+        let winkey = self.reveal_mode.uses_winkey() && self.win_key_held;
+        let window_trigger = self.desktop_focused
+            || self.flyout_open
+            || self.taskbar_uncovered
+            || self.unlisted_app;
+        self.paused || self.peek_active || window_trigger || winkey
+    }
+
+    /// Decides whether the taskbar should be shown or hidden at `now`
+    ///
+    /// While paused the taskbar is simply left visible. Over a fullscreen app
+    /// only an explicit hotkey hold may reveal it, and once the user is away or
+    /// a blocked exclusive fullscreen app is in front nothing but pausing does.
+    pub fn evaluate(&self, now: u64, over_fullscreen: bool) -> Decision {
+        let edge_hover =
+            self.reveal_mode.uses_edge_hover() && self.edge_hover_active && !over_fullscreen;
+        let reveal_all = self.reveals_all();
+        let delay = self.press_holds_delay || self.is_within_delay_period(now);
+        let held = self.flyout_holding && self.visible;
+        let suppressed = self.user_away || self.fullscreen_blocked;
+        let should_show =
+            (reveal_all || edge_hover || delay || held) && (!suppressed || self.paused);
+
+        match (should_show, self.visible) {
+            (true, false) => Decision::Show { all: reveal_all || !self.per_monitor_reveal },
+            // A trigger for every monitor, or a hover onto another monitor, arrived
+            // while only some bars may be up
+            (true, true) if reveal_all || edge_hover => {
+                Decision::Show { all: reveal_all || !self.per_monitor_reveal }
+            }
+            (false, true) => {
+                // A quick tap keeps the bar up briefly instead of flickering it
+                let visible_until = self.shown_at + self.min_visible_ms;
+                if now < visible_until {
+                    Decision::HideAfter(visible_until - now)
+                } else {
+                    Decision::Hide
+                }
+            }
+            _ => Decision::Unchanged,
+        }
+    }
+}
+
+/// Spots the user coming back after being away, from the time since the last input
+pub struct IdleWatch {
+    /// Time without input that counts as being away; zero turns the watch off
+    threshold_ms: u64,
+    away: bool,
+}
+
+impl IdleWatch {
+    /// Creates a watch for absences of at least `threshold_ms`
+    pub fn new(threshold_ms: u64) -> IdleWatch {
+        IdleWatch { threshold_ms, away: false }
+    }
+
+    /// Takes the time since the last input, returning true on the first input after
+    /// an absence of at least the threshold
+    pub fn update(&mut self, idle_ms: u64) -> bool {
+        if self.threshold_ms == 0 {
+            return false;
+        }
+        if idle_ms >= self.threshold_ms {
+            self.away = true;
+            return false;
+        }
+        std::mem::replace(&mut self.away, false)
+    }
+}
+
+/// Spots the battery running low while the machine is unplugged
+pub struct LowBatteryWatch {
+    /// Charge, in percent, at or below which the battery counts as low; zero turns
+    /// the watch off
+    threshold: u32,
+    percent: u32,
+    on_battery: bool,
+    /// The current low stretch was already reported
+    alerted: bool,
+}
+
+impl LowBatteryWatch {
+    /// Creates a watch for a charge of at most `threshold` percent
+    pub fn new(threshold: u32) -> LowBatteryWatch {
+        LowBatteryWatch { threshold, percent: 100, on_battery: false, alerted: false }
+    }
+
+    /// Takes the remaining charge, returning true once the battery turns low
+    pub fn set_percent(&mut self, percent: u32) -> bool {
+        self.percent = percent;
+        self.check()
+    }
+
+    /// Takes the power source, returning true once the battery turns low
+    pub fn set_on_battery(&mut self, on_battery: bool) -> bool {
+        self.on_battery = on_battery;
+        self.check()
+    }
+
+    /// Returns true while unplugged with the charge at or below the threshold
+    pub fn is_low(&self) -> bool {
+        self.threshold > 0 && self.on_battery && self.percent <= self.threshold
+    }
+
+    /// Reports a low battery once, until charging or a higher charge ends it
+    fn check(&mut self) -> bool {
+        if !self.is_low() {
+            self.alerted = false;
+            return false;
+        }
+        !std::mem::replace(&mut self.alerted, true)
+    }
+}
+
+impl Default for RevealState {
+    fn default() -> Self {
+        RevealState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> RevealState {
+        let mut state = RevealState::new();
+        state.reveal_delay_ms = 400;
+        state.min_visible_ms = 150;
+        state
+    }
+
+    #[test]
+    fn press_reveals_at_once_without_a_threshold() {
+        let mut state = state();
+        assert!(!state.press());
+        assert!(state.win_key_held);
+        assert_eq!(state.evaluate(1000, false), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn press_waits_for_the_hold_threshold() {
+        let mut state = state();
+        state.hold_threshold_ms = 200;
+        assert!(state.press());
+        assert_eq!(state.evaluate(1000, false), Decision::Unchanged);
+        assert!(state.hold_elapsed());
+        assert!(state.win_key_held);
+        assert_eq!(state.evaluate(1200, false), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn release_before_the_threshold_never_reveals() {
+        let mut state = state();
+        state.hold_threshold_ms = 200;
+        state.press();
+        assert!(!state.release(1100));
+        assert!(!state.hold_elapsed());
+        assert!(!state.is_within_delay_period(1100));
+        assert_eq!(state.evaluate(1200, false), Decision::Unchanged);
+    }
+
+    #[test]
+    fn release_keeps_the_bar_up_for_the_reveal_delay() {
+        let mut state = state();
+        state.press();
+        state.shown(1000);
+        assert!(state.release(2000));
+        assert!(state.is_within_delay_period(2000));
+        assert!(state.is_within_delay_period(2399));
+        assert!(!state.is_within_delay_period(2400));
+        assert_eq!(state.evaluate(2200, false), Decision::Unchanged);
+        assert_eq!(state.evaluate(2400, false), Decision::Hide);
+    }
+
+    #[test]
+    fn a_new_trigger_cancels_the_release_delay() {
+        let mut state = state();
+        state.press();
+        state.release(2000);
+        state.hold();
+        assert!(!state.is_within_delay_period(2100));
+    }
+
+    #[test]
+    fn a_press_short_of_the_threshold_holds_the_release_delay() {
+        let mut state = state();
+        state.hold_threshold_ms = 200;
+        state.press();
+        state.hold_elapsed();
+        state.shown(1000);
+        assert!(state.release(2000));
+
+        assert!(state.press());
+        assert!(!state.is_within_delay_period(2100));
+        assert_eq!(state.evaluate(2500, false), Decision::Unchanged);
+
+        // Released before the threshold: the delay starts over
+        assert!(state.release(2550));
+        assert!(state.is_within_delay_period(2900));
+        assert_eq!(state.evaluate(2900, false), Decision::Unchanged);
+        assert_eq!(state.evaluate(2950, false), Decision::Hide);
+    }
+
+    #[test]
+    fn a_press_held_past_the_threshold_takes_over_the_release_delay() {
+        let mut state = state();
+        state.hold_threshold_ms = 200;
+        state.press();
+        state.hold_elapsed();
+        state.shown(1000);
+        state.release(2000);
+
+        state.press();
+        assert!(state.hold_elapsed());
+        assert!(state.win_key_held);
+        assert_eq!(state.evaluate(3000, false), Decision::Show { all: true });
+        assert!(state.release(3000));
+        assert_eq!(state.evaluate(3400, false), Decision::Hide);
+    }
+
+    #[test]
+    fn a_press_after_the_bar_hid_does_not_hold_it() {
+        let mut state = state();
+        state.hold_threshold_ms = 200;
+        state.press();
+        state.hold_elapsed();
+        state.shown(1000);
+        state.release(2000);
+        state.hidden();
+
+        state.press();
+        assert!(!state.release(3050));
+        assert_eq!(state.evaluate(3100, false), Decision::Unchanged);
+    }
+
+    #[test]
+    fn instant_hides_as_soon_as_the_hotkey_is_released() {
+        let mut state = state();
+        state.instant = true;
+        state.min_visible_ms = 0;
+        state.press();
+        state.shown(1000);
+        assert!(state.release(1010));
+        assert!(!state.is_within_delay_period(1010));
+        assert_eq!(state.evaluate(1010, false), Decision::Hide);
+    }
+
+    #[test]
+    fn hide_waits_for_the_minimum_visible_time() {
+        let mut state = state();
+        state.reveal_delay_ms = 0;
+        state.press();
+        state.shown(1000);
+        state.release(1050);
+        assert_eq!(state.evaluate(1050, false), Decision::HideAfter(100));
+        assert_eq!(state.evaluate(1150, false), Decision::Hide);
+        state.hidden();
+        assert_eq!(state.evaluate(1200, false), Decision::Unchanged);
+    }
+
+    #[test]
+    fn edge_hover_is_suppressed_over_fullscreen_apps() {
+        let mut state = state();
+        state.edge_hover_active = true;
+        assert_eq!(state.evaluate(1000, true), Decision::Unchanged);
+        assert_eq!(state.evaluate(1000, false), Decision::Show { all: true });
+        state.press();
+        assert_eq!(state.evaluate(1000, true), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn per_monitor_reveal_widens_for_a_hotkey_hold() {
+        let mut state = state();
+        state.per_monitor_reveal = true;
+        state.edge_hover_active = true;
+        assert_eq!(state.evaluate(1000, false), Decision::Show { all: false });
+        state.shown(1000);
+        // A continuing hover still asks for the hovered monitor's bar, which may be new
+        assert_eq!(state.evaluate(1100, false), Decision::Show { all: false });
+        state.press();
+        assert_eq!(state.evaluate(1200, false), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn visible_bar_stays_unchanged_without_a_trigger_for_more() {
+        let mut state = state();
+        state.press();
+        state.shown(1000);
+        state.release(1100);
+        assert_eq!(state.evaluate(1200, false), Decision::Unchanged);
+    }
+
+    #[test]
+    fn reveal_mode_ignores_disabled_triggers() {
+        let mut state = state();
+        state.reveal_mode = RevealMode::EdgeHover;
+        state.press();
+        assert_eq!(state.evaluate(1000, false), Decision::Unchanged);
+        state.reveal_mode = RevealMode::WinKey;
+        state.win_key_held = false;
+        state.edge_hover_active = true;
+        assert_eq!(state.evaluate(1000, false), Decision::Unchanged);
+    }
+
+    #[test]
+    fn paused_keeps_the_bar_up() {
+        let mut state = state();
+        state.paused = true;
+        assert_eq!(state.evaluate(2000, true), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn an_open_flyout_reveals_every_bar_until_the_delay_after_it_closes() {
+        let mut state = state();
+        state.per_monitor_reveal = true;
+        state.flyout_open = true;
+        assert_eq!(state.evaluate(1000, false), Decision::Show { all: true });
+        state.shown(1000);
+        state.flyout_open = false;
+        state.start_release_delay(2000);
+        assert_eq!(state.evaluate(2300, false), Decision::Unchanged);
+        assert_eq!(state.evaluate(2400, false), Decision::Hide);
+    }
+
+    #[test]
+    fn a_holding_flyout_only_keeps_a_visible_bar_up() {
+        let mut state = state();
+        state.flyout_holding = true;
+        assert_eq!(state.evaluate(1000, false), Decision::Unchanged);
+        state.press();
+        state.shown(1000);
+        state.release(1100);
+        assert_eq!(state.evaluate(2000, false), Decision::Unchanged);
+        state.flyout_holding = false;
+        assert_eq!(state.evaluate(2000, false), Decision::Hide);
+    }
+
+    #[test]
+    fn an_unlisted_app_keeps_every_bar_up_and_a_listed_one_hides_it() {
+        let mut state = state();
+        state.unlisted_app = true;
+        assert_eq!(state.evaluate(1000, false), Decision::Show { all: true });
+        state.shown(1000);
+        state.unlisted_app = false;
+        assert_eq!(state.evaluate(2000, false), Decision::Hide);
+    }
+
+    #[test]
+    fn an_away_user_hides_a_hovered_bar_but_not_a_paused_one() {
+        let mut state = state();
+        state.edge_hover_active = true;
+        state.shown(1000);
+        state.user_away = true;
+        assert_eq!(state.evaluate(2000, false), Decision::Hide);
+        state.paused = true;
+        assert_eq!(state.evaluate(2000, false), Decision::Show { all: true });
+        state.paused = false;
+        state.user_away = false;
+        assert_eq!(state.evaluate(2000, false), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn blocked_exclusive_fullscreen_hides_even_a_held_bar() {
+        let mut state = state();
+        state.win_key_held = true;
+        state.shown(1000);
+        state.fullscreen_blocked = true;
+        assert_eq!(state.evaluate(2000, true), Decision::Hide);
+        state.paused = true;
+        assert_eq!(state.evaluate(2000, true), Decision::Show { all: true });
+    }
+
+    #[test]
+    fn low_battery_watch_fires_once_per_low_stretch() {
+        let mut battery = LowBatteryWatch::new(10);
+        assert!(!battery.set_percent(8));
+        assert!(battery.set_on_battery(true));
+        assert!(!battery.set_percent(5));
+        assert!(!battery.set_on_battery(false));
+        assert!(!battery.is_low());
+        assert!(battery.set_on_battery(true));
+        assert!(!battery.set_percent(40));
+        assert!(battery.set_percent(10));
+
+        let mut off = LowBatteryWatch::new(0);
+        assert!(!off.set_on_battery(true));
+        assert!(!off.set_percent(1));
+    }
+
+    #[test]
+    fn idle_watch_fires_once_when_input_resumes_after_the_threshold() {
+        let mut idle = IdleWatch::new(60_000);
+        assert!(!idle.update(1_000));
+        assert!(!idle.update(60_000));
+        assert!(!idle.update(90_000));
+        assert!(idle.update(200));
+        assert!(!idle.update(700));
+
+        let mut off = IdleWatch::new(0);
+        assert!(!off.update(90_000));
+        assert!(!off.update(200));
+    }
+
+    #[test]
+    fn a_monitor_state_follows_the_shared_triggers_with_its_own_delay() {
+        let mut shared = state();
+        let mut monitor = state();
+        monitor.apply_section(&config::MonitorSection {
+            device: "DISPLAY2".to_string(),
+            reveal_mode: Some(RevealMode::WinKey),
+            reveal_delay_ms: Some(1000),
+            min_visible_ms: None,
+        });
+        assert_eq!(monitor.reveal_mode, RevealMode::WinKey);
+        assert_eq!(monitor.min_visible_ms, 150);
+
+        shared.hold();
+        monitor.follow_shared(&shared, 1000);
+        assert_eq!(monitor.evaluate(1000, false), Decision::Show { all: true });
+        monitor.shown(1000);
+
+        shared.release(1500);
+        monitor.follow_shared(&shared, 1500);
+        assert_eq!(monitor.delay_remaining(1600), Some(900));
+        assert_eq!(monitor.evaluate(2000, false), Decision::Unchanged);
+        assert_eq!(monitor.evaluate(2500, false), Decision::Hide);
+    }
+}
@@ -0,0 +1,246 @@
+//! Settings window module
+//!
+//! A small modal window for editing the reveal delay and reveal mode without
+//! touching the config file by hand.
+
+use std::mem::size_of;
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{GetStockObject, COLOR_BTNFACE, DEFAULT_GUI_FONT, HBRUSH};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetDlgItem, GetMessageW,
+    GetWindow, GetWindowTextW, IsDialogMessageW, IsWindow, MessageBoxW, PostMessageW,
+    PostQuitMessage, RegisterClassExW, SendMessageW, SetForegroundWindow, ShowWindow,
+    TranslateMessage, BS_DEFPUSHBUTTON, BS_PUSHBUTTON, CBS_DROPDOWNLIST, CB_ADDSTRING,
+    CB_GETCURSEL, CB_SETCURSEL, CW_USEDEFAULT, ES_NUMBER, GW_OWNER, HMENU, MB_ICONWARNING, MB_OK,
+    MSG, SW_SHOW, WM_COMMAND, WM_CREATE, WM_QUIT, WM_SETFONT, WM_USER, WNDCLASSEXW, WS_BORDER,
+    WS_CAPTION, WS_CHILD, WS_EX_DLGMODALFRAME, WS_SYSMENU, WS_TABSTOP, WS_VISIBLE,
+};
+use taskbar_hider::config::{self, RevealMode};
+
+/// Posted to the owner after new settings have been saved
+pub const WM_SETTINGS_CHANGED: u32 = WM_USER + 120;
+
+const IDC_OK: usize = 1;
+const IDC_CANCEL: usize = 2;
+const IDC_DELAY: i32 = 101;
+const IDC_MODE: i32 = 102;
+
+/// Reveal modes in combo box order, with their labels
+const MODES: [(RevealMode, &str); 3] = [
+    (RevealMode::WinKey, "Windows key"),
+    (RevealMode::EdgeHover, "Screen edge hover"),
+    (RevealMode::Both, "Windows key or edge hover"),
+];
+
+static SETTINGS_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Shows the settings window and blocks until it is closed
+///
+/// If the window is already open it is brought to the front instead.
+pub fn show(owner: HWND) {
+    unsafe {
+        let open = SETTINGS_HWND.load(Ordering::SeqCst);
+        if !open.is_null() {
+            SetForegroundWindow(open);
+            return;
+        }
+
+        let instance = GetModuleHandleW(null());
+        let class_name = wide_string("TaskbarHiderSettings");
+        let wc = WNDCLASSEXW {
+            cbSize: size_of::<WNDCLASSEXW>() as u32,
+            style: 0,
+            lpfnWndProc: Some(settings_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: instance,
+            hIcon: null_mut(),
+            hCursor: null_mut(),
+            hbrBackground: (COLOR_BTNFACE + 1) as HBRUSH,
+            lpszMenuName: null(),
+            lpszClassName: class_name.as_ptr(),
+            hIconSm: null_mut(),
+        };
+        // Fails harmlessly when the class is already registered
+        RegisterClassExW(&wc);
+
+        let title = wide_string("Taskbar Hider Settings");
+        let hwnd = CreateWindowExW(
+            WS_EX_DLGMODALFRAME,
+            class_name.as_ptr(),
+            title.as_ptr(),
+            WS_CAPTION | WS_SYSMENU,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            300,
+            170,
+            owner,
+            null_mut(),
+            instance,
+            null(),
+        );
+        if hwnd.is_null() {
+            return;
+        }
+        SETTINGS_HWND.store(hwnd, Ordering::SeqCst);
+
+        ShowWindow(hwnd, SW_SHOW);
+        SetForegroundWindow(hwnd);
+
+        // Local loop so Tab and Enter navigate the controls like a dialog
+        let mut msg: MSG = std::mem::zeroed();
+        while IsWindow(hwnd) != 0 {
+            if GetMessageW(&mut msg, null_mut(), 0, 0) <= 0 {
+                DestroyWindow(hwnd);
+                // Leave the quit request for the main loop
+                if msg.message == WM_QUIT {
+                    PostQuitMessage(msg.wParam as i32);
+                }
+                break;
+            }
+            if IsDialogMessageW(hwnd, &msg) == 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+        SETTINGS_HWND.store(null_mut(), Ordering::SeqCst);
+    }
+}
+
+/// Creates a child control using the default GUI font
+unsafe fn add_control(
+    parent: HWND,
+    class: &str,
+    text: &str,
+    style: u32,
+    (x, y, w, h): (i32, i32, i32, i32),
+    id: usize,
+) -> HWND {
+    let class = wide_string(class);
+    let text = wide_string(text);
+    let control = CreateWindowExW(
+        0,
+        class.as_ptr(),
+        text.as_ptr(),
+        WS_CHILD | WS_VISIBLE | style,
+        x,
+        y,
+        w,
+        h,
+        parent,
+        id as HMENU,
+        null_mut(),
+        null(),
+    );
+    SendMessageW(control, WM_SETFONT, GetStockObject(DEFAULT_GUI_FONT) as WPARAM, 1);
+    control
+}
+
+/// Creates the controls, filled in from the current config
+unsafe fn create_controls(hwnd: HWND) {
+    let config = config::load();
+
+    add_control(hwnd, "STATIC", "Reveal delay (ms):", 0, (12, 14, 110, 20), 0);
+    add_control(
+        hwnd,
+        "EDIT",
+        &config.reveal_delay_ms.to_string(),
+        WS_BORDER | WS_TABSTOP | ES_NUMBER as u32,
+        (130, 12, 140, 22),
+        IDC_DELAY as usize,
+    );
+
+    add_control(hwnd, "STATIC", "Reveal with:", 0, (12, 46, 110, 20), 0);
+    let combo = add_control(
+        hwnd,
+        "COMBOBOX",
+        "",
+        WS_TABSTOP | CBS_DROPDOWNLIST as u32,
+        (130, 44, 140, 100),
+        IDC_MODE as usize,
+    );
+    for (index, (mode, label)) in MODES.into_iter().enumerate() {
+        let label = wide_string(label);
+        SendMessageW(combo, CB_ADDSTRING, 0, label.as_ptr() as LPARAM);
+        if mode == config.reveal_mode {
+            SendMessageW(combo, CB_SETCURSEL, index, 0);
+        }
+    }
+
+    let ok_style = WS_TABSTOP | BS_DEFPUSHBUTTON as u32;
+    add_control(hwnd, "BUTTON", "OK", ok_style, (110, 90, 75, 25), IDC_OK);
+    let cancel_style = WS_TABSTOP | BS_PUSHBUTTON as u32;
+    add_control(hwnd, "BUTTON", "Cancel", cancel_style, (195, 90, 75, 25), IDC_CANCEL);
+}
+
+/// Saves the entered values, returning false if they are invalid
+unsafe fn save(hwnd: HWND) -> bool {
+    let mut buf = [0u16; 32];
+    let len = GetWindowTextW(GetDlgItem(hwnd, IDC_DELAY), buf.as_mut_ptr(), buf.len() as i32);
+    let text = String::from_utf16_lossy(&buf[..len.max(0) as usize]);
+    let Ok(delay_ms) = text.trim().parse::<u64>() else {
+        warn(hwnd, "The reveal delay must be a whole number of milliseconds.");
+        return false;
+    };
+
+    let index = SendMessageW(GetDlgItem(hwnd, IDC_MODE), CB_GETCURSEL, 0, 0);
+    let mode = MODES.get(index as usize).map_or(RevealMode::Both, |&(mode, _)| mode);
+
+    let values = [
+        ("reveal_delay_ms", delay_ms.to_string()),
+        ("reveal_mode", format!("\"{}\"", mode.as_str())),
+    ];
+    if let Err(e) = config::update(&values) {
+        warn(hwnd, e);
+        return false;
+    }
+    true
+}
+
+/// Shows a warning message box over the settings window
+unsafe fn warn(hwnd: HWND, text: &str) {
+    let message = wide_string(text);
+    let title = wide_string("Taskbar Hider Settings");
+    MessageBoxW(hwnd, message.as_ptr(), title.as_ptr(), MB_OK | MB_ICONWARNING);
+}
+
+unsafe extern "system" fn settings_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_CREATE => {
+            create_controls(hwnd);
+            return 0;
+        }
+
+        WM_COMMAND => match wparam & 0xFFFF {
+            IDC_OK => {
+                if save(hwnd) {
+                    PostMessageW(GetWindow(hwnd, GW_OWNER), WM_SETTINGS_CHANGED, 0, 0);
+                    DestroyWindow(hwnd);
+                }
+                return 0;
+            }
+            IDC_CANCEL => {
+                DestroyWindow(hwnd);
+                return 0;
+            }
+            _ => {}
+        },
+
+        _ => {}
+    }
+
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
@@ -0,0 +1,150 @@
+//! Shell window module
+//!
+//! Inspects the foreground window, e.g. to detect fullscreen applications, and
+//! whether they are borderless or exclusive, or the bare desktop.
+
+use std::mem::size_of;
+use windows_sys::Win32::Foundation::{CloseHandle, HWND, RECT, S_OK};
+use windows_sys::Win32::Graphics::Gdi::{
+    GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONULL,
+};
+use windows_sys::Win32::System::Threading::{
+    OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+    PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows_sys::Win32::UI::Shell::{SHQueryUserNotificationState, QUNS_RUNNING_D3D_FULL_SCREEN};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetClassNameW, GetDesktopWindow, GetForegroundWindow, GetShellWindow, GetWindowRect,
+    GetWindowThreadProcessId,
+};
+
+/// Window classes belonging to the desktop and shell, never treated as fullscreen
+const SHELL_WINDOW_CLASSES: &[&str] = &[
+    "Progman",
+    "WorkerW",
+    "Shell_TrayWnd",
+    "Shell_SecondaryTrayWnd",
+];
+
+/// Returns the class name of a window
+pub fn window_class(hwnd: HWND) -> String {
+    let mut buf = [0u16; 256];
+    let len = unsafe { GetClassNameW(hwnd, buf.as_mut_ptr(), buf.len() as i32) };
+    String::from_utf16_lossy(&buf[..len.max(0) as usize])
+}
+
+/// Returns the lowercase executable file name of the process owning a window
+pub fn owner_exe_name(hwnd: HWND) -> Option<String> {
+    unsafe {
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+
+        let mut buf = [0u16; 260];
+        let mut len = buf.len() as u32;
+        let ok =
+            QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buf.as_mut_ptr(), &mut len);
+        CloseHandle(process);
+        if ok == 0 {
+            return None;
+        }
+
+        let path = String::from_utf16_lossy(&buf[..len as usize]);
+        path.rsplit('\\').next().map(str::to_ascii_lowercase)
+    }
+}
+
+/// Returns true if the window is the desktop or part of the shell
+fn is_shell_window(hwnd: HWND) -> bool {
+    unsafe {
+        if hwnd == GetDesktopWindow() || hwnd == GetShellWindow() {
+            return true;
+        }
+    }
+    SHELL_WINDOW_CLASSES.contains(&window_class(hwnd).as_str())
+}
+
+/// Returns true if the window is the desktop itself
+pub fn is_desktop_window(hwnd: HWND) -> bool {
+    if hwnd == unsafe { GetShellWindow() } {
+        return true;
+    }
+    matches!(window_class(hwnd).as_str(), "Progman" | "WorkerW")
+}
+
+/// Returns true if the window is one of the taskbars
+pub fn is_taskbar_window(hwnd: HWND) -> bool {
+    matches!(window_class(hwnd).as_str(), "Shell_TrayWnd" | "Shell_SecondaryTrayWnd")
+}
+
+/// Returns true if the desktop is the foreground window
+pub fn is_desktop_foreground() -> bool {
+    let hwnd = unsafe { GetForegroundWindow() };
+    !hwnd.is_null() && is_desktop_window(hwnd)
+}
+
+/// Returns the rect of the foreground window, unless it is the desktop or part of the shell
+pub fn foreground_rect() -> Option<RECT> {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() || is_shell_window(hwnd) {
+            return None;
+        }
+        let mut rect: RECT = std::mem::zeroed();
+        (GetWindowRect(hwnd, &mut rect) != 0).then_some(rect)
+    }
+}
+
+/// How the foreground app fills its monitor
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Fullscreen {
+    /// A borderless window covering the monitor, composed by the desktop as usual
+    Borderless,
+    /// Exclusive Direct3D fullscreen, where the game owns the display
+    Exclusive,
+}
+
+/// Returns how the foreground window fills its monitor, or `None` if it doesn't
+pub fn foreground_fullscreen() -> Option<Fullscreen> {
+    if !is_foreground_fullscreen() {
+        return None;
+    }
+    let mut state = 0;
+    let exclusive = unsafe { SHQueryUserNotificationState(&mut state) } == S_OK
+        && state == QUNS_RUNNING_D3D_FULL_SCREEN;
+    Some(if exclusive { Fullscreen::Exclusive } else { Fullscreen::Borderless })
+}
+
+/// Returns true if the foreground window covers its entire monitor
+pub fn is_foreground_fullscreen() -> bool {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() || is_shell_window(hwnd) {
+            return false;
+        }
+
+        let mut window_rect: RECT = std::mem::zeroed();
+        if GetWindowRect(hwnd, &mut window_rect) == 0 {
+            return false;
+        }
+
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONULL);
+        if monitor.is_null() {
+            return false;
+        }
+        let mut info: MONITORINFO = std::mem::zeroed();
+        info.cbSize = size_of::<MONITORINFO>() as u32;
+        if GetMonitorInfoW(monitor, &mut info) == 0 {
+            return false;
+        }
+
+        let monitor_rect = info.rcMonitor;
+        window_rect.left <= monitor_rect.left
+            && window_rect.top <= monitor_rect.top
+            && window_rect.right >= monitor_rect.right
+            && window_rect.bottom >= monitor_rect.bottom
+    }
+}
@@ -1,12 +1,22 @@
 //! Taskbar visibility control module
 //!
 //! Handles finding taskbar windows by class name and controlling their visibility.
+//! `TaskbarController` owns the managed handles and the state needed to undo
+//! our changes on exit.
 
-use std::thread;
-use std::time::Duration;
-use windows_sys::Win32::Foundation::HWND;
-use windows_sys::Win32::UI::Shell::{ABM_SETSTATE, ABS_AUTOHIDE, APPBARDATA, SHAppBarMessage};
-use windows_sys::Win32::UI::WindowsAndMessaging::{FindWindowW, ShowWindow, SW_HIDE, SW_SHOWNOACTIVATE};
+use std::time::{Duration, Instant};
+use windows_sys::Win32::Foundation::{ERROR_SUCCESS, HWND, RECT};
+use windows_sys::Win32::Graphics::Gdi::HMONITOR;
+use windows_sys::Win32::System::Registry::{
+    RegGetValueW, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ,
+};
+use windows_sys::Win32::UI::Shell::{
+    ABE_BOTTOM, ABE_LEFT, ABE_RIGHT, ABE_TOP, ABM_GETSTATE, ABM_GETTASKBARPOS, ABM_SETSTATE,
+    ABS_AUTOHIDE, APPBARDATA,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{SW_HIDE, SW_SHOWNOACTIVATE, WS_EX_LAYERED};
+use crate::config::{HideStyle, DEFAULT_HIDE_ALPHA};
+use crate::winapi::{SystemApi, WinApi};
 
 /// Encodes a string as a null-terminated wide string
 fn wide_string(s: &str) -> Vec<u16> {
@@ -14,55 +24,954 @@ fn wide_string(s: &str) -> Vec<u16> {
 }
 
 /// Finds the primary taskbar window handle
-pub fn find_primary_taskbar() -> Option<HWND> {
-    unsafe {
-        let class_name = wide_string("Shell_TrayWnd");
-        let hwnd = FindWindowW(class_name.as_ptr(), std::ptr::null());
-        if hwnd.is_null() {
-            None
-        } else {
-            Some(hwnd)
+pub fn find_primary_taskbar(api: &impl WinApi) -> Option<HWND> {
+    let hwnd = api.find_window("Shell_TrayWnd", std::ptr::null_mut());
+    if hwnd.is_null() {
+        None
+    } else {
+        Some(hwnd)
+    }
+}
+
+/// Finds every taskbar window: the primary bar first, then one per secondary monitor
+pub fn find_all_taskbars(api: &impl WinApi) -> Vec<HWND> {
+    let mut hwnds = Vec::new();
+    for class in ["Shell_TrayWnd", "Shell_SecondaryTrayWnd"] {
+        let mut hwnd: HWND = std::ptr::null_mut();
+        loop {
+            hwnd = api.find_window(class, hwnd);
+            if hwnd.is_null() {
+                break;
+            }
+            hwnds.push(hwnd);
+        }
+    }
+    hwnds
+}
+
+/// Returns the taskbars shown on the given monitor
+pub fn on_monitor(api: &impl WinApi, hwnds: &[HWND], monitor: HMONITOR) -> Vec<HWND> {
+    hwnds.iter().copied().filter(|&hwnd| api.monitor_of(hwnd) == monitor).collect()
+}
+
+/// Reads the configured Winlogon shell, preferring the per-user override
+fn configured_shell() -> Option<String> {
+    let subkey = wide_string("Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon");
+    let value = wide_string("Shell");
+
+    for root in [HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE] {
+        let mut buf = [0u16; 260];
+        let mut size = size_of_val(&buf) as u32;
+        let status = unsafe {
+            RegGetValueW(
+                root,
+                subkey.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                buf.as_mut_ptr() as *mut _,
+                &mut size,
+            )
+        };
+        if status == ERROR_SUCCESS {
+            let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+            return Some(String::from_utf16_lossy(&buf[..len]));
+        }
+    }
+    None
+}
+
+/// Returns true unless a replacement for Explorer is configured as the shell
+pub fn is_standard_shell() -> bool {
+    match configured_shell() {
+        Some(shell) => {
+            let shell = shell.trim().trim_matches('"').to_ascii_lowercase();
+            shell.is_empty() || shell.trim_end_matches(".exe").ends_with("explorer")
         }
+        None => true,
     }
 }
 
+/// Returns an `APPBARDATA` addressed to the given taskbar
+fn app_bar_data(hwnd: HWND) -> APPBARDATA {
+    let mut abd: APPBARDATA = unsafe { std::mem::zeroed() };
+    abd.cbSize = std::mem::size_of::<APPBARDATA>() as u32;
+    abd.hWnd = hwnd;
+    abd
+}
+
 /// Sets the taskbar to auto-hide mode
-pub fn set_autohide_mode(hwnd: HWND, enable: bool) {
-    unsafe {
-        let mut abd: APPBARDATA = std::mem::zeroed();
-        abd.cbSize = std::mem::size_of::<APPBARDATA>() as u32;
-        abd.hWnd = hwnd;
-        abd.lParam = if enable { ABS_AUTOHIDE as isize } else { 0 };
-        SHAppBarMessage(ABM_SETSTATE, &mut abd);
+pub fn set_autohide_mode(api: &impl WinApi, hwnd: HWND, enable: bool) {
+    let mut abd = app_bar_data(hwnd);
+    abd.lParam = if enable { ABS_AUTOHIDE as isize } else { 0 };
+    api.app_bar_message(ABM_SETSTATE, &mut abd);
+}
+
+/// Returns true if the taskbar is in auto-hide mode
+pub fn is_autohide_enabled(api: &impl WinApi, hwnd: HWND) -> bool {
+    let mut abd = app_bar_data(hwnd);
+    api.app_bar_message(ABM_GETSTATE, &mut abd) as u32 & ABS_AUTOHIDE != 0
+}
+
+/// Screen edge a taskbar is docked to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum Edge {
+    Left = ABE_LEFT,
+    Top = ABE_TOP,
+    Right = ABE_RIGHT,
+    Bottom = ABE_BOTTOM,
+}
+
+impl Edge {
+    /// Converts an `ABE_*` value, treating anything unknown as the bottom edge
+    pub fn from_abe(value: u32) -> Edge {
+        match value {
+            ABE_LEFT => Edge::Left,
+            ABE_TOP => Edge::Top,
+            ABE_RIGHT => Edge::Right,
+            _ => Edge::Bottom,
+        }
+    }
+}
+
+/// Returns the screen edge the taskbar occupies, falling back to the bottom
+pub fn taskbar_edge(api: &impl WinApi, hwnd: HWND) -> Edge {
+    let mut abd = app_bar_data(hwnd);
+    if api.app_bar_message(ABM_GETTASKBARPOS, &mut abd) != 0 {
+        Edge::from_abe(abd.uEdge)
+    } else {
+        Edge::Bottom
+    }
+}
+
+/// Returns the screen edge the primary taskbar occupies
+pub fn primary_edge(api: &impl WinApi) -> Edge {
+    taskbar_edge(api, find_primary_taskbar(api).unwrap_or(std::ptr::null_mut()))
+}
+
+/// Hides the taskbar windows
+///
+/// Explorer sometimes re-shows the bar right after it is hidden, so callers
+/// should retry while `is_any_visible` reports it back.
+pub fn hide_taskbar(api: &impl WinApi, hwnds: &[HWND]) {
+    for &hwnd in hwnds {
+        api.show_window(hwnd, SW_HIDE);
+    }
+}
+
+/// Makes a taskbar layered if needed and sets its opacity
+///
+/// Returns true if the window only became layered just now.
+fn set_opacity(api: &impl WinApi, hwnd: HWND, alpha: u8) -> bool {
+    let ex_style = api.ex_style(hwnd);
+    let added = ex_style & WS_EX_LAYERED as isize == 0;
+    if added {
+        api.set_ex_style(hwnd, ex_style | WS_EX_LAYERED as isize);
+    }
+    api.set_layered_alpha(hwnd, alpha);
+    added
+}
+
+/// Puts any faded-out taskbar back to full opacity
+///
+/// Also used on the next launch after a crash, when it is no longer known
+/// which bars were faded, so it only touches bars that are partly transparent.
+pub fn restore_opacity(api: &impl WinApi, hwnds: &[HWND]) {
+    for &hwnd in hwnds {
+        let layered = api.ex_style(hwnd) & WS_EX_LAYERED as isize != 0;
+        if layered && api.layered_alpha(hwnd).is_some_and(|alpha| alpha < 255) {
+            api.set_layered_alpha(hwnd, 255);
+        }
+    }
+}
+
+/// Removes the `WS_EX_LAYERED` style from taskbars that still exist
+fn remove_layered_style(api: &impl WinApi, hwnds: &[HWND]) {
+    for &hwnd in hwnds {
+        if api.is_window(hwnd) {
+            let ex_style = api.ex_style(hwnd);
+            api.set_ex_style(hwnd, ex_style & !(WS_EX_LAYERED as isize));
+        }
+    }
+}
+
+/// Returns true if any of the taskbar windows is currently visible
+pub fn is_any_visible(api: &impl WinApi, hwnds: &[HWND]) -> bool {
+    hwnds.iter().any(|&hwnd| api.is_window_visible(hwnd))
+}
+
+/// Shows the taskbar windows without activating them
+pub fn show_taskbar(api: &impl WinApi, hwnds: &[HWND]) {
+    for (hwnd, rect) in docked_rects(api, hwnds) {
+        api.move_window(hwnd, (rect.left, rect.top));
+        api.show_window(hwnd, SW_SHOWNOACTIVATE);
+    }
+}
+
+/// Slide animation moving the taskbars between their docked and off-screen positions,
+/// or fading them in and out in place
+pub struct Slide {
+    bars: Vec<(HWND, RECT)>,
+    edge: Edge,
+    showing: bool,
+    /// Fades the opacity instead of moving the bars
+    fade: bool,
+    /// Bars the fade made layered, which lose the style again when it finishes
+    layered: Vec<HWND>,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Slide {
+    /// Returns true if the taskbars are sliding into view
+    pub fn is_showing(&self) -> bool {
+        self.showing
+    }
+
+    /// Advances the animation, returning true once it is due to `finish`
+    pub fn step(&self, api: &impl WinApi) -> bool {
+        let t = self.started.elapsed().as_secs_f32() / self.duration.as_secs_f32();
+        if t >= 1.0 {
+            return true;
+        }
+
+        // Ease out so the bar decelerates as it settles
+        let eased = 1.0 - (1.0 - t) * (1.0 - t);
+        let visible = if self.showing { eased } else { 1.0 - eased };
+        for &(hwnd, rect) in &self.bars {
+            if self.fade {
+                api.set_layered_alpha(hwnd, (visible * 255.0) as u8);
+            } else {
+                api.move_window(hwnd, slide_position(rect, self.edge, visible));
+            }
+        }
+        false
+    }
+
+    /// Jumps to the end state: docked when showing, hidden when hiding
+    pub fn finish(&self, api: &impl WinApi) {
+        for &(hwnd, rect) in &self.bars {
+            if !self.showing {
+                api.show_window(hwnd, SW_HIDE);
+            }
+            // Always leave the window at its docked rect for the next reveal
+            api.move_window(hwnd, (rect.left, rect.top));
+        }
+        if self.fade {
+            // A fully opaque bar needs no layering, which only costs compositing
+            let hwnds: Vec<HWND> = self.bars.iter().map(|&(hwnd, _)| hwnd).collect();
+            restore_opacity(api, &hwnds);
+            remove_layered_style(api, &self.layered);
+        }
+    }
+}
+
+/// Computes a bar's position when `visible` (0.0 to 1.0) of it is on screen
+fn slide_position(docked: RECT, edge: Edge, visible: f32) -> (i32, i32) {
+    let hidden = 1.0 - visible;
+    let dx = (hidden * (docked.right - docked.left) as f32) as i32;
+    let dy = (hidden * (docked.bottom - docked.top) as f32) as i32;
+    match edge {
+        Edge::Left => (docked.left - dx, docked.top),
+        Edge::Top => (docked.left, docked.top - dy),
+        Edge::Right => (docked.left + dx, docked.top),
+        Edge::Bottom => (docked.left, docked.top + dy),
+    }
+}
+
+/// Records the docked rect of each taskbar
+fn docked_rects(api: &impl WinApi, hwnds: &[HWND]) -> Vec<(HWND, RECT)> {
+    hwnds
+        .iter()
+        .filter_map(|&hwnd| docked_rect(api, hwnd).map(|rect| (hwnd, rect)))
+        .collect()
+}
+
+/// Returns where a taskbar sits when docked
+///
+/// The shell reports the primary bar's docked rect directly, which stays right
+/// for a top, left or right dock even if the window was left off-screen.
+/// Secondary bars fall back to their current window rect.
+fn docked_rect(api: &impl WinApi, hwnd: HWND) -> Option<RECT> {
+    if Some(hwnd) == find_primary_taskbar(api) {
+        let mut abd = app_bar_data(hwnd);
+        if api.app_bar_message(ABM_GETTASKBARPOS, &mut abd) != 0 {
+            return Some(abd.rc);
+        }
+    }
+    api.window_rect(hwnd)
+}
+
+/// Returns true if a window reaches well into a taskbar's docked rect
+///
+/// Window rects include invisible resize borders several pixels wide, so the
+/// overlap only counts once it is deeper than a quarter of the bar's thickness.
+pub fn covers(bar: RECT, window: RECT) -> bool {
+    let overlap_x = bar.right.min(window.right) - bar.left.max(window.left);
+    let overlap_y = bar.bottom.min(window.bottom) - bar.top.max(window.top);
+    if overlap_x <= 0 || overlap_y <= 0 {
+        return false;
+    }
+    let (width, height) = (bar.right - bar.left, bar.bottom - bar.top);
+    if width >= height {
+        overlap_y * 4 > height
+    } else {
+        overlap_x * 4 > width
+    }
+}
+
+/// Returns true if a window with the given rect covers any of the taskbars
+pub fn is_covered(api: &impl WinApi, hwnds: &[HWND], window: RECT) -> bool {
+    docked_rects(api, hwnds).into_iter().any(|(_, bar)| covers(bar, window))
+}
+
+/// Starts sliding the taskbars in from off-screen
+pub fn animate_show(api: &impl WinApi, hwnds: &[HWND], duration_ms: u64) -> Slide {
+    let slide = Slide {
+        bars: docked_rects(api, hwnds),
+        edge: hwnds.first().map_or(Edge::Bottom, |&hwnd| taskbar_edge(api, hwnd)),
+        showing: true,
+        fade: false,
+        layered: Vec::new(),
+        started: Instant::now(),
+        duration: Duration::from_millis(duration_ms),
+    };
+    for &(hwnd, rect) in &slide.bars {
+        api.move_window(hwnd, slide_position(rect, slide.edge, 0.0));
+        api.show_window(hwnd, SW_SHOWNOACTIVATE);
+    }
+    slide
+}
+
+/// Starts sliding the taskbars off-screen; they are hidden when it finishes
+pub fn animate_hide(api: &impl WinApi, hwnds: &[HWND], duration_ms: u64) -> Slide {
+    Slide {
+        bars: docked_rects(api, hwnds),
+        edge: hwnds.first().map_or(Edge::Bottom, |&hwnd| taskbar_edge(api, hwnd)),
+        showing: false,
+        fade: false,
+        layered: Vec::new(),
+        started: Instant::now(),
+        duration: Duration::from_millis(duration_ms),
     }
 }
 
-/// Hides the taskbar window (with retry logic)
-pub fn hide_taskbar(hwnd: HWND) {
-    unsafe {
-        for _ in 0..3 {
-            ShowWindow(hwnd, SW_HIDE);
-            thread::sleep(Duration::from_millis(50));
+/// Starts fading the taskbars in, or out and then hiding them
+///
+/// The bars are made layered before they are shown, so a fade-in starts from
+/// fully transparent instead of flashing at full opacity.
+pub fn animate_fade(api: &impl WinApi, hwnds: &[HWND], showing: bool, duration_ms: u64) -> Slide {
+    let bars = docked_rects(api, hwnds);
+    let start_alpha = if showing { 0 } else { 255 };
+    let layered = bars
+        .iter()
+        .filter(|&&(hwnd, _)| set_opacity(api, hwnd, start_alpha))
+        .map(|&(hwnd, _)| hwnd)
+        .collect();
+    if showing {
+        for &(hwnd, rect) in &bars {
+            api.move_window(hwnd, (rect.left, rect.top));
+            api.show_window(hwnd, SW_SHOWNOACTIVATE);
         }
     }
+    Slide {
+        bars,
+        edge: Edge::Bottom,
+        showing,
+        fade: true,
+        layered,
+        started: Instant::now(),
+        duration: Duration::from_millis(duration_ms),
+    }
+}
+
+/// Owns the managed taskbar handles and hides, shows and restores them
+pub struct TaskbarController<A: WinApi = SystemApi> {
+    api: A,
+    hwnds: Vec<HWND>,
+    /// Leave the primary taskbar out of the managed set
+    keep_primary: bool,
+    /// Monitor device names, e.g. `DISPLAY2`, whose taskbars are always left shown
+    always_visible: Vec<String>,
+    /// Monitors whose taskbars are revealed on their own, from `[monitor.*]` sections
+    independent: Vec<String>,
+    /// Revealed bars on those monitors, which `hide` leaves alone
+    independent_shown: Vec<HWND>,
+    /// Never reveal the secondary bars, only the primary one
+    secondary_hidden: bool,
+    /// Fade the taskbars out instead of hiding them
+    hide_style: HideStyle,
+    hide_alpha: u8,
+    /// Whether `init` may turn on auto-hide
+    autohide_allowed: bool,
+    /// Set once we turn auto-hide on, so exit only undoes our own change
+    autohide_set_by_us: bool,
+    /// Taskbars we added `WS_EX_LAYERED` to, so cleanup only removes our own style
+    layered_by_us: Vec<HWND>,
 }
 
-/// Shows the taskbar window without activating it
-pub fn show_taskbar(hwnd: HWND) {
-    unsafe {
-        ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+impl<A: WinApi> TaskbarController<A> {
+    /// Creates a controller that manages no taskbars until `init` is called
+    pub fn new(api: A) -> TaskbarController<A> {
+        TaskbarController {
+            api,
+            hwnds: Vec::new(),
+            keep_primary: false,
+            always_visible: Vec::new(),
+            independent: Vec::new(),
+            independent_shown: Vec::new(),
+            secondary_hidden: false,
+            hide_style: HideStyle::Hide,
+            hide_alpha: DEFAULT_HIDE_ALPHA,
+            autohide_allowed: true,
+            autohide_set_by_us: false,
+            layered_by_us: Vec::new(),
+        }
+    }
+
+    /// Returns the API the taskbars are driven through
+    pub fn api(&self) -> &A {
+        &self.api
+    }
+
+    /// Leaves the primary taskbar out of the managed set when `keep` is true
+    pub fn set_keep_primary(&mut self, keep: bool) {
+        self.keep_primary = keep;
+    }
+
+    /// Leaves the taskbars on the given monitors, e.g. `DISPLAY2`, always shown
+    pub fn set_always_visible(&mut self, monitors: Vec<String>) {
+        self.always_visible = monitors;
+    }
+
+    /// Returns the monitors whose taskbar is left always shown
+    pub fn always_visible(&self) -> &[String] {
+        &self.always_visible
+    }
+
+    /// Reveals the taskbars on the given monitors on their own instead of with the rest
+    pub fn set_independent(&mut self, monitors: Vec<String>) {
+        self.independent = monitors;
+    }
+
+    /// Keeps the secondary bars hidden at all times when `hidden` is true, so only the
+    /// primary one is ever revealed
+    pub fn set_secondary_hidden(&mut self, hidden: bool) {
+        self.secondary_hidden = hidden;
+    }
+
+    /// Returns the managed taskbars that may be revealed, leaving out the secondary
+    /// ones when they stay hidden
+    fn revealable(&self) -> Vec<HWND> {
+        if !self.secondary_hidden {
+            return self.hwnds.clone();
+        }
+        let primary = find_primary_taskbar(&self.api);
+        self.hwnds.iter().copied().filter(|&hwnd| Some(hwnd) == primary).collect()
+    }
+
+    /// Returns the revealable taskbars on the monitor with the given device name
+    pub fn bars_on(&self, device: &str) -> Vec<HWND> {
+        let on_device = |hwnd: &HWND| {
+            self.api.monitor_device_name(*hwnd).as_deref() == Some(device)
+        };
+        self.revealable().into_iter().filter(on_device).collect()
+    }
+
+    /// Returns the revealable taskbars shown together, i.e. not on an independent monitor
+    pub fn shared_hwnds(&self) -> Vec<HWND> {
+        if self.independent.is_empty() {
+            return self.revealable();
+        }
+        let shared = |hwnd: &HWND| {
+            let device = self.api.monitor_device_name(*hwnd);
+            device.is_none_or(|name| !self.independent.contains(&name))
+        };
+        self.revealable().into_iter().filter(shared).collect()
+    }
+
+    /// Shows taskbars of an independent monitor and keeps `hide` from hiding them
+    pub fn show_independent(&mut self, hwnds: &[HWND]) {
+        self.show_bars(hwnds);
+        for &hwnd in hwnds {
+            if !self.independent_shown.contains(&hwnd) {
+                self.independent_shown.push(hwnd);
+            }
+        }
+    }
+
+    /// Hides taskbars of an independent monitor again
+    pub fn hide_independent(&mut self, hwnds: &[HWND]) {
+        self.independent_shown.retain(|hwnd| !hwnds.contains(hwnd));
+        self.hide_bars(hwnds);
+    }
+
+    /// Lets `init` turn on auto-hide; off where `SHAppBarMessage` is unreliable
+    pub fn set_autohide_allowed(&mut self, allowed: bool) {
+        self.autohide_allowed = allowed;
+    }
+
+    /// Splits the taskbars into the ones to manage and the ones left shown
+    fn partition_taskbars(&self) -> (Vec<HWND>, Vec<HWND>) {
+        let primary = find_primary_taskbar(&self.api);
+        find_all_taskbars(&self.api).into_iter().partition(|&hwnd| {
+            let kept_primary = self.keep_primary && Some(hwnd) == primary;
+            let kept_monitor = !self.always_visible.is_empty()
+                && self
+                    .api
+                    .monitor_device_name(hwnd)
+                    .is_some_and(|name| self.always_visible.contains(&name));
+            !kept_primary && !kept_monitor
+        })
+    }
+
+    /// Chooses whether `hide` hides the bars or fades them to `alpha`
+    ///
+    /// Switching styles undoes the old one; the next `hide` or `init` applies the new one.
+    pub fn set_hide_style(&mut self, style: HideStyle, alpha: u8) {
+        if self.is_transparent_style() && style != HideStyle::Transparent {
+            self.release_layered(&self.hwnds.clone());
+        } else if style == HideStyle::Transparent && self.autohide_set_by_us {
+            // A faded bar stays docked, so auto-hide would only slide it out of view
+            self.set_autohide(false);
+            self.autohide_set_by_us = false;
+        }
+        self.hide_style = style;
+        self.hide_alpha = alpha;
+    }
+
+    /// Puts the given bars back to full opacity and drops the layered style we added
+    fn release_layered(&mut self, hwnds: &[HWND]) {
+        restore_opacity(&self.api, hwnds);
+        let (ours, rest): (Vec<HWND>, Vec<HWND>) =
+            self.layered_by_us.iter().partition(|hwnd| hwnds.contains(hwnd));
+        remove_layered_style(&self.api, &ours);
+        self.layered_by_us = rest;
+    }
+
+    /// Returns true if the taskbars are faded out rather than hidden
+    pub fn is_transparent_style(&self) -> bool {
+        self.hide_style == HideStyle::Transparent
+    }
+
+    /// Returns the managed taskbar handles, the primary bar first
+    pub fn hwnds(&self) -> &[HWND] {
+        &self.hwnds
+    }
+
+    /// Returns true if auto-hide is on because we turned it on
+    pub fn autohide_set_by_us(&self) -> bool {
+        self.autohide_set_by_us
+    }
+
+    /// Finds the taskbars, enables auto-hide and hides them
+    pub fn init(&mut self) -> Result<(), &'static str> {
+        if find_primary_taskbar(&self.api).is_none() {
+            return Err("Failed to find taskbar");
+        }
+        let (managed, kept) = self.partition_taskbars();
+        self.hwnds = managed;
+        // Every bar is hidden below; the caller reveals the independent ones again
+        self.independent_shown.clear();
+        // The auto-hide state is shared by all bars, so it would also hide a kept bar.
+        // A faded bar stays docked, so auto-hide would only slide it out of view.
+        if self.autohide_allowed && kept.is_empty() && !self.is_transparent_style() {
+            self.set_autohide(true);
+        } else if self.autohide_set_by_us && !kept.is_empty() {
+            // A kept bar appeared since, e.g. its monitor was plugged in
+            for &hwnd in &kept {
+                set_autohide_mode(&self.api, hwnd, false);
+            }
+            self.autohide_set_by_us = false;
+        }
+        // A bar that was managed before a display change or reload may still be
+        // faded or hidden
+        self.release_layered(&kept);
+        let hidden: Vec<HWND> =
+            kept.into_iter().filter(|&hwnd| !self.api.is_window_visible(hwnd)).collect();
+        show_taskbar(&self.api, &hidden);
+        self.hide();
+        Ok(())
+    }
+
+    /// Re-enumerates the taskbars if any cached handle is no longer a window
+    ///
+    /// Returns true if the handles were replaced.
+    pub fn refresh(&mut self) -> bool {
+        if self.hwnds.iter().all(|&hwnd| self.api.is_window(hwnd)) {
+            return false;
+        }
+        self.hwnds = self.partition_taskbars().0;
+        self.independent_shown.retain(|hwnd| self.hwnds.contains(hwnd));
+        true
+    }
+
+    /// Turns auto-hide on or off for the managed taskbars
+    pub fn set_autohide(&mut self, enable: bool) {
+        for &hwnd in &self.hwnds {
+            if is_autohide_enabled(&self.api, hwnd) != enable {
+                set_autohide_mode(&self.api, hwnd, enable);
+                self.autohide_set_by_us = enable;
+            }
+        }
+    }
+
+    /// Turns our auto-hide back on for bars where something else turned it off
+    ///
+    /// Returns true if any bar had to be corrected.
+    pub fn reassert_autohide(&mut self) -> bool {
+        if !self.autohide_set_by_us {
+            return false;
+        }
+        let reset: Vec<HWND> = self
+            .hwnds
+            .iter()
+            .copied()
+            .filter(|&hwnd| !is_autohide_enabled(&self.api, hwnd))
+            .collect();
+        for &hwnd in &reset {
+            set_autohide_mode(&self.api, hwnd, true);
+        }
+        !reset.is_empty()
+    }
+
+    /// Hides every managed taskbar but the revealed independent ones, or fades it out
+    /// with the transparent style
+    pub fn hide(&mut self) {
+        let hwnds: Vec<HWND> = self
+            .hwnds
+            .iter()
+            .copied()
+            .filter(|hwnd| !self.independent_shown.contains(hwnd))
+            .collect();
+        self.hide_bars(&hwnds);
+    }
+
+    /// Hides or fades out the given taskbars
+    fn hide_bars(&mut self, hwnds: &[HWND]) {
+        if !self.is_transparent_style() {
+            hide_taskbar(&self.api, hwnds);
+            return;
+        }
+        for &hwnd in hwnds {
+            if set_opacity(&self.api, hwnd, self.hide_alpha) {
+                self.layered_by_us.push(hwnd);
+            }
+        }
+    }
+
+    /// Shows every managed taskbar
+    pub fn show(&mut self) {
+        let hwnds = self.hwnds.clone();
+        self.show_bars(&hwnds);
+    }
+
+    /// Shows the given taskbars, e.g. only the one on a hovered monitor
+    pub fn show_bars(&mut self, hwnds: &[HWND]) {
+        if self.is_transparent_style() {
+            for &hwnd in hwnds {
+                if set_opacity(&self.api, hwnd, 255) {
+                    self.layered_by_us.push(hwnd);
+                }
+            }
+        }
+        show_taskbar(&self.api, hwnds);
+    }
+
+    /// Returns true if any managed taskbar that should be hidden is currently visible
+    pub fn is_any_visible(&self) -> bool {
+        let hidden: Vec<HWND> = self
+            .hwnds
+            .iter()
+            .copied()
+            .filter(|hwnd| !self.independent_shown.contains(hwnd))
+            .collect();
+        is_any_visible(&self.api, &hidden)
+    }
+
+    /// Restores taskbar visibility, opacity and the user's auto-hide setting
+    pub fn cleanup(&mut self) {
+        if self.autohide_set_by_us {
+            self.set_autohide(false);
+            self.autohide_set_by_us = false;
+        }
+        self.show();
+        self.independent_shown.clear();
+        restore_opacity(&self.api, &self.hwnds);
+        remove_layered_style(&self.api, &self.layered_by_us);
+        self.layered_by_us.clear();
     }
 }
 
-/// Initialize taskbar control - find handles and set auto-hide
-pub fn init() -> Result<HWND, &'static str> {
-    let hwnd = find_primary_taskbar().ok_or("Failed to find taskbar")?;
-    set_autohide_mode(hwnd, true);
-    hide_taskbar(hwnd);
-    Ok(hwnd)
+impl<A: WinApi + Default> Default for TaskbarController<A> {
+    fn default() -> Self {
+        TaskbarController::new(A::default())
+    }
 }
 
-/// Cleanup - restore taskbar visibility
-pub fn cleanup(hwnd: HWND) {
-    show_taskbar(hwnd);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::winapi::mock::MockApi;
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> RECT {
+        RECT { left, top, right, bottom }
+    }
+
+    #[test]
+    fn slide_position_moves_off_the_docked_edge() {
+        let docked = rect(0, 1040, 1920, 1080);
+        assert_eq!(slide_position(docked, Edge::Bottom, 1.0), (0, 1040));
+        assert_eq!(slide_position(docked, Edge::Bottom, 0.0), (0, 1080));
+        assert_eq!(slide_position(docked, Edge::Bottom, 0.5), (0, 1060));
+        assert_eq!(slide_position(docked, Edge::Top, 0.0), (0, 1000));
+    }
+
+    #[test]
+    fn slide_position_uses_the_width_for_side_docks() {
+        let docked = rect(0, 0, 48, 1080);
+        assert_eq!(slide_position(docked, Edge::Left, 0.0), (-48, 0));
+        assert_eq!(slide_position(docked, Edge::Left, 0.5), (-24, 0));
+        let docked = rect(1872, 0, 1920, 1080);
+        assert_eq!(slide_position(docked, Edge::Right, 0.0), (1920, 0));
+    }
+
+    #[test]
+    fn covers_needs_more_than_the_resize_border() {
+        let bar = rect(0, 1040, 1920, 1080);
+        assert!(covers(bar, rect(0, 0, 1920, 1080)));
+        assert!(!covers(bar, rect(0, 0, 1920, 1047)));
+        assert!(covers(bar, rect(1800, 600, 2200, 1060)));
+        assert!(!covers(bar, rect(1920, 0, 3840, 1080)));
+        let side_bar = rect(0, 0, 48, 1080);
+        assert!(!covers(side_bar, rect(40, 0, 960, 1080)));
+        assert!(covers(side_bar, rect(0, 0, 960, 1080)));
+    }
+
+    #[test]
+    fn init_hides_every_bar_and_turns_on_autohide() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.init().unwrap();
+        assert_eq!(controller.hwnds(), [1 as HWND, 2 as HWND]);
+        assert!(!controller.api().is_visible(1 as HWND));
+        assert!(!controller.api().is_visible(2 as HWND));
+        assert!(*controller.api().autohide.borrow());
+        assert!(controller.autohide_set_by_us());
+    }
+
+    #[test]
+    fn reassert_autohide_only_restores_our_own_setting() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.init().unwrap();
+        assert!(!controller.reassert_autohide());
+        *controller.api().autohide.borrow_mut() = false;
+        assert!(controller.reassert_autohide());
+        assert!(*controller.api().autohide.borrow());
+
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(0));
+        controller.set_autohide_allowed(false);
+        controller.init().unwrap();
+        assert!(!controller.reassert_autohide());
+        assert!(!*controller.api().autohide.borrow());
+    }
+
+    #[test]
+    fn init_fails_without_a_taskbar() {
+        let mut controller = TaskbarController::new(MockApi::default());
+        assert!(controller.init().is_err());
+    }
+
+    #[test]
+    fn init_leaves_autohide_alone_when_not_allowed() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(0));
+        controller.set_autohide_allowed(false);
+        controller.init().unwrap();
+        assert!(!controller.api().is_visible(1 as HWND));
+        assert!(!*controller.api().autohide.borrow());
+    }
+
+    #[test]
+    fn show_and_hide_transition_the_managed_bars() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.init().unwrap();
+        controller.show_bars(&[2 as HWND]);
+        assert!(!controller.api().is_visible(1 as HWND));
+        assert!(controller.api().is_visible(2 as HWND));
+        controller.show();
+        assert!(controller.is_any_visible());
+        controller.hide();
+        assert!(!controller.is_any_visible());
+    }
+
+    #[test]
+    fn cleanup_restores_the_bars_and_autohide() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.init().unwrap();
+        controller.cleanup();
+        assert!(controller.api().is_visible(1 as HWND));
+        assert!(controller.api().is_visible(2 as HWND));
+        assert!(!*controller.api().autohide.borrow());
+    }
+
+    #[test]
+    fn cleanup_leaves_the_users_autohide_alone() {
+        let api = MockApi::with_taskbars(0);
+        *api.autohide.borrow_mut() = true;
+        let mut controller = TaskbarController::new(api);
+        controller.init().unwrap();
+        controller.cleanup();
+        assert!(*controller.api().autohide.borrow());
+    }
+
+    #[test]
+    fn keep_primary_leaves_the_primary_bar_shown() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.set_keep_primary(true);
+        controller.init().unwrap();
+        assert_eq!(controller.hwnds(), [2 as HWND]);
+        assert!(controller.api().is_visible(1 as HWND));
+        // Auto-hide would hide the kept bar too
+        assert!(!*controller.api().autohide.borrow());
+    }
+
+    #[test]
+    fn always_visible_monitors_are_left_shown() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(2));
+        controller.set_always_visible(vec!["DISPLAY3".to_string()]);
+        controller.init().unwrap();
+        assert_eq!(controller.hwnds(), [1 as HWND, 2 as HWND]);
+        assert!(controller.api().is_visible(3 as HWND));
+    }
+
+    #[test]
+    fn independent_monitors_are_shown_and_hidden_on_their_own() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(2));
+        controller.set_independent(vec!["DISPLAY2".to_string()]);
+        controller.init().unwrap();
+        assert_eq!(controller.shared_hwnds(), [1 as HWND, 3 as HWND]);
+        let bars = controller.bars_on("DISPLAY2");
+        assert_eq!(bars, [2 as HWND]);
+
+        controller.show_independent(&bars);
+        controller.show();
+        controller.hide();
+        assert!(controller.api().is_visible(2 as HWND));
+        assert!(!controller.api().is_visible(1 as HWND));
+        assert!(!controller.is_any_visible());
+
+        controller.hide_independent(&bars);
+        assert!(!controller.api().is_visible(2 as HWND));
+    }
+
+    #[test]
+    fn hidden_secondary_bars_are_never_revealed() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(2));
+        controller.set_secondary_hidden(true);
+        controller.init().unwrap();
+        // Still managed, so init hid them and they come back on cleanup
+        assert_eq!(controller.hwnds().len(), 3);
+        assert!(!controller.api().is_visible(2 as HWND));
+        assert_eq!(controller.shared_hwnds(), [1 as HWND]);
+        assert!(controller.bars_on("DISPLAY3").is_empty());
+
+        controller.cleanup();
+        assert!(controller.api().is_visible(3 as HWND));
+    }
+
+    #[test]
+    fn transparent_style_fades_and_cleanup_removes_the_layered_style() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(0));
+        controller.set_hide_style(HideStyle::Transparent, 40);
+        controller.init().unwrap();
+        let bar = controller.api().window(1 as HWND);
+        assert!(bar.visible);
+        assert_eq!(bar.alpha, Some(40));
+        assert_ne!(bar.ex_style & WS_EX_LAYERED as isize, 0);
+        assert!(!*controller.api().autohide.borrow());
+
+        controller.cleanup();
+        let bar = controller.api().window(1 as HWND);
+        assert_eq!(bar.alpha, Some(255));
+        assert_eq!(bar.ex_style & WS_EX_LAYERED as isize, 0);
+    }
+
+    #[test]
+    fn switching_from_transparent_restores_the_bars() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(0));
+        controller.set_hide_style(HideStyle::Transparent, 40);
+        controller.init().unwrap();
+        controller.set_hide_style(HideStyle::Hide, 40);
+        controller.init().unwrap();
+        let bar = controller.api().window(1 as HWND);
+        assert!(!bar.visible);
+        assert_eq!(bar.alpha, Some(255));
+        assert_eq!(bar.ex_style & WS_EX_LAYERED as isize, 0);
+        assert!(*controller.api().autohide.borrow());
+    }
+
+    #[test]
+    fn switching_to_transparent_turns_off_our_autohide() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(0));
+        controller.init().unwrap();
+        controller.set_hide_style(HideStyle::Transparent, 40);
+        controller.init().unwrap();
+        assert!(!*controller.api().autohide.borrow());
+        assert_eq!(controller.api().window(1 as HWND).alpha, Some(40));
+    }
+
+    #[test]
+    fn a_newly_kept_monitor_is_restored() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.set_hide_style(HideStyle::Transparent, 40);
+        controller.init().unwrap();
+        controller.set_always_visible(vec!["DISPLAY2".to_string()]);
+        controller.init().unwrap();
+        assert_eq!(controller.hwnds(), [1 as HWND]);
+        let bar = controller.api().window(2 as HWND);
+        assert_eq!(bar.alpha, Some(255));
+        assert_eq!(bar.ex_style & WS_EX_LAYERED as isize, 0);
+    }
+
+    #[test]
+    fn refresh_re_enumerates_after_a_bar_is_destroyed() {
+        let mut controller = TaskbarController::new(MockApi::with_taskbars(1));
+        controller.init().unwrap();
+        assert!(!controller.refresh());
+        controller.api().destroy(2 as HWND);
+        assert!(controller.refresh());
+        assert_eq!(controller.hwnds(), [1 as HWND]);
+    }
+
+    #[test]
+    fn slide_finish_docks_the_bars() {
+        let api = MockApi::with_taskbars(0);
+        let slide = animate_show(&api, &[1 as HWND], 100);
+        assert!(api.is_visible(1 as HWND));
+        assert_eq!(api.window(1 as HWND).rect.top, 1080);
+        slide.finish(&api);
+        assert_eq!(api.window(1 as HWND).rect.top, 1040);
+
+        let slide = animate_hide(&api, &[1 as HWND], 100);
+        slide.finish(&api);
+        assert!(!api.is_visible(1 as HWND));
+        assert_eq!(api.window(1 as HWND).rect.top, 1040);
+    }
+
+    #[test]
+    fn fade_starts_transparent_and_drops_the_layered_style_at_the_end() {
+        let api = MockApi::with_taskbars(0);
+        let fade = animate_fade(&api, &[1 as HWND], true, 100);
+        assert!(api.is_visible(1 as HWND));
+        assert_eq!(api.window(1 as HWND).alpha, Some(0));
+        assert_ne!(api.window(1 as HWND).ex_style & WS_EX_LAYERED as isize, 0);
+        fade.finish(&api);
+        assert_eq!(api.window(1 as HWND).alpha, Some(255));
+        assert_eq!(api.window(1 as HWND).ex_style & WS_EX_LAYERED as isize, 0);
+
+        let fade = animate_fade(&api, &[1 as HWND], false, 100);
+        fade.finish(&api);
+        assert!(!api.is_visible(1 as HWND));
+        assert_eq!(api.window(1 as HWND).ex_style & WS_EX_LAYERED as isize, 0);
+    }
 }
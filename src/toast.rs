@@ -0,0 +1,79 @@
+//! Toast notification module
+//!
+//! Watches for notification toasts appearing so the taskbar can be revealed
+//! briefly. Toasts are `Windows.UI.Core.CoreWindow` windows owned by the
+//! shell experience host.
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    PostMessageW, EVENT_OBJECT_SHOW, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT,
+    WINEVENT_SKIPOWNPROCESS, WM_USER,
+};
+use crate::shell;
+use taskbar_hider::winapi::Error;
+
+pub const WM_TOAST_SHOWN: u32 = WM_USER + 140;
+
+const TOAST_WINDOW_CLASS: &str = "Windows.UI.Core.CoreWindow";
+const TOAST_HOST_EXE: &str = "shellexperiencehost.exe";
+
+static HOOK_HANDLE: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+static NOTIFY_HWND: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(null_mut());
+
+/// Returns true if the window is a notification toast
+fn is_toast_window(hwnd: HWND) -> bool {
+    // The class check is cheap and filters out nearly every other window
+    shell::window_class(hwnd) == TOAST_WINDOW_CLASS
+        && shell::owner_exe_name(hwnd).as_deref() == Some(TOAST_HOST_EXE)
+}
+
+/// Window shown event callback
+unsafe extern "system" fn show_event_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    _id_child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    if id_object == OBJID_WINDOW && !hwnd.is_null() && is_toast_window(hwnd) {
+        PostMessageW(NOTIFY_HWND.load(Ordering::SeqCst) as HWND, WM_TOAST_SHOWN, 0, 0);
+    }
+}
+
+/// Start watching for toasts
+pub fn install(notify_hwnd: HWND) -> Result<(), Error> {
+    unsafe {
+        NOTIFY_HWND.store(notify_hwnd as *mut _, Ordering::SeqCst);
+
+        let hook = SetWinEventHook(
+            EVENT_OBJECT_SHOW,
+            EVENT_OBJECT_SHOW,
+            null_mut(),
+            Some(show_event_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+        );
+        if hook.is_null() {
+            return Err(Error::last("Failed to install toast watcher"));
+        }
+
+        HOOK_HANDLE.store(hook, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Stop watching for toasts
+pub fn uninstall() {
+    unsafe {
+        let hook = HOOK_HANDLE.swap(null_mut(), Ordering::SeqCst);
+        if !hook.is_null() {
+            UnhookWinEvent(hook);
+        }
+    }
+}
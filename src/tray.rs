@@ -1,29 +1,142 @@
 //! System tray icon module
 //!
-//! Provides a tray icon with right-click quit menu.
+//! Provides a tray icon with a right-click menu (peek, pause, autostart, logon
+//! task, settings, monitors, reload, reset, export, import, debug overlay, about,
+//! quit), whose entries and order can be configured.
+//! A left click toggles pause and a double-click opens the settings.
 
 use std::mem::size_of;
+use std::path::{Path, PathBuf};
 use std::ptr::{null, null_mut};
-use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use windows_sys::core::PCWSTR;
+use windows_sys::Win32::Foundation::{BOOL, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT};
+use windows_sys::Win32::UI::Controls::Dialogs::{
+    CommDlgExtendedError, GetOpenFileNameW, GetSaveFileNameW, OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR,
+    OFN_OVERWRITEPROMPT, OFN_PATHMUSTEXIST, OPENFILENAMEW,
+};
+use windows_sys::Win32::Graphics::Gdi::{
+    EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOEXW,
+};
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::GetDoubleClickTime;
 use windows_sys::Win32::UI::Shell::{
-    NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NOTIFYICONDATAW, Shell_NotifyIconW,
+    ShellExecuteW, Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_INFO,
+    NIIF_NOSOUND, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
 };
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    AppendMenuW, CreatePopupMenu, DestroyMenu, GetCursorPos, LoadIconW, SetForegroundWindow,
-    TrackPopupMenu, IDI_APPLICATION, MF_STRING, TPM_BOTTOMALIGN, TPM_LEFTALIGN, WM_RBUTTONUP,
-    WM_USER,
+    AppendMenuW, CreatePopupMenu, DestroyMenu, GetCursorPos, KillTimer, LoadIconW, MessageBoxW,
+    PostMessageW, SetForegroundWindow, SetTimer, TrackPopupMenu, HICON, HMENU, IDI_APPLICATION,
+    IDYES, MB_DEFBUTTON2, MB_ICONINFORMATION, MB_ICONWARNING, MB_OK, MB_YESNO, MF_CHECKED,
+    MF_DISABLED, MF_GRAYED, MF_POPUP, MF_SEPARATOR, MF_STRING, SW_SHOW, TPM_BOTTOMALIGN,
+    TPM_LEFTALIGN, WM_COMMAND, WM_LBUTTONDBLCLK, WM_LBUTTONUP, WM_RBUTTONUP, WM_USER,
 };
+use taskbar_hider::config::MenuItem;
+use taskbar_hider::winapi::Error;
 
 pub const WM_TRAYICON: u32 = WM_USER + 1;
-pub const IDM_QUIT: usize = 1001;
+/// Command posted by a single click on the icon
+pub const IDM_TOGGLE: usize = 1002;
+/// Command posted by a double-click on the icon
+pub const IDM_SETTINGS: usize = 1004;
+/// Command ID of the first configured menu entry; the others follow in order
+const IDM_FIRST_ITEM: usize = 2000;
+/// Command ID of the first monitor in the Monitors submenu
+const IDM_FIRST_MONITOR: usize = 3000;
+
+/// Icon resource ID in `assets/app.rc`
+const APP_ICON_ID: usize = 1;
+
+/// Timer that waits out the double-click time before acting on a single click
+const CLICK_TIMER_ID: usize = 100;
+
+/// Set after a double-click so its trailing button-up isn't taken as a click
+static SKIP_NEXT_CLICK: AtomicBool = AtomicBool::new(false);
+
+/// Set while the About box is open so a second one isn't stacked on top
+static ABOUT_OPEN: AtomicBool = AtomicBool::new(false);
+/// Set while the reset confirmation is open, for the same reason
+static RESET_OPEN: AtomicBool = AtomicBool::new(false);
+/// Set while the export or import file dialog is open, for the same reason
+static FILE_DIALOG_OPEN: AtomicBool = AtomicBool::new(false);
+
+const APP_NAME: &str = "Taskbar Hider";
+const DEFAULT_TOOLTIP: &str = "Taskbar Hider - Right-click to quit";
+
+/// User-configured tooltip, replacing the default text and the app name in status lines
+static CUSTOM_TOOLTIP: Mutex<Option<String>> = Mutex::new(None);
+/// Configured menu entries; an entry's command ID is `IDM_FIRST_ITEM` plus its index
+static MENU_ITEMS: Mutex<Vec<MenuItem>> = Mutex::new(Vec::new());
+/// Connected monitors as (device name, label), in the order of the Monitors submenu
+static MONITORS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
 
 /// Encodes a string as a null-terminated wide string
 fn wide_string(s: &str) -> Vec<u16> {
     s.encode_utf16().chain(std::iter::once(0)).collect()
 }
 
+/// Popup menu handle that is destroyed when dropped
+struct Menu(HMENU);
+
+impl Menu {
+    /// Creates an empty popup menu
+    fn popup() -> Option<Menu> {
+        let menu = unsafe { CreatePopupMenu() };
+        if menu.is_null() {
+            None
+        } else {
+            Some(Menu(menu))
+        }
+    }
+}
+
+impl Drop for Menu {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyMenu(self.0);
+        }
+    }
+}
+
+/// Tray icon that is removed from the notification area when dropped
+pub struct TrayIcon {
+    hwnd: HWND,
+}
+
+impl TrayIcon {
+    /// Adds the tray icon, returning a guard that owns it
+    pub fn add(hwnd: HWND, instance: HINSTANCE) -> Result<TrayIcon, Error> {
+        add_tray_icon(hwnd, instance).map(|()| TrayIcon { hwnd })
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        remove_tray_icon(self.hwnd);
+    }
+}
+
+/// Re-adds the tray icon after Explorer restarts, replacing any existing one
+pub fn readd_tray_icon(hwnd: HWND, instance: HINSTANCE) -> Result<(), Error> {
+    remove_tray_icon(hwnd);
+    add_tray_icon(hwnd, instance)
+}
+
+/// Loads the embedded application icon, falling back to the generic one
+fn load_app_icon(instance: HINSTANCE) -> HICON {
+    unsafe {
+        // MAKEINTRESOURCEW: the resource ID is passed in place of a name
+        let icon = LoadIconW(instance, APP_ICON_ID as PCWSTR);
+        if icon.is_null() {
+            LoadIconW(null_mut(), IDI_APPLICATION)
+        } else {
+            icon
+        }
+    }
+}
+
 /// Adds the system tray icon
-pub fn add_tray_icon(hwnd: HWND) -> bool {
+fn add_tray_icon(hwnd: HWND, instance: HINSTANCE) -> Result<(), Error> {
     unsafe {
         let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
         nid.cbSize = size_of::<NOTIFYICONDATAW>() as u32;
@@ -31,15 +144,91 @@ pub fn add_tray_icon(hwnd: HWND) -> bool {
         nid.uID = 1;
         nid.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
         nid.uCallbackMessage = WM_TRAYICON;
-        nid.hIcon = LoadIconW(null_mut(), IDI_APPLICATION);
+        nid.hIcon = load_app_icon(instance);
+
+        let custom = CUSTOM_TOOLTIP.lock().ok().and_then(|guard| guard.clone());
+        copy_tooltip(&mut nid, custom.as_deref().unwrap_or(DEFAULT_TOOLTIP));
+
+        if Shell_NotifyIconW(NIM_ADD, &nid) == 0 {
+            // Shell_NotifyIconW doesn't set the last error
+            return Err(Error::Failed("Failed to add tray icon"));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the configured app name, or the default one
+pub fn app_name() -> String {
+    let custom = CUSTOM_TOOLTIP.lock().ok().and_then(|guard| guard.clone());
+    custom.unwrap_or_else(|| APP_NAME.to_string())
+}
+
+/// Sets the configured tooltip; an empty string restores the default
+pub fn set_custom_tooltip(text: &str) {
+    if let Ok(mut guard) = CUSTOM_TOOLTIP.lock() {
+        *guard = (!text.is_empty()).then(|| text.to_string());
+    }
+}
+
+/// Updates the tray icon tooltip to show a status line
+///
+/// A long custom name is shortened rather than the status, so the whole status fits.
+pub fn set_tooltip(hwnd: HWND, status: &str) {
+    let text = fit_tooltip(&app_name(), status);
+    unsafe {
+        let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
+        nid.cbSize = size_of::<NOTIFYICONDATAW>() as u32;
+        nid.hWnd = hwnd;
+        nid.uID = 1;
+        nid.uFlags = NIF_TIP;
+        copy_tooltip(&mut nid, &text);
+        Shell_NotifyIconW(NIM_MODIFY, &nid);
+    }
+}
 
-        let tip = "Taskbar Hider - Right-click to quit";
-        let tip_wide: Vec<u16> = tip.encode_utf16().chain(std::iter::once(0)).collect();
-        let copy_len = tip_wide.len().min(128);
-        nid.szTip[..copy_len].copy_from_slice(&tip_wide[..copy_len]);
+/// Shows a short balloon notification from the tray icon, without a sound
+pub fn show_balloon(hwnd: HWND, text: &str) {
+    unsafe {
+        let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
+        nid.cbSize = size_of::<NOTIFYICONDATAW>() as u32;
+        nid.hWnd = hwnd;
+        nid.uID = 1;
+        nid.uFlags = NIF_INFO;
+        nid.dwInfoFlags = NIIF_INFO | NIIF_NOSOUND;
+        copy_wide(&mut nid.szInfoTitle, &app_name());
+        copy_wide(&mut nid.szInfo, text);
+        Shell_NotifyIconW(NIM_MODIFY, &nid);
+    }
+}
 
-        Shell_NotifyIconW(NIM_ADD, &nid) != 0
+/// Copies a string into a fixed-size wide buffer, truncating it to fit
+fn copy_wide(buf: &mut [u16], text: &str) {
+    let wide = wide_string(text);
+    let copy_len = wide.len().min(buf.len());
+    buf[..copy_len].copy_from_slice(&wide[..copy_len]);
+    // Keep the string terminated when it was truncated
+    buf[copy_len - 1] = 0;
+}
+
+/// Joins the name and status, cutting the name short if both don't fit in the tooltip
+fn fit_tooltip(name: &str, status: &str) -> String {
+    // 127 UTF-16 units leave room for the terminator
+    let room = 127usize.saturating_sub(status.encode_utf16().count() + 3);
+    let mut short = String::new();
+    let mut used = 0;
+    for c in name.chars() {
+        used += c.len_utf16();
+        if used > room {
+            break;
+        }
+        short.push(c);
     }
+    format!("{} - {}", short, status)
+}
+
+/// Copies a tooltip into `szTip`, truncating to fit the 128-char buffer
+fn copy_tooltip(nid: &mut NOTIFYICONDATAW, text: &str) {
+    copy_wide(&mut nid.szTip, text);
 }
 
 /// Removes the system tray icon
@@ -53,33 +242,311 @@ pub fn remove_tray_icon(hwnd: HWND) {
     }
 }
 
-/// Shows the context menu on right-click
-pub fn show_context_menu(hwnd: HWND) {
+/// Sets the context menu entries and their order
+pub fn set_menu_items(items: &[MenuItem]) {
+    if let Ok(mut menu_items) = MENU_ITEMS.lock() {
+        *menu_items = items.to_vec();
+    }
+}
+
+/// Returns the menu entry a `WM_COMMAND` from the tray stands for
+pub fn menu_command(id: usize) -> Option<MenuItem> {
+    match id {
+        IDM_TOGGLE => Some(MenuItem::Toggle),
+        IDM_SETTINGS => Some(MenuItem::Settings),
+        _ => {
+            let index = id.checked_sub(IDM_FIRST_ITEM)?;
+            MENU_ITEMS.lock().ok()?.get(index).copied()
+        }
+    }
+}
+
+/// Adds a connected monitor to the list passed through `lparam`
+unsafe extern "system" fn monitor_enum_proc(
+    monitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    lparam: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(lparam as *mut Vec<(u32, String, String)>);
+    let mut info: MONITORINFOEXW = std::mem::zeroed();
+    info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+    if GetMonitorInfoW(monitor, &mut info as *mut _ as *mut MONITORINFO) != 0 {
+        let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+        let name = String::from_utf16_lossy(&info.szDevice[..len]);
+        let device = name.trim_start_matches(['\\', '.']).to_ascii_uppercase();
+        let number = device.trim_start_matches("DISPLAY").parse().unwrap_or(0);
+        let bounds = info.monitorInfo.rcMonitor;
+        let (width, height) = (bounds.right - bounds.left, bounds.bottom - bounds.top);
+        monitors.push((number, device, format!("Display {}: {}x{}", number, width, height)));
+    }
+    1
+}
+
+/// Re-reads the connected monitors for the Monitors submenu
+pub fn refresh_monitors() {
+    let mut found: Vec<(u32, String, String)> = Vec::new();
     unsafe {
-        let menu = CreatePopupMenu();
-        if menu.is_null() {
-            return;
+        EnumDisplayMonitors(
+            null_mut(),
+            null(),
+            Some(monitor_enum_proc),
+            &mut found as *mut _ as LPARAM,
+        );
+    }
+    found.sort();
+    if let Ok(mut monitors) = MONITORS.lock() {
+        *monitors = found.into_iter().map(|(_, device, label)| (device, label)).collect();
+    }
+}
+
+/// Returns the device name of the monitor a `WM_COMMAND` from the Monitors submenu picked
+pub fn monitor_command(id: usize) -> Option<String> {
+    let index = id.checked_sub(IDM_FIRST_MONITOR)?;
+    MONITORS.lock().ok()?.get(index).map(|(device, _)| device.clone())
+}
+
+/// Appends the Monitors submenu, checking the monitors whose taskbar is hidden
+unsafe fn append_monitors_menu(menu: &Menu, kept: &[String]) {
+    let Some(submenu) = Menu::popup() else {
+        return;
+    };
+    let monitors = MONITORS.lock().map(|monitors| monitors.clone()).unwrap_or_default();
+    for (index, (device, label)) in monitors.iter().enumerate() {
+        let text = wide_string(label);
+        let flags = if kept.contains(device) { MF_STRING } else { MF_STRING | MF_CHECKED };
+        AppendMenuW(submenu.0, flags, IDM_FIRST_MONITOR + index, text.as_ptr());
+    }
+    let text = wide_string("Hide on monitors");
+    // The parent menu destroys the submenu once it owns it
+    let submenu = std::mem::ManuallyDrop::new(submenu);
+    AppendMenuW(menu.0, MF_POPUP, submenu.0 as usize, text.as_ptr());
+}
+
+/// Returns the label of a menu entry and whether it is checked
+fn menu_label(item: MenuItem, paused: bool) -> (&'static str, bool) {
+    match item {
+        MenuItem::Peek => ("Show temporarily", false),
+        MenuItem::Toggle => (if paused { "Resume" } else { "Pause" }, false),
+        MenuItem::Autostart => ("Start with Windows", crate::autostart::is_enabled()),
+        MenuItem::LogonTask => {
+            ("Start elevated at logon", crate::autostart::is_task_registered())
+        }
+        MenuItem::Settings => ("Settings...", false),
+        MenuItem::Monitors => ("Hide on monitors", false),
+        MenuItem::OpenConfig => ("Open config file", false),
+        MenuItem::Reload => ("Reload config", false),
+        MenuItem::Reset => ("Reset to defaults...", false),
+        MenuItem::Export => ("Export settings...", false),
+        MenuItem::Import => ("Import settings...", false),
+        MenuItem::Overlay => ("Debug overlay", crate::overlay::is_shown()),
+        MenuItem::About => ("About", false),
+        MenuItem::Quit => ("Quit", false),
+        MenuItem::Separator => ("", false),
+    }
+}
+
+/// Shows the context menu on right-click, headed by a disabled status line
+///
+/// `kept` lists the monitors whose taskbar is left alone, unchecked in the
+/// Monitors submenu.
+pub fn show_context_menu(hwnd: HWND, status: &str, paused: bool, kept: &[String]) {
+    let Some(menu) = Menu::popup() else {
+        return;
+    };
+    let items = MENU_ITEMS.lock().map(|items| items.clone()).unwrap_or_default();
+
+    unsafe {
+        let status_text = wide_string(status);
+        AppendMenuW(menu.0, MF_STRING | MF_DISABLED | MF_GRAYED, 0, status_text.as_ptr());
+        AppendMenuW(menu.0, MF_SEPARATOR, 0, null());
+
+        for (index, &item) in items.iter().enumerate() {
+            match item {
+                MenuItem::Separator => {
+                    AppendMenuW(menu.0, MF_SEPARATOR, 0, null());
+                    continue;
+                }
+                MenuItem::Monitors => {
+                    append_monitors_menu(&menu, kept);
+                    continue;
+                }
+                _ => {}
+            }
+            let (label, checked) = menu_label(item, paused);
+            let text = wide_string(label);
+            let flags = if checked { MF_STRING | MF_CHECKED } else { MF_STRING };
+            AppendMenuW(menu.0, flags, IDM_FIRST_ITEM + index, text.as_ptr());
         }
-        let quit_text = wide_string("Quit");
-        AppendMenuW(menu, MF_STRING, IDM_QUIT, quit_text.as_ptr());
 
         let mut pt = POINT { x: 0, y: 0 };
         GetCursorPos(&mut pt);
 
         SetForegroundWindow(hwnd);
-        TrackPopupMenu(menu, TPM_BOTTOMALIGN | TPM_LEFTALIGN, pt.x, pt.y, 0, hwnd, null());
-        DestroyMenu(menu);
+        TrackPopupMenu(menu.0, TPM_BOTTOMALIGN | TPM_LEFTALIGN, pt.x, pt.y, 0, hwnd, null());
+    }
+}
+
+/// Opens the config file in its associated editor, or Notepad if there is none
+pub fn open_config(hwnd: HWND, path: &Path) -> Result<(), Error> {
+    let file = wide_string(&path.display().to_string());
+    let open = wide_string("open");
+    unsafe {
+        // ShellExecuteW returns a value above 32 on success
+        let result = ShellExecuteW(hwnd, open.as_ptr(), file.as_ptr(), null(), null(), SW_SHOW);
+        if result as usize > 32 {
+            return Ok(());
+        }
+        let notepad = wide_string("notepad.exe");
+        let params = wide_string(&format!("\"{}\"", path.display()));
+        let result =
+            ShellExecuteW(hwnd, open.as_ptr(), notepad.as_ptr(), params.as_ptr(), null(), SW_SHOW);
+        if result as usize > 32 {
+            Ok(())
+        } else {
+            Err(Error::Failed("Failed to open the config file"))
+        }
+    }
+}
+
+/// Shows the app name, version and a short description
+///
+/// The message box runs its own modal loop, which keeps dispatching the hooks'
+/// messages and the hide timers to `hwnd` while it is open.
+pub fn show_about(hwnd: HWND) {
+    if ABOUT_OPEN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let text = wide_string(&format!(
+        "{} {}\n\nHides the taskbar and shows it while the Windows key is held.",
+        APP_NAME,
+        env!("CARGO_PKG_VERSION")
+    ));
+    let title = wide_string(&format!("About {}", APP_NAME));
+    unsafe {
+        MessageBoxW(hwnd, text.as_ptr(), title.as_ptr(), MB_OK | MB_ICONINFORMATION);
+    }
+    ABOUT_OPEN.store(false, Ordering::SeqCst);
+}
+
+/// Asks whether to replace the config file with the defaults
+///
+/// Returns false if the user declines or a confirmation is already open.
+pub fn confirm_reset(hwnd: HWND) -> bool {
+    if RESET_OPEN.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    let text = wide_string(
+        "Replace all settings with the defaults?\n\nThe current config file is kept next to it \
+         with a .bak extension.",
+    );
+    let title = wide_string(APP_NAME);
+    let answer = unsafe {
+        MessageBoxW(hwnd, text.as_ptr(), title.as_ptr(), MB_YESNO | MB_ICONWARNING | MB_DEFBUTTON2)
+    };
+    RESET_OPEN.store(false, Ordering::SeqCst);
+    answer == IDYES
+}
+
+/// Asks for a file to export the settings to, or to import them from with `save` unset
+///
+/// Returns `None` if the user cancels or a file dialog is already open.
+pub fn pick_settings_file(hwnd: HWND, save: bool) -> Result<Option<PathBuf>, Error> {
+    if FILE_DIALOG_OPEN.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    // Pairs of a description and a pattern, ending with an empty pair
+    let filter: Vec<u16> =
+        "Settings (*.toml)\0*.toml\0All files\0*.*\0\0".encode_utf16().collect();
+    let title = wide_string(if save { "Export settings" } else { "Import settings" });
+    let extension = wide_string("toml");
+    let mut file = [0u16; 1024];
+    copy_wide(&mut file, if save { "clean-taskbar.toml" } else { "" });
+
+    let picked = unsafe {
+        let mut dialog: OPENFILENAMEW = std::mem::zeroed();
+        dialog.lStructSize = size_of::<OPENFILENAMEW>() as u32;
+        dialog.hwndOwner = hwnd;
+        dialog.lpstrFilter = filter.as_ptr();
+        dialog.lpstrFile = file.as_mut_ptr();
+        dialog.nMaxFile = file.len() as u32;
+        dialog.lpstrTitle = title.as_ptr();
+        dialog.lpstrDefExt = extension.as_ptr();
+        dialog.Flags = OFN_NOCHANGEDIR | OFN_PATHMUSTEXIST;
+        if save {
+            dialog.Flags |= OFN_OVERWRITEPROMPT;
+            GetSaveFileNameW(&mut dialog)
+        } else {
+            dialog.Flags |= OFN_FILEMUSTEXIST;
+            GetOpenFileNameW(&mut dialog)
+        }
+    };
+    FILE_DIALOG_OPEN.store(false, Ordering::SeqCst);
+
+    if picked == 0 {
+        // A cancelled dialog fails without an extended error
+        return match unsafe { CommDlgExtendedError() } {
+            0 => Ok(None),
+            code => Err(Error::status("Failed to show the file dialog", code)),
+        };
     }
+    let len = file.iter().position(|&c| c == 0).unwrap_or(file.len());
+    Ok(Some(PathBuf::from(String::from_utf16_lossy(&file[..len]))))
+}
+
+/// Fires once no double-click followed a single click
+unsafe extern "system" fn click_timer_proc(hwnd: HWND, _msg: u32, id: usize, _time: u32) {
+    KillTimer(hwnd, id);
+    PostMessageW(hwnd, WM_COMMAND, IDM_TOGGLE, 0);
 }
 
 /// Handle tray icon messages in window proc
-pub fn handle_tray_message(lparam: LPARAM, hwnd: HWND) -> Option<LRESULT> {
+///
+/// A left click toggles pause and a double-click opens the settings window;
+/// both are delivered to `hwnd` as menu commands.
+pub fn handle_tray_message(
+    lparam: LPARAM,
+    hwnd: HWND,
+    status: &str,
+    paused: bool,
+    kept: &[String],
+) -> Option<LRESULT> {
     let message = (lparam & 0xFFFF) as u32;
     match message {
         WM_RBUTTONUP => {
-            show_context_menu(hwnd);
+            show_context_menu(hwnd, status, paused, kept);
+            Some(0)
+        }
+        WM_LBUTTONUP => {
+            if !SKIP_NEXT_CLICK.swap(false, Ordering::SeqCst) {
+                unsafe {
+                    SetTimer(hwnd, CLICK_TIMER_ID, GetDoubleClickTime(), Some(click_timer_proc));
+                }
+            }
+            Some(0)
+        }
+        WM_LBUTTONDBLCLK => {
+            SKIP_NEXT_CLICK.store(true, Ordering::SeqCst);
+            unsafe {
+                KillTimer(hwnd, CLICK_TIMER_ID);
+                PostMessageW(hwnd, WM_COMMAND, IDM_SETTINGS, 0);
+            }
             Some(0)
         }
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_tooltip_shortens_the_name_to_keep_the_status() {
+        let status = "Taskbar hidden; reveal: hotkey or edge hover";
+        assert_eq!(fit_tooltip("Hider", status), format!("Hider - {}", status));
+        let text = fit_tooltip(&"x".repeat(200), status);
+        assert_eq!(text.encode_utf16().count(), 127);
+        assert!(text.ends_with(status));
+    }
+}
@@ -0,0 +1,162 @@
+//! Virtual desktop module
+//!
+//! Watches the registry values Explorer updates when the user switches virtual
+//! desktops, since Explorer may re-show the taskbar on the new desktop.
+
+use std::ptr::{null, null_mut};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use windows_sys::Win32::Foundation::{CloseHandle, ERROR_SUCCESS, HANDLE, HWND, WAIT_OBJECT_0};
+use windows_sys::Win32::System::Registry::{
+    RegCloseKey, RegNotifyChangeKeyValue, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, KEY_NOTIFY,
+    REG_NOTIFY_CHANGE_LAST_SET,
+};
+use windows_sys::Win32::System::Threading::{
+    CreateEventW, SetEvent, WaitForMultipleObjects, INFINITE,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_USER};
+use taskbar_hider::winapi::Error;
+
+/// Posted to the main window after the current virtual desktop changes
+pub const WM_VIRTUAL_DESKTOP_CHANGED: u32 = WM_USER + 160;
+
+/// Keys holding `CurrentVirtualDesktop`: Windows 11 keeps it in the first,
+/// Windows 10 in a per-session subkey of the second
+const WATCHED_KEYS: [(&str, bool); 2] = [
+    (r"Software\Microsoft\Windows\CurrentVersion\Explorer\VirtualDesktops", false),
+    (r"Software\Microsoft\Windows\CurrentVersion\Explorer\SessionInfo", true),
+];
+
+/// Watcher thread and the event that tells it to stop
+static WATCHER: Mutex<Option<(JoinHandle<()>, usize)>> = Mutex::new(None);
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// A watched registry key and the event signalled when it changes
+struct WatchedKey {
+    key: HKEY,
+    subtree: bool,
+    event: HANDLE,
+}
+
+// Registry keys and events may be used from any thread
+unsafe impl Send for WatchedKey {}
+
+impl WatchedKey {
+    /// Opens a key under HKCU for change notification
+    fn open(path: &str, subtree: bool) -> Option<WatchedKey> {
+        let path = wide_string(path);
+        unsafe {
+            let mut key: HKEY = null_mut();
+            if RegOpenKeyExW(HKEY_CURRENT_USER, path.as_ptr(), 0, KEY_NOTIFY, &mut key)
+                != ERROR_SUCCESS
+            {
+                return None;
+            }
+            let event = CreateEventW(null(), 0, 0, null());
+            if event.is_null() {
+                RegCloseKey(key);
+                return None;
+            }
+            Some(WatchedKey { key, subtree, event })
+        }
+    }
+
+    /// Asks for the event to be signalled on the next change
+    fn arm(&self) {
+        unsafe {
+            RegNotifyChangeKeyValue(
+                self.key,
+                self.subtree as i32,
+                REG_NOTIFY_CHANGE_LAST_SET,
+                self.event,
+                1,
+            );
+        }
+    }
+}
+
+impl Drop for WatchedKey {
+    fn drop(&mut self) {
+        unsafe {
+            RegCloseKey(self.key);
+            CloseHandle(self.event);
+        }
+    }
+}
+
+/// Waits for changes to the watched keys until `stop_event` is signalled
+fn watch(keys: Vec<WatchedKey>, stop_event: HANDLE, notify_hwnd: HWND) {
+    let mut handles: Vec<HANDLE> = keys.iter().map(|k| k.event).collect();
+    handles.push(stop_event);
+
+    for key in &keys {
+        key.arm();
+    }
+    loop {
+        let signalled =
+            unsafe { WaitForMultipleObjects(handles.len() as u32, handles.as_ptr(), 0, INFINITE) };
+        let Some(key) = keys.get(signalled.wrapping_sub(WAIT_OBJECT_0) as usize) else {
+            // The stop event, or a failed wait
+            return;
+        };
+        // Notifications are one-shot; re-arm before reporting so none are missed
+        key.arm();
+        unsafe {
+            PostMessageW(notify_hwnd, WM_VIRTUAL_DESKTOP_CHANGED, 0, 0);
+        }
+    }
+}
+
+/// Starts watching for virtual desktop switches on a background thread
+pub fn start(notify_hwnd: HWND) -> Result<(), Error> {
+    let keys: Vec<WatchedKey> = WATCHED_KEYS
+        .iter()
+        .filter_map(|&(path, subtree)| WatchedKey::open(path, subtree))
+        .collect();
+    if keys.is_empty() {
+        return Err(Error::Failed("Virtual desktop registry keys not found"));
+    }
+
+    let stop_event = unsafe { CreateEventW(null(), 1, 0, null()) };
+    if stop_event.is_null() {
+        return Err(Error::last("Failed to create virtual desktop stop event"));
+    }
+
+    // Raw handles aren't Send; pass them across as integers
+    let (stop, hwnd) = (stop_event as usize, notify_hwnd as usize);
+    let watcher = std::thread::Builder::new()
+        .name("vdesktop".to_string())
+        .spawn(move || watch(keys, stop as HANDLE, hwnd as HWND));
+    let watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(_) => {
+            unsafe {
+                CloseHandle(stop_event);
+            }
+            return Err(Error::Failed("Failed to start virtual desktop thread"));
+        }
+    };
+
+    if let Ok(mut guard) = WATCHER.lock() {
+        *guard = Some((watcher, stop));
+    }
+    Ok(())
+}
+
+/// Stops the watcher and waits for its thread to exit
+pub fn stop() {
+    let Some((watcher, stop)) = WATCHER.lock().ok().and_then(|mut guard| guard.take()) else {
+        return;
+    };
+    unsafe {
+        SetEvent(stop as HANDLE);
+    }
+    let _ = watcher.join();
+    unsafe {
+        CloseHandle(stop as HANDLE);
+    }
+}
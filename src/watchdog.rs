@@ -0,0 +1,75 @@
+//! External watchdog module
+//!
+//! A second copy of the hider, started with `--watchdog`, that puts the taskbar
+//! back if the main process is killed or stops responding. The main process
+//! proves it is alive by rewriting the recovery marker on a timer.
+
+use windows_sys::Win32::Foundation::{CloseHandle, WAIT_TIMEOUT};
+use windows_sys::Win32::System::Threading::{
+    OpenProcess, WaitForSingleObject, PROCESS_SYNCHRONIZE,
+};
+use crate::{autostart, commands, recovery};
+use taskbar_hider::winapi::Error;
+
+/// Command-line flag that runs the watchdog, followed by the watched process ID and
+/// the hang threshold in milliseconds
+pub const WATCHDOG_FLAG: &str = "--watchdog";
+/// How often the main process rewrites the marker
+pub const HEARTBEAT_INTERVAL_MS: u32 = 5000;
+/// How often the watchdog checks on the main process
+const CHECK_INTERVAL_MS: u32 = 1000;
+
+/// Starts a watchdog for this process, restoring the taskbar after `hang_ms`
+/// without a heartbeat
+pub fn spawn(hang_ms: u64) -> Result<(), Error> {
+    let exe =
+        std::env::current_exe().map_err(|_| Error::Failed("Failed to get executable path"))?;
+    let flags =
+        [WATCHDOG_FLAG.to_string(), std::process::id().to_string(), hang_ms.to_string()];
+    commands::spawn(&autostart::command_line(&exe.display().to_string(), &flags))
+}
+
+/// Runs the watchdog if the command line asks for one, returning false otherwise
+pub fn run_if_requested(args: &[String]) -> bool {
+    let Some(index) = args.iter().position(|arg| arg == WATCHDOG_FLAG) else {
+        return false;
+    };
+    let pid = args.get(index + 1).and_then(|pid| pid.parse().ok());
+    let hang_ms = args.get(index + 2).and_then(|ms| ms.parse().ok());
+    if let (Some(pid), Some(hang_ms)) = (pid, hang_ms) {
+        watch(pid, hang_ms);
+    }
+    true
+}
+
+/// Waits for the process to exit or hang, restoring the taskbar if it didn't clean up
+fn watch(pid: u32, hang_ms: u64) {
+    let process = unsafe { OpenProcess(PROCESS_SYNCHRONIZE, 0, pid) };
+    if process.is_null() {
+        return;
+    }
+    // Counted in checks rather than by the clock, so a sleep doesn't look like a hang
+    let mut last_heartbeat = recovery::marker_written_at();
+    let mut silent_ms = 0;
+    loop {
+        if unsafe { WaitForSingleObject(process, CHECK_INTERVAL_MS) } != WAIT_TIMEOUT {
+            // A clean exit removed the marker, so this only acts after a kill or crash
+            recovery::restore_if_stale();
+            break;
+        }
+        let heartbeat = recovery::marker_written_at();
+        if heartbeat != last_heartbeat {
+            last_heartbeat = heartbeat;
+            silent_ms = 0;
+            continue;
+        }
+        silent_ms += u64::from(CHECK_INTERVAL_MS);
+        if heartbeat.is_some() && silent_ms >= hang_ms {
+            recovery::restore_for_hung_instance();
+            break;
+        }
+    }
+    unsafe {
+        CloseHandle(process);
+    }
+}
@@ -0,0 +1,402 @@
+//! Win32 API module
+//!
+//! The window and shell calls the taskbar control is built on, behind a trait
+//! so the control logic can be exercised without a real desktop.
+
+use windows_sys::Win32::Foundation::{GetLastError, ERROR_HOTKEY_ALREADY_REGISTERED, HWND, RECT};
+use windows_sys::Win32::System::Diagnostics::Debug::{
+    FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+};
+use windows_sys::Win32::Graphics::Gdi::{
+    GetMonitorInfoW, MonitorFromWindow, HMONITOR, MONITORINFO, MONITORINFOEXW,
+    MONITOR_DEFAULTTONEAREST,
+};
+use windows_sys::Win32::UI::Shell::{SHAppBarMessage, APPBARDATA};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    FindWindowExW, GetLayeredWindowAttributes, GetWindowLongPtrW, GetWindowRect, IsWindow,
+    IsWindowVisible, SetLayeredWindowAttributes, SetWindowLongPtrW, SetWindowPos, ShowWindow,
+    GWL_EXSTYLE, LWA_ALPHA, SHOW_WINDOW_CMD, SWP_NOACTIVATE, SWP_NOSIZE, SWP_NOZORDER,
+};
+
+/// Encodes a string as a null-terminated wide string
+fn wide_string(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// A failed Win32 call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The call failed with an error code, from `GetLastError` or returned as a status
+    Win32 { context: &'static str, code: u32 },
+    /// The call failed without setting an error code
+    Failed(&'static str),
+}
+
+impl Error {
+    /// Captures the calling thread's last error for a call that just failed
+    pub fn last(context: &'static str) -> Error {
+        Error::Win32 { context, code: unsafe { GetLastError() } }
+    }
+
+    /// Wraps a status code returned by a call such as the registry functions
+    pub fn status(context: &'static str, code: u32) -> Error {
+        Error::Win32 { context, code }
+    }
+
+    /// Returns the Win32 error code, if the call set one
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            Error::Win32 { code, .. } => Some(*code),
+            Error::Failed(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Win32 { context, code: ERROR_HOTKEY_ALREADY_REGISTERED } => {
+                write!(f, "{}: another application already uses it", context)
+            }
+            Error::Win32 { context, code } => {
+                write!(f, "{} (error {}: {})", context, code, system_message(*code))
+            }
+            Error::Failed(context) => f.write_str(context),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for String {
+    fn from(error: Error) -> String {
+        error.to_string()
+    }
+}
+
+/// Returns the system's description of a Win32 error code
+fn system_message(code: u32) -> String {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            std::ptr::null(),
+        )
+    } as usize;
+    String::from_utf16_lossy(&buffer[..len]).trim_end().to_string()
+}
+
+/// Window and shell calls used to find, hide and show the taskbars
+pub trait WinApi {
+    /// Finds the next top-level window of `class` after `after`, or the first for a null handle
+    fn find_window(&self, class: &str, after: HWND) -> HWND;
+    /// Shows or hides a window with an `SW_*` command
+    fn show_window(&self, hwnd: HWND, cmd: SHOW_WINDOW_CMD);
+    /// Sends an `ABM_*` message to the shell, as `SHAppBarMessage` does
+    fn app_bar_message(&self, msg: u32, data: &mut APPBARDATA) -> usize;
+    /// Returns true if the handle still names a window
+    fn is_window(&self, hwnd: HWND) -> bool;
+    /// Returns true if the window has the visible style
+    fn is_window_visible(&self, hwnd: HWND) -> bool;
+    /// Returns the window's rect in screen coordinates
+    fn window_rect(&self, hwnd: HWND) -> Option<RECT>;
+    /// Moves a window without resizing or activating it
+    fn move_window(&self, hwnd: HWND, pos: (i32, i32));
+    /// Returns the monitor the window is on, or the nearest one
+    fn monitor_of(&self, hwnd: HWND) -> HMONITOR;
+    /// Returns the device name of the window's monitor without the `\\.\` prefix
+    fn monitor_device_name(&self, hwnd: HWND) -> Option<String>;
+    /// Returns the window's extended style
+    fn ex_style(&self, hwnd: HWND) -> isize;
+    /// Replaces the window's extended style
+    fn set_ex_style(&self, hwnd: HWND, ex_style: isize);
+    /// Returns the opacity of a layered window, if it has one set
+    fn layered_alpha(&self, hwnd: HWND) -> Option<u8>;
+    /// Sets the opacity of a layered window
+    fn set_layered_alpha(&self, hwnd: HWND, alpha: u8);
+}
+
+/// The real Win32 API
+#[derive(Clone, Copy, Default)]
+pub struct SystemApi;
+
+// Window handles are checked by Win32 rather than dereferenced
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+impl WinApi for SystemApi {
+    fn find_window(&self, class: &str, after: HWND) -> HWND {
+        let class_name = wide_string(class);
+        unsafe {
+            FindWindowExW(std::ptr::null_mut(), after, class_name.as_ptr(), std::ptr::null())
+        }
+    }
+
+    fn show_window(&self, hwnd: HWND, cmd: SHOW_WINDOW_CMD) {
+        unsafe {
+            ShowWindow(hwnd, cmd);
+        }
+    }
+
+    fn app_bar_message(&self, msg: u32, data: &mut APPBARDATA) -> usize {
+        unsafe { SHAppBarMessage(msg, data) }
+    }
+
+    fn is_window(&self, hwnd: HWND) -> bool {
+        unsafe { IsWindow(hwnd) != 0 }
+    }
+
+    fn is_window_visible(&self, hwnd: HWND) -> bool {
+        unsafe { IsWindowVisible(hwnd) != 0 }
+    }
+
+    fn window_rect(&self, hwnd: HWND) -> Option<RECT> {
+        unsafe {
+            let mut rect: RECT = std::mem::zeroed();
+            (GetWindowRect(hwnd, &mut rect) != 0).then_some(rect)
+        }
+    }
+
+    fn move_window(&self, hwnd: HWND, (x, y): (i32, i32)) {
+        unsafe {
+            SetWindowPos(
+                hwnd,
+                std::ptr::null_mut(),
+                x,
+                y,
+                0,
+                0,
+                SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    fn monitor_of(&self, hwnd: HWND) -> HMONITOR {
+        unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) }
+    }
+
+    fn monitor_device_name(&self, hwnd: HWND) -> Option<String> {
+        unsafe {
+            let monitor = self.monitor_of(hwnd);
+            let mut info: MONITORINFOEXW = std::mem::zeroed();
+            info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+            if GetMonitorInfoW(monitor, &mut info as *mut _ as *mut MONITORINFO) == 0 {
+                return None;
+            }
+            let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+            let name = String::from_utf16_lossy(&info.szDevice[..len]);
+            Some(name.trim_start_matches(['\\', '.']).to_ascii_uppercase())
+        }
+    }
+
+    fn ex_style(&self, hwnd: HWND) -> isize {
+        unsafe { GetWindowLongPtrW(hwnd, GWL_EXSTYLE) }
+    }
+
+    fn set_ex_style(&self, hwnd: HWND, ex_style: isize) {
+        unsafe {
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+        }
+    }
+
+    fn layered_alpha(&self, hwnd: HWND) -> Option<u8> {
+        let mut alpha = 255u8;
+        let mut flags = 0;
+        let ok = unsafe {
+            GetLayeredWindowAttributes(hwnd, std::ptr::null_mut(), &mut alpha, &mut flags) != 0
+        };
+        (ok && flags & LWA_ALPHA != 0).then_some(alpha)
+    }
+
+    fn set_layered_alpha(&self, hwnd: HWND, alpha: u8) {
+        unsafe {
+            SetLayeredWindowAttributes(hwnd, 0, alpha, LWA_ALPHA);
+        }
+    }
+}
+
+/// A scripted desktop for tests
+/// A fake desktop for the unit tests and the integration tests under `tests/`
+pub mod mock {
+    use super::WinApi;
+    use std::cell::RefCell;
+    use windows_sys::Win32::Foundation::{HWND, RECT};
+    use windows_sys::Win32::Graphics::Gdi::HMONITOR;
+    use windows_sys::Win32::UI::Shell::{
+        ABE_BOTTOM, ABM_GETSTATE, ABM_GETTASKBARPOS, ABM_SETSTATE, ABS_AUTOHIDE, APPBARDATA,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{SHOW_WINDOW_CMD, SW_HIDE};
+
+    /// A fake top-level window
+    #[derive(Clone)]
+    pub struct MockWindow {
+        pub hwnd: HWND,
+        pub class: &'static str,
+        pub monitor: &'static str,
+        pub rect: RECT,
+        pub visible: bool,
+        pub ex_style: isize,
+        pub alpha: Option<u8>,
+        pub destroyed: bool,
+    }
+
+    /// Returns the fake handle of a monitor, e.g. 2 for `DISPLAY2`
+    pub fn monitor_handle(name: &str) -> HMONITOR {
+        let index = name.trim_start_matches("DISPLAY").parse::<usize>().unwrap_or(0);
+        index as HMONITOR
+    }
+
+    /// Records every change made through the API to a set of fake windows
+    #[derive(Default)]
+    pub struct MockApi {
+        pub windows: RefCell<Vec<MockWindow>>,
+        pub autohide: RefCell<bool>,
+    }
+
+    impl MockApi {
+        /// Creates a desktop with a primary taskbar and `secondary` more bars, all visible
+        pub fn with_taskbars(secondary: usize) -> MockApi {
+            let api = MockApi::default();
+            for i in 0..=secondary {
+                let class = if i == 0 { "Shell_TrayWnd" } else { "Shell_SecondaryTrayWnd" };
+                let monitor = ["DISPLAY1", "DISPLAY2", "DISPLAY3", "DISPLAY4"][i];
+                let left = 1920 * i as i32;
+                api.windows.borrow_mut().push(MockWindow {
+                    hwnd: (i + 1) as HWND,
+                    class,
+                    monitor,
+                    rect: RECT { left, top: 1040, right: left + 1920, bottom: 1080 },
+                    visible: true,
+                    ex_style: 0,
+                    alpha: None,
+                    destroyed: false,
+                });
+            }
+            api
+        }
+
+        /// Returns a copy of the fake window with the given handle
+        pub fn window(&self, hwnd: HWND) -> MockWindow {
+            self.windows.borrow().iter().find(|w| w.hwnd == hwnd).unwrap().clone()
+        }
+
+        /// Returns true if the window with the given handle is visible
+        pub fn is_visible(&self, hwnd: HWND) -> bool {
+            self.window(hwnd).visible
+        }
+
+        /// Destroys a window, as Explorer does when it restarts
+        pub fn destroy(&self, hwnd: HWND) {
+            self.update(hwnd, |w| w.destroyed = true);
+        }
+
+        fn update(&self, hwnd: HWND, f: impl FnOnce(&mut MockWindow)) {
+            if let Some(w) = self.windows.borrow_mut().iter_mut().find(|w| w.hwnd == hwnd) {
+                f(w);
+            }
+        }
+
+        fn live(&self, hwnd: HWND) -> Option<MockWindow> {
+            self.windows.borrow().iter().find(|w| w.hwnd == hwnd && !w.destroyed).cloned()
+        }
+    }
+
+    impl WinApi for MockApi {
+        fn find_window(&self, class: &str, after: HWND) -> HWND {
+            let windows = self.windows.borrow();
+            let mut matching = windows.iter().filter(|w| w.class == class && !w.destroyed);
+            let found = if after.is_null() {
+                matching.next()
+            } else {
+                matching.skip_while(|w| w.hwnd != after).nth(1)
+            };
+            found.map_or(std::ptr::null_mut(), |w| w.hwnd)
+        }
+
+        fn show_window(&self, hwnd: HWND, cmd: SHOW_WINDOW_CMD) {
+            self.update(hwnd, |w| w.visible = cmd != SW_HIDE);
+        }
+
+        fn app_bar_message(&self, msg: u32, data: &mut APPBARDATA) -> usize {
+            match msg {
+                ABM_GETSTATE if *self.autohide.borrow() => ABS_AUTOHIDE as usize,
+                ABM_GETSTATE => 0,
+                ABM_SETSTATE => {
+                    *self.autohide.borrow_mut() = data.lParam & ABS_AUTOHIDE as isize != 0;
+                    1
+                }
+                ABM_GETTASKBARPOS => match self.live(data.hWnd) {
+                    Some(w) => {
+                        data.rc = w.rect;
+                        data.uEdge = ABE_BOTTOM;
+                        1
+                    }
+                    None => 0,
+                },
+                _ => 0,
+            }
+        }
+
+        fn is_window(&self, hwnd: HWND) -> bool {
+            self.live(hwnd).is_some()
+        }
+
+        fn is_window_visible(&self, hwnd: HWND) -> bool {
+            self.live(hwnd).is_some_and(|w| w.visible)
+        }
+
+        fn window_rect(&self, hwnd: HWND) -> Option<RECT> {
+            self.live(hwnd).map(|w| w.rect)
+        }
+
+        fn move_window(&self, hwnd: HWND, (x, y): (i32, i32)) {
+            self.update(hwnd, |w| {
+                w.rect = RECT {
+                    left: x,
+                    top: y,
+                    right: x + w.rect.right - w.rect.left,
+                    bottom: y + w.rect.bottom - w.rect.top,
+                }
+            });
+        }
+
+        fn monitor_of(&self, hwnd: HWND) -> HMONITOR {
+            self.live(hwnd).map_or(std::ptr::null_mut(), |w| monitor_handle(w.monitor))
+        }
+
+        fn monitor_device_name(&self, hwnd: HWND) -> Option<String> {
+            self.live(hwnd).map(|w| w.monitor.to_string())
+        }
+
+        fn ex_style(&self, hwnd: HWND) -> isize {
+            self.live(hwnd).map_or(0, |w| w.ex_style)
+        }
+
+        fn set_ex_style(&self, hwnd: HWND, ex_style: isize) {
+            self.update(hwnd, |w| w.ex_style = ex_style);
+        }
+
+        fn layered_alpha(&self, hwnd: HWND) -> Option<u8> {
+            self.live(hwnd).and_then(|w| w.alpha)
+        }
+
+        fn set_layered_alpha(&self, hwnd: HWND, alpha: u8) {
+            self.update(hwnd, |w| w.alpha = Some(alpha));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_keeps_the_code() {
+        let error = Error::status("Failed to write autostart entry", 5);
+        assert_eq!(error.code(), Some(5));
+        assert_eq!(Error::Failed("Failed to add tray icon").code(), None);
+    }
+}
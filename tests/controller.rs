@@ -0,0 +1,148 @@
+//! Integration tests driving the taskbar controller and reveal state together
+//! through the mock desktop, the way the binary's message loop does.
+
+use taskbar_hider::reveal::{Decision, RevealState};
+use taskbar_hider::taskbar::{self, TaskbarController};
+use taskbar_hider::winapi::mock::{monitor_handle, MockApi};
+use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::Graphics::Gdi::HMONITOR;
+
+/// The binary's visibility handling, minus its timers and hooks
+struct Hider {
+    taskbar: TaskbarController<MockApi>,
+    reveal: RevealState,
+    revealed: Vec<HWND>,
+    hover_monitor: HMONITOR,
+}
+
+impl Hider {
+    fn new(secondary: usize) -> Hider {
+        let mut taskbar = TaskbarController::new(MockApi::with_taskbars(secondary));
+        taskbar.init().unwrap();
+        let mut reveal = RevealState::new();
+        reveal.reveal_delay_ms = 400;
+        reveal.min_visible_ms = 0;
+        Hider { taskbar, reveal, revealed: Vec::new(), hover_monitor: std::ptr::null_mut() }
+    }
+
+    /// Returns the bars on the hovered monitor, or all of them if none is there
+    fn hovered(&self) -> Vec<HWND> {
+        let api = self.taskbar.api();
+        let hwnds = taskbar::on_monitor(api, self.taskbar.hwnds(), self.hover_monitor);
+        if hwnds.is_empty() {
+            self.taskbar.hwnds().to_vec()
+        } else {
+            hwnds
+        }
+    }
+
+    fn update(&mut self, now: u64) {
+        match self.reveal.evaluate(now, false) {
+            Decision::Show { all } if self.reveal.is_visible() => {
+                let wanted = if all { self.taskbar.hwnds().to_vec() } else { self.hovered() };
+                let missing: Vec<HWND> =
+                    wanted.into_iter().filter(|hwnd| !self.revealed.contains(hwnd)).collect();
+                self.taskbar.show_bars(&missing);
+                self.revealed.extend(missing);
+            }
+            Decision::Show { all } => {
+                self.revealed = if all { self.taskbar.hwnds().to_vec() } else { self.hovered() };
+                self.taskbar.show_bars(&self.revealed);
+                self.reveal.shown(now);
+            }
+            Decision::Hide => {
+                self.taskbar.hide();
+                self.revealed.clear();
+                self.reveal.hidden();
+            }
+            Decision::Unchanged | Decision::HideAfter(_) => {}
+        }
+    }
+
+    fn hover(&mut self, monitor: &str, now: u64) {
+        self.hover_monitor = monitor_handle(monitor);
+        self.reveal.edge_hover_active = true;
+        self.reveal.cancel_release_delay();
+        self.update(now);
+    }
+
+    fn visible(&self) -> Vec<HWND> {
+        let api = self.taskbar.api();
+        self.taskbar.hwnds().iter().copied().filter(|&hwnd| api.is_visible(hwnd)).collect()
+    }
+}
+
+#[test]
+fn a_hotkey_hold_reveals_every_bar_until_the_delay_ends() {
+    let mut hider = Hider::new(1);
+    assert!(hider.visible().is_empty());
+
+    hider.reveal.press();
+    hider.update(1000);
+    assert_eq!(hider.visible(), [1 as HWND, 2 as HWND]);
+
+    hider.reveal.release(1500);
+    hider.update(1600);
+    assert_eq!(hider.visible().len(), 2);
+    hider.update(1900);
+    assert!(hider.visible().is_empty());
+}
+
+#[test]
+fn a_per_monitor_hover_follows_the_cursor_to_the_next_monitor() {
+    let mut hider = Hider::new(2);
+    hider.reveal.per_monitor_reveal = true;
+
+    hider.hover("DISPLAY2", 1000);
+    assert_eq!(hider.visible(), [2 as HWND]);
+
+    hider.hover("DISPLAY3", 1100);
+    assert_eq!(hider.visible(), [2 as HWND, 3 as HWND]);
+    assert_eq!(hider.revealed, [2 as HWND, 3 as HWND]);
+
+    hider.reveal.edge_hover_active = false;
+    hider.reveal.start_release_delay(1200);
+    hider.update(1700);
+    assert!(hider.visible().is_empty());
+}
+
+#[test]
+fn a_hotkey_hold_widens_a_per_monitor_hover() {
+    let mut hider = Hider::new(1);
+    hider.reveal.per_monitor_reveal = true;
+
+    hider.hover("DISPLAY1", 1000);
+    assert_eq!(hider.visible(), [1 as HWND]);
+    hider.reveal.press();
+    hider.update(1100);
+    assert_eq!(hider.visible(), [1 as HWND, 2 as HWND]);
+}
+
+#[test]
+fn a_hover_without_per_monitor_reveal_shows_every_bar() {
+    let mut hider = Hider::new(1);
+    hider.hover("DISPLAY2", 1000);
+    assert_eq!(hider.visible(), [1 as HWND, 2 as HWND]);
+}
+
+#[test]
+fn a_hover_on_a_kept_monitor_falls_back_to_every_bar() {
+    let mut hider = Hider::new(2);
+    hider.taskbar.set_always_visible(vec!["DISPLAY3".to_string()]);
+    hider.taskbar.init().unwrap();
+    hider.reveal.per_monitor_reveal = true;
+
+    hider.hover("DISPLAY3", 1000);
+    assert_eq!(hider.visible(), [1 as HWND, 2 as HWND]);
+}
+
+#[test]
+fn a_recreated_taskbar_is_picked_up_again() {
+    let mut hider = Hider::new(1);
+    hider.taskbar.api().destroy(2 as HWND);
+    assert!(hider.taskbar.refresh());
+
+    hider.reveal.press();
+    hider.update(1000);
+    assert_eq!(hider.visible(), [1 as HWND]);
+}